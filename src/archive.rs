@@ -0,0 +1,193 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use anyhow::{bail, Context as _};
+use cargo::core::{Package, PackageId, PackageSet, SourceId};
+use cargo::sources::SourceConfigMap;
+use cargo::util::CargoResult;
+use cargo::GlobalContext;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// For a given file at path `path`, generate the SHA256 sum
+pub(crate) fn file_sha256<P: AsRef<Path>>(path: P) -> Result<String, io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Verifies that the archive at `path` matches the sha256 `expected` by
+/// Cargo.lock for `pkg`
+fn verify(path: &Path, pkg: PackageId, expected: &str) -> CargoResult<()> {
+    let actual = file_sha256(path)
+        .with_context(|| format!("Unable to read crate archive '{}'", path.display()))?;
+    if actual != expected {
+        bail!(
+            "checksum mismatch for {} {}\n\
+             Cargo.lock expects sha256 {}\n\
+             but the cached archive '{}' has sha256 {}\n\
+             the local cache is likely poisoned, remove the file and try again",
+            pkg.name(),
+            pkg.version(),
+            expected,
+            path.display(),
+            actual
+        );
+    }
+    Ok(())
+}
+
+/// Locates the `.crate` archives cargo caches for registry packages
+/// and verifies them against Cargo.lock. Every access to a crate archive
+/// must go through here so a corrupted mirror never ends up in a recipe.
+pub struct ArchiveCache<'cfg> {
+    config: &'cfg GlobalContext,
+    sources: SourceConfigMap<'cfg>,
+    /// registry sources after source replacement (sparse index, mirrors)
+    replaced: HashMap<SourceId, SourceId>,
+}
+
+impl<'cfg> ArchiveCache<'cfg> {
+    pub fn new(config: &'cfg GlobalContext) -> CargoResult<Self> {
+        Ok(Self {
+            config,
+            sources: SourceConfigMap::new(config)?,
+            replaced: HashMap::new(),
+        })
+    }
+
+    /// The locations cargo may have cached the downloaded `.crate` archive
+    /// of a registry package at. The cache directory is named after the
+    /// registry host plus a hash whose scheme has changed between cargo
+    /// releases, so every directory for the host is considered.
+    fn cached_paths(&mut self, pkg: PackageId) -> CargoResult<Vec<PathBuf>> {
+        let src_id = match self.replaced.get(&pkg.source_id()) {
+            Some(src_id) => *src_id,
+            None => {
+                let src_id = self
+                    .sources
+                    .load(pkg.source_id(), &HashSet::new())?
+                    .replaced_source_id();
+                self.replaced.insert(pkg.source_id(), src_id);
+                src_id
+            }
+        };
+        let prefix = format!("{}-", src_id.url().host_str().unwrap_or(""));
+        let file_name = format!("{}-{}.crate", pkg.name(), pkg.version());
+
+        let cache = self.config.registry_cache_path().into_path_unlocked();
+        let entries = match fs::read_dir(&cache) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(vec![]),
+        };
+        let mut paths = entries
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+            .map(|entry| entry.path().join(&file_name))
+            .filter(|path| path.exists())
+            .collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Verifies the cached `.crate` archive of `pkg` against the sha256
+    /// `expected` by Cargo.lock, returning the path of the archive if it
+    /// is present in the cache
+    pub fn verify_cached(
+        &mut self,
+        pkg: PackageId,
+        expected: &str,
+    ) -> CargoResult<Option<PathBuf>> {
+        let paths = self.cached_paths(pkg)?;
+        for path in &paths {
+            verify(path, pkg, expected)?;
+        }
+        Ok(paths.into_iter().next())
+    }
+
+    /// Verifies the cached archives of `pkgs` against `checksums`, the
+    /// ones of Cargo.lock, skipping the packages without a checksum
+    fn verify_all(
+        &mut self,
+        pkgs: &[PackageId],
+        checksums: &HashMap<PackageId, Option<String>>,
+    ) -> CargoResult<()> {
+        for pkg in pkgs {
+            if let Some(Some(expected)) = checksums.get(pkg) {
+                self.verify_cached(*pkg, expected)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets `pkg` from `package_set`, downloading it unless cargo has it
+    /// already, with its archive verified against `checksums` both before
+    /// it is unpacked and after it is downloaded
+    pub fn get_one<'a>(
+        &mut self,
+        package_set: &'a PackageSet<'_>,
+        pkg: PackageId,
+        checksums: &HashMap<PackageId, Option<String>>,
+    ) -> CargoResult<&'a Package> {
+        Ok(self.get_many(package_set, &[pkg], checksums)?.remove(0))
+    }
+
+    /// Gets `pkgs` from `package_set` like `get_one`, downloading them
+    /// in parallel
+    pub fn get_many<'a>(
+        &mut self,
+        package_set: &'a PackageSet<'_>,
+        pkgs: &[PackageId],
+        checksums: &HashMap<PackageId, Option<String>>,
+    ) -> CargoResult<Vec<&'a Package>> {
+        self.verify_all(pkgs, checksums)?;
+        let packages = package_set.get_many(pkgs.iter().copied())?;
+        self.verify_all(pkgs, checksums)?;
+        Ok(packages)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    fn pkg() -> PackageId {
+        let src =
+            SourceId::crates_io_maybe_sparse_http(&GlobalContext::default().unwrap()).unwrap();
+        PackageId::try_new("foo", "1.0.0", src).unwrap()
+    }
+
+    #[test]
+    fn matching_checksum() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"hello").unwrap();
+        let expected = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(verify(file.path(), pkg(), expected).is_ok());
+    }
+
+    #[test]
+    fn mismatched_checksum() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"poisoned").unwrap();
+        let expected = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let err = verify(file.path(), pkg(), expected)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("foo 1.0.0"));
+        assert!(err.contains(expected));
+        assert!(err.contains(&file.path().display().to_string()));
+    }
+}
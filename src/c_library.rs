@@ -0,0 +1,121 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Packaging the C libraries built from `cdylib` and `staticlib` targets.
+
+use cargo::core::Package;
+
+/// class building and installing C libraries with cargo-c
+pub const CARGO_C_CLASS: &str = "cargo_c";
+
+/// The C library a package builds
+#[derive(Debug, Eq, PartialEq)]
+pub struct CLibrary {
+    /// name of the package, which cargo-c names the headers and the
+    /// pkg-config file after
+    pub package: String,
+    /// name of the library target, `lib<name>.so` and `lib<name>.a`
+    pub lib: String,
+    pub cdylib: bool,
+    pub staticlib: bool,
+    /// whether the package has binaries, too
+    pub has_bin: bool,
+}
+
+impl CLibrary {
+    /// the C library `pkg` builds, if any
+    pub fn of(pkg: &Package) -> Option<Self> {
+        let lib = pkg.targets().iter().find(|target| target.is_lib())?;
+        if !lib.is_cdylib() && !lib.is_staticlib() {
+            return None;
+        }
+        Some(Self {
+            package: pkg.name().to_string(),
+            lib: lib.crate_name(),
+            cdylib: lib.is_cdylib(),
+            staticlib: lib.is_staticlib(),
+            has_bin: pkg.targets().iter().any(|target| target.is_bin()),
+        })
+    }
+
+    /// the FILES of the packages the artifacts of the library go to, as
+    /// cargo-c installs them
+    pub fn files(&self, sep: &str) -> String {
+        let mut files = format!(
+            "# C library built from the {} crate types of {}\n",
+            match (self.cdylib, self.staticlib) {
+                (true, true) => "cdylib and staticlib",
+                (true, false) => "cdylib",
+                _ => "staticlib",
+            },
+            self.package
+        );
+        if self.cdylib {
+            files.push_str(&format!(
+                "FILES{}${{PN}} += \"${{libdir}}/lib{}.so.*\"\n",
+                sep, self.lib
+            ));
+        }
+        files.push_str(&format!(
+            "FILES{}${{PN}}-dev += \"${{includedir}}/{}{} ${{libdir}}/pkgconfig/{}.pc\"\n",
+            sep,
+            self.package,
+            if self.cdylib {
+                format!(" ${{libdir}}/lib{}.so", self.lib)
+            } else {
+                String::new()
+            },
+            self.package
+        ));
+        if self.staticlib {
+            files.push_str(&format!(
+                "FILES{}${{PN}}-staticdev += \"${{libdir}}/lib{}.a\"\n",
+                sep, self.lib
+            ));
+        }
+        files
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn library(cdylib: bool, staticlib: bool) -> CLibrary {
+        CLibrary {
+            package: "foo-ffi".into(),
+            lib: "foo_ffi".into(),
+            cdylib,
+            staticlib,
+            has_bin: false,
+        }
+    }
+
+    #[test]
+    fn cdylib_and_staticlib() {
+        assert_eq!(
+            library(true, true).files(":"),
+            "# C library built from the cdylib and staticlib crate types of foo-ffi\n\
+             FILES:${PN} += \"${libdir}/libfoo_ffi.so.*\"\n\
+             FILES:${PN}-dev += \"${includedir}/foo-ffi ${libdir}/libfoo_ffi.so ${libdir}/pkgconfig/foo-ffi.pc\"\n\
+             FILES:${PN}-staticdev += \"${libdir}/libfoo_ffi.a\"\n"
+        );
+    }
+
+    #[test]
+    fn staticlib_legacy() {
+        assert_eq!(
+            library(false, true).files("_"),
+            "# C library built from the staticlib crate types of foo-ffi\n\
+             FILES_${PN}-dev += \"${includedir}/foo-ffi ${libdir}/pkgconfig/foo-ffi.pc\"\n\
+             FILES_${PN}-staticdev += \"${libdir}/libfoo_ffi.a\"\n"
+        );
+    }
+}
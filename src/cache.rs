@@ -0,0 +1,67 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! What previous runs computed, kept in TOML files in `~/.cargo/bitbake`.
+
+use anyhow::Context as _;
+use cargo::util::CargoResult;
+use cargo::GlobalContext;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// The entries of one cache file, by key
+pub struct Cache<V> {
+    path: PathBuf,
+    pub entries: BTreeMap<String, V>,
+}
+
+impl<V: Serialize + DeserializeOwned> Cache<V> {
+    /// loads the cache `file`, the `name` of which is warned about with
+    /// what happens `instead` if it is corrupt
+    pub fn load(
+        config: &GlobalContext,
+        file: &str,
+        name: &str,
+        instead: &str,
+    ) -> CargoResult<Self> {
+        let path = config
+            .home()
+            .join("bitbake")
+            .into_path_unlocked()
+            .join(file);
+        let entries = match fs::read_to_string(&path) {
+            Ok(contents) => match toml::from_str(&contents) {
+                Ok(entries) => entries,
+                Err(_) => {
+                    config.shell().warn(format!(
+                        "Ignoring the corrupt {} '{}', {}",
+                        name,
+                        path.display(),
+                        instead
+                    ))?;
+                    BTreeMap::new()
+                }
+            },
+            Err(_) => BTreeMap::new(),
+        };
+        Ok(Self { path, entries })
+    }
+
+    pub fn save(&self) -> CargoResult<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, toml::to_string(&self.entries)?)
+            .with_context(|| format!("Unable to write '{}'", self.path.display()))
+    }
+}
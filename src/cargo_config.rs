@@ -0,0 +1,112 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use cargo::util::context::{ConfigValue, Definition};
+use cargo::util::CargoResult;
+use cargo::GlobalContext;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+
+/// top level cargo configuration tables that change how dependencies are
+/// resolved or built
+const RELEVANT_TABLES: &[&str] = &["build", "env", "patch", "source", "target"];
+
+/// the cargo configuration files `values` were loaded from
+pub fn loaded_files(values: &HashMap<String, ConfigValue>) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    for value in values.values() {
+        collect_files(value, &mut files);
+    }
+    files
+}
+
+fn collect_files(value: &ConfigValue, files: &mut BTreeSet<PathBuf>) {
+    if let Definition::Path(path) = value.definition() {
+        files.insert(path.clone());
+    }
+    match value {
+        ConfigValue::Table(table, _) => {
+            for value in table.values() {
+                collect_files(value, files);
+            }
+        }
+        ConfigValue::List(list, _) => {
+            for (_, def) in list {
+                if let Definition::Path(path) = def {
+                    files.insert(path.clone());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// describes the loaded cargo configuration that influences the recipe,
+/// one line per config file or setting
+pub fn describe(config: &GlobalContext) -> CargoResult<Vec<String>> {
+    let values = config.values()?;
+    let mut lines = loaded_files(values)
+        .into_iter()
+        .map(|path| format!("cargo config loaded from {}", path.display()))
+        .collect::<Vec<_>>();
+
+    for table in RELEVANT_TABLES {
+        if let Some(ConfigValue::Table(entries, _)) = values.get(*table) {
+            let keys = entries.keys().collect::<BTreeSet<_>>();
+            for key in keys {
+                lines.push(format!(
+                    "cargo config sets {}.{} = {:?}",
+                    table, key, entries[key]
+                ));
+            }
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn files_of_nested_values() {
+        let a = PathBuf::from("/ws/.cargo/config.toml");
+        let b = PathBuf::from("/home/.cargo/config.toml");
+        let mut build = HashMap::new();
+        build.insert(
+            "target".to_string(),
+            ConfigValue::String(
+                "aarch64-unknown-linux-gnu".into(),
+                Definition::Path(a.clone()),
+            ),
+        );
+        let mut values = HashMap::new();
+        values.insert(
+            "build".to_string(),
+            ConfigValue::Table(build, Definition::Path(a.clone())),
+        );
+        values.insert(
+            "net".to_string(),
+            ConfigValue::Boolean(true, Definition::Environment("CARGO_NET_OFFLINE".into())),
+        );
+        values.insert(
+            "paths".to_string(),
+            ConfigValue::List(
+                vec![("../vendor".into(), Definition::Path(b.clone()))],
+                Definition::Cli(None),
+            ),
+        );
+
+        assert_eq!(
+            loaded_files(&values).into_iter().collect::<Vec<_>>(),
+            vec![b, a]
+        );
+    }
+}
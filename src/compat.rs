@@ -0,0 +1,361 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Checks which Yocto releases a recipe works with by looking for
+//! release-sensitive constructs.
+
+use crate::src_uri::SrcUri;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fmt::{self, Display};
+
+/// A Yocto Project release
+#[derive(Debug, Eq, PartialEq)]
+pub struct Release {
+    pub name: &'static str,
+    pub version: &'static str,
+}
+
+/// the releases checked, oldest first
+pub const RELEASES: &[Release] = &[
+    Release {
+        name: "dunfell",
+        version: "3.1",
+    },
+    Release {
+        name: "gatesgarth",
+        version: "3.2",
+    },
+    Release {
+        name: "hardknott",
+        version: "3.3",
+    },
+    Release {
+        name: "honister",
+        version: "3.4",
+    },
+    Release {
+        name: "kirkstone",
+        version: "4.0",
+    },
+    Release {
+        name: "langdale",
+        version: "4.1",
+    },
+    Release {
+        name: "mickledore",
+        version: "4.2",
+    },
+    Release {
+        name: "nanbield",
+        version: "4.3",
+    },
+    Release {
+        name: "scarthgap",
+        version: "5.0",
+    },
+    Release {
+        name: "styhead",
+        version: "5.1",
+    },
+    Release {
+        name: "walnascar",
+        version: "5.2",
+    },
+];
+
+// indices into RELEASES
+const GATESGARTH: usize = 1;
+const HARDKNOTT: usize = 2;
+const HONISTER: usize = 3;
+const KIRKSTONE: usize = 4;
+const MICKLEDORE: usize = 6;
+const NANBIELD: usize = 7;
+const STYHEAD: usize = 9;
+
+impl Release {
+    /// the release called `name`, for --yocto-release
+    pub fn by_name(name: &str) -> Result<&'static Release, String> {
+        RELEASES
+            .iter()
+            .find(|release| release.name == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown Yocto release '{}', expected one of: {}",
+                    name,
+                    RELEASES
+                        .iter()
+                        .map(|release| release.name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+
+    fn index(&self) -> usize {
+        RELEASES
+            .iter()
+            .position(|release| release == self)
+            .unwrap_or_default()
+    }
+
+    /// whether the release uses the ':' override syntax
+    pub fn has_colon_overrides(&self) -> bool {
+        self.index() >= HONISTER
+    }
+
+    /// whether the cargo class builds the Cargo.toml in CARGO_SRC_DIR,
+    /// before it S has to point at the package itself
+    pub fn has_cargo_src_dir(&self) -> bool {
+        self.index() >= HONISTER
+    }
+
+    /// whether git repositories should be fetched with protocol=https,
+    /// which the fetcher asks for from kirkstone on
+    pub fn wants_https_git(&self) -> bool {
+        self.index() >= KIRKSTONE
+    }
+
+    /// whether git SRC_URIs should name the branch of their revision,
+    /// which the fetcher warns about from kirkstone on
+    pub fn wants_git_branch(&self) -> bool {
+        self.index() >= KIRKSTONE
+    }
+
+    /// whether the git revision is added to the package version by itself
+    /// once PV contains "+git", which replaced SRCPV in nanbield
+    pub fn appends_srcrev(&self) -> bool {
+        self.index() >= NANBIELD
+    }
+
+    /// whether the git fetcher takes ;tag= and checks SRCREV against it,
+    /// which it does from styhead on
+    pub fn has_git_tag_param(&self) -> bool {
+        self.index() >= STYHEAD
+    }
+}
+
+lazy_static! {
+    static ref OLD_OVERRIDE: Regex =
+        Regex::new(r"^\s*[\w${}-]+_((append|prepend|remove)\b|\$\{PN\})").unwrap();
+    static ref NEW_OVERRIDE: Regex =
+        Regex::new(r"^\s*[\w${}-]+:((append|prepend|remove)\b|\$\{PN\})").unwrap();
+    static ref CRATE_CHECKSUM: Regex =
+        Regex::new(r"^\s*SRC_URI\[[\w.+-]+-\d[\w.+-]*\.sha256sum\]").unwrap();
+}
+
+/// How well a recipe works with a release
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Verdict {
+    Compatible,
+    /// works, possibly with additional layers or point releases
+    Caveats,
+    Incompatible,
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Verdict::Compatible => "compatible",
+                Verdict::Caveats => "compatible with caveats",
+                Verdict::Incompatible => "incompatible",
+            }
+        )
+    }
+}
+
+/// A line of the recipe affecting compatibility with a release
+#[derive(Debug, Eq, PartialEq)]
+pub struct Finding {
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+    pub reason: &'static str,
+    pub verdict: Verdict,
+}
+
+/// The compatibility of a recipe with one release
+#[derive(Debug)]
+pub struct ReleaseReport {
+    pub release: &'static Release,
+    pub findings: Vec<Finding>,
+}
+
+impl ReleaseReport {
+    pub fn verdict(&self) -> Verdict {
+        self.findings
+            .iter()
+            .map(|finding| finding.verdict)
+            .max()
+            .unwrap_or(Verdict::Compatible)
+    }
+}
+
+/// checks `recipe` against every release in `RELEASES`
+pub fn report(recipe: &str) -> Vec<ReleaseReport> {
+    RELEASES
+        .iter()
+        .enumerate()
+        .map(|(idx, release)| ReleaseReport {
+            release,
+            findings: recipe
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim_start().starts_with('#'))
+                .flat_map(|(num, line)| {
+                    check_line(idx, line)
+                        .into_iter()
+                        .map(move |(verdict, reason)| Finding {
+                            line: num + 1,
+                            text: line.trim().to_string(),
+                            reason,
+                            verdict,
+                        })
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// the problems `line` causes with the release at index `release`
+fn check_line(release: usize, line: &str) -> Vec<(Verdict, &'static str)> {
+    let mut found = vec![];
+
+    if OLD_OVERRIDE.is_match(line) && release >= HONISTER {
+        found.push((
+            Verdict::Incompatible,
+            "the '_' override syntax was replaced by ':' in honister",
+        ));
+    }
+    if NEW_OVERRIDE.is_match(line) {
+        if release == GATESGARTH {
+            found.push((
+                Verdict::Incompatible,
+                "the ':' override syntax is not supported before honister",
+            ));
+        } else if release < HONISTER {
+            found.push((
+                Verdict::Caveats,
+                "the ':' override syntax needs a point release supporting it",
+            ));
+        }
+    }
+    if CRATE_CHECKSUM.is_match(line) && release < MICKLEDORE {
+        found.push((
+            Verdict::Caveats,
+            "crate checksums are only verified from mickledore on",
+        ));
+    }
+
+    for uri in line
+        .split(|c: char| c.is_whitespace() || c == '"')
+        .filter(|token| token.contains("://"))
+        .map(SrcUri::parse)
+    {
+        if uri.url.starts_with("crate://") && release < HARDKNOTT {
+            found.push((
+                Verdict::Caveats,
+                "the crate fetcher and cargo class come from meta-rust before hardknott",
+            ));
+        }
+        let is_git = uri.url.starts_with("git://") || uri.url.starts_with("gitsm://");
+        if is_git && uri.param("branch").is_none() && uri.param("nobranch").is_none() {
+            if release >= KIRKSTONE {
+                found.push((
+                    Verdict::Incompatible,
+                    "git SRC_URI entries need branch= or nobranch=1 from kirkstone on",
+                ));
+            } else if release == HONISTER {
+                found.push((
+                    Verdict::Caveats,
+                    "git SRC_URI entries without branch= or nobranch=1 warn in honister",
+                ));
+            }
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn verdict(recipe: &str, release: &str) -> Verdict {
+        report(recipe)
+            .into_iter()
+            .find(|report| report.release.name == release)
+            .unwrap()
+            .verdict()
+    }
+
+    #[test]
+    fn generated_recipe() {
+        let recipe = "inherit cargo\n\
+                      SRC_URI += \"git://github.com/a/b.git;protocol=https;nobranch=1\"\n\
+                      SRC_URI += \" \\\n    crate://crates.io/itoa/1.0.0 \\\n\"\n\
+                      SRC_URI[itoa-1.0.0.sha256sum] = \"abc\"\n";
+        assert_eq!(verdict(recipe, "scarthgap"), Verdict::Compatible);
+        assert_eq!(verdict(recipe, "kirkstone"), Verdict::Caveats);
+        assert_eq!(verdict(recipe, "dunfell"), Verdict::Caveats);
+    }
+
+    #[test]
+    fn override_syntax() {
+        let old = "RDEPENDS_${PN} += \"bash\"\nSRC_URI_append = \" file://a.patch\"\n";
+        assert_eq!(verdict(old, "dunfell"), Verdict::Compatible);
+        let report = report(old);
+        let honister = &report[HONISTER];
+        assert_eq!(honister.verdict(), Verdict::Incompatible);
+        assert_eq!(
+            honister.findings.iter().map(|f| f.line).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        let new = "SRC_URI:append = \" file://a.patch\"\n";
+        assert_eq!(verdict(new, "gatesgarth"), Verdict::Incompatible);
+        assert_eq!(verdict(new, "dunfell"), Verdict::Caveats);
+        assert_eq!(verdict(new, "kirkstone"), Verdict::Compatible);
+    }
+
+    #[test]
+    fn git_without_branch() {
+        let recipe = "SRC_URI += \"git://github.com/a/b.git;protocol=https\"\n";
+        assert_eq!(verdict(recipe, "hardknott"), Verdict::Compatible);
+        assert_eq!(verdict(recipe, "honister"), Verdict::Caveats);
+        assert_eq!(verdict(recipe, "kirkstone"), Verdict::Incompatible);
+    }
+
+    #[test]
+    fn release_profile() {
+        let dunfell = Release::by_name("dunfell").unwrap();
+        assert!(!dunfell.has_colon_overrides());
+        assert!(!dunfell.wants_https_git());
+        let kirkstone = Release::by_name("kirkstone").unwrap();
+        assert!(kirkstone.has_colon_overrides());
+        assert!(kirkstone.wants_https_git());
+        assert!(!kirkstone.appends_srcrev());
+        assert!(kirkstone.has_cargo_src_dir());
+        assert!(!dunfell.has_cargo_src_dir());
+        assert!(Release::by_name("scarthgap").unwrap().appends_srcrev());
+        assert!(!Release::by_name("scarthgap").unwrap().has_git_tag_param());
+        assert!(Release::by_name("styhead").unwrap().has_git_tag_param());
+        assert!(Release::by_name("zeus").unwrap_err().contains("dunfell"));
+    }
+
+    #[test]
+    fn comments_ignored() {
+        let recipe = "# SRC_URI_append = \"git://github.com/a/b.git\"\n";
+        assert_eq!(verdict(recipe, "walnascar"), Verdict::Compatible);
+    }
+}
@@ -0,0 +1,101 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::src_uri::SrcUriOverride;
+use anyhow::Context as _;
+use cargo::util::CargoResult;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// name of the configuration file looked for at the workspace root
+pub const CONFIG_FILE_NAME: &str = "cargo-bitbake.toml";
+
+/// Settings read from the cargo-bitbake configuration file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// patterns matched against the source URL of dependencies which
+    /// are left out of SRC_URI
+    pub exclude_sources: Vec<String>,
+    /// adjustments to generated SRC_URI entries keyed by crate name or
+    /// repository URL
+    pub src_uri_overrides: BTreeMap<String, SrcUriOverride>,
+}
+
+impl Config {
+    /// loads the configuration from `path` if provided, otherwise from
+    /// `cargo-bitbake.toml` at the workspace root if it exists
+    pub fn load(ws_root: &Path, path: Option<&Path>) -> CargoResult<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let path = ws_root.join(CONFIG_FILE_NAME);
+                if !path.exists() {
+                    return Ok(Self::default());
+                }
+                path
+            }
+        };
+        Self::from_file(&path)
+    }
+
+    fn from_file(path: &PathBuf) -> CargoResult<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read config file '{}'", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Unable to parse config file '{}'", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.exclude_sources.is_empty());
+    }
+
+    #[test]
+    fn exclude_sources() {
+        let config: Config =
+            toml::from_str(r#"exclude_sources = ["*github.com/our-org/*"]"#).unwrap();
+        assert_eq!(config.exclude_sources, vec!["*github.com/our-org/*"]);
+    }
+
+    #[test]
+    fn src_uri_overrides() {
+        let config: Config = toml::from_str(
+            r#"
+            [src_uri_overrides.foo]
+            protocol = "ssh"
+            nobranch = true
+
+            [src_uri_overrides."https://github.com/our-org/bar"]
+            url = "git://mirror.example.com/bar.git"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.src_uri_overrides.len(), 2);
+        assert_eq!(
+            config.src_uri_overrides["foo"].protocol.as_deref(),
+            Some("ssh")
+        );
+        assert_eq!(config.src_uri_overrides["foo"].nobranch, Some(true));
+    }
+
+    #[test]
+    fn unknown_key() {
+        assert!(toml::from_str::<Config>("bogus = 1").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! LIC_FILES_CHKSUM entries for the license files of the crates, for
+//! `--crate-license-files`.
+
+use crate::archive::ArchiveCache;
+use crate::cache::Cache;
+use crate::license;
+use anyhow::Context as _;
+use cargo::core::{PackageId, PackageSet};
+use cargo::util::{CargoResult, GlobalContext};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// where the crate fetcher unpacks the crates
+const VENDORING_DIR: &str = "${CARGO_VENDORING_DIRECTORY}";
+
+/// The license files of the crates
+#[derive(Debug, Default)]
+pub struct CrateLicenseFiles {
+    /// the LIC_FILES_CHKSUM entries
+    pub entries: Vec<String>,
+    /// crates without a license file
+    pub missing: Vec<String>,
+}
+
+/// the entries for the license files of `crates`, downloading those that
+/// haven't been looked at before
+pub fn crate_license_files(
+    config: &GlobalContext,
+    archives: &mut ArchiveCache<'_>,
+    package_set: &PackageSet<'_>,
+    crates: &[PackageId],
+    checksums: &HashMap<PackageId, Option<String>>,
+    lic_checksum: license::LicChecksum,
+) -> CargoResult<CrateLicenseFiles> {
+    let mut cache = cache(config)?;
+    let mut files = CrateLicenseFiles::default();
+    for id in crates {
+        // the checksum makes sure a republished crate is looked at again
+        let key = checksums.get(id).cloned().flatten().map(|checksum| {
+            format!(
+                "{}-{}-{}-{}",
+                id.name(),
+                id.version(),
+                checksum,
+                lic_checksum
+            )
+        });
+        let entries = match key.as_ref().and_then(|key| cache.entries.get(key)) {
+            Some(entries) => entries.clone(),
+            None => {
+                let pkg = archives
+                    .get_one(package_set, *id, checksums)
+                    .with_context(|| {
+                        format!("Unable to download {} {}", id.name(), id.version())
+                    })?;
+                let dir =
+                    PathBuf::from(format!("{}/{}-{}", VENDORING_DIR, id.name(), id.version()));
+                let metadata = pkg.manifest().metadata();
+                let entries = entries(
+                    pkg.root(),
+                    &dir,
+                    metadata.license.as_deref(),
+                    metadata.license_file.as_deref(),
+                    lic_checksum,
+                );
+                if let Some(key) = key {
+                    cache.entries.insert(key, entries.clone());
+                }
+                entries
+            }
+        };
+        if entries.is_empty() {
+            files
+                .missing
+                .push(format!("{} {}", id.name(), id.version()));
+        }
+        for entry in entries {
+            if !files.entries.contains(&entry) {
+                files.entries.push(entry);
+            }
+        }
+    }
+    cache.save()?;
+    Ok(files)
+}
+
+/// the entries for the license files of the crate unpacked at `root`,
+/// which is at `dir` in the recipe
+fn entries(
+    root: &Path,
+    dir: &Path,
+    license: Option<&str>,
+    license_file: Option<&str>,
+    lic_checksum: license::LicChecksum,
+) -> Vec<String> {
+    let names = match (license, license_file) {
+        (_, Some(license_file)) => vec![license_file.to_string()],
+        (Some(license), None) => match license::Expression::parse(license) {
+            Ok(expr) => expr.licenses().into_iter().map(str::to_string).collect(),
+            Err(_) => vec![],
+        },
+        (None, None) => vec![],
+    };
+    let mut entries = vec![];
+    for name in names {
+        if let Some(file) = license::file(&[(root, dir)], &name, lic_checksum) {
+            if !entries.contains(&file.entry) {
+                entries.push(file.entry);
+            }
+        }
+    }
+    entries
+}
+
+/// The entries found before, by crate name, version and checksum
+fn cache(config: &GlobalContext) -> CargoResult<Cache<Vec<String>>> {
+    Cache::load(
+        config,
+        "crate-license-files.toml",
+        "license file cache",
+        "the crates are looked at again",
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn corrupt_cache() {
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir_all(home.path().join("bitbake")).unwrap();
+        fs::write(home.path().join("bitbake/crate-license-files.toml"), "[[[").unwrap();
+        let config = GlobalContext::new(
+            cargo::core::Shell::new(),
+            home.path().to_path_buf(),
+            home.path().to_path_buf(),
+        );
+        let (cache, captured) = crate::capture_shell(&config, || cache(&config));
+        assert!(cache.unwrap().entries.is_empty());
+        assert!(
+            captured.contains("corrupt license file cache"),
+            "{}",
+            captured
+        );
+    }
+
+    #[test]
+    fn crate_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("LICENSE-MIT"), "MIT\n").unwrap();
+        fs::write(dir.path().join("LICENSE-APACHE"), "Apache\n").unwrap();
+        let unpacked = Path::new("${CARGO_VENDORING_DIRECTORY}/itoa-1.0.0");
+
+        let found = entries(
+            dir.path(),
+            unpacked,
+            Some("MIT OR Apache-2.0"),
+            None,
+            license::LicChecksum::Md5,
+        );
+        assert_eq!(found.len(), 2);
+        assert!(
+            found[0].starts_with("file://${CARGO_VENDORING_DIRECTORY}/itoa-1.0.0/LICENSE-MIT;md5=")
+        );
+        assert!(found[1].contains("/LICENSE-APACHE;md5="));
+        assert!(entries(
+            dir.path(),
+            unpacked,
+            Some("Zlib"),
+            None,
+            license::LicChecksum::Md5
+        )
+        .is_empty());
+    }
+}
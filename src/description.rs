@@ -0,0 +1,151 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Splitting the package description into SUMMARY and DESCRIPTION.
+
+use crate::template;
+
+/// longest SUMMARY before it is cut off
+const MAX_SUMMARY: usize = 80;
+
+/// abbreviations whose '.' doesn't end a sentence, besides the ones with
+/// several dots like "e.g."
+const ABBREVIATIONS: &[&str] = &["approx", "cf", "etc", "incl", "vs"];
+
+/// The package description as BitBake wants it
+#[derive(Debug, Eq, PartialEq)]
+pub struct Description {
+    /// the first sentence or line, short enough for SUMMARY
+    pub summary: String,
+    /// the lines of the whole text, `None` if the summary already says it all
+    pub lines: Option<Vec<String>>,
+}
+
+impl Description {
+    pub fn new(description: &str) -> Self {
+        let lines = description
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+
+        let first_line = lines.first().map(String::as_str).unwrap_or_default();
+        let summary = match sentence_end(first_line) {
+            Some(end) => truncate(&first_line[..end]),
+            // a single sentence is kept as it is
+            None => truncate(first_line),
+        };
+
+        let full = lines.join(" ");
+        let lines = if summary == full || summary == full.trim_end_matches('.') {
+            None
+        } else {
+            Some(lines)
+        };
+        Self { summary, lines }
+    }
+
+    /// the DESCRIPTION assignment, continued over several lines for a
+    /// multi-line description, empty if there is none
+    pub fn assignment(&self) -> String {
+        match self.lines {
+            Some(ref lines) => format!(
+                "DESCRIPTION = \"{}\"\n",
+                lines
+                    .iter()
+                    .map(|line| template::escape(line))
+                    .collect::<Vec<_>>()
+                    .join(" \\\n")
+            ),
+            None => String::new(),
+        }
+    }
+}
+
+/// where the first of several sentences in `line` ends, not counting the
+/// dots of abbreviations
+fn sentence_end(line: &str) -> Option<usize> {
+    line.match_indices(". ").map(|(end, _)| end).find(|&end| {
+        let word = line[..end].rsplit(' ').next().unwrap_or_default();
+        word.chars().count() > 1
+            && !word.contains('.')
+            && !ABBREVIATIONS.contains(&word.to_lowercase().as_str())
+    })
+}
+
+/// cuts `text` off at a word boundary if it is too long for SUMMARY
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_SUMMARY {
+        return text.to_string();
+    }
+    let limit = text
+        .char_indices()
+        .nth(MAX_SUMMARY - 3)
+        .map_or(text.len(), |(idx, _)| idx);
+    let cut = text[..limit].rfind(' ').unwrap_or(limit);
+    format!("{}...", text[..cut].trim_end_matches([' ', ',', ';', ':']))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn short() {
+        let description = Description::new("A fast JSON parser.");
+        assert_eq!(description.summary, "A fast JSON parser.");
+        assert_eq!(description.assignment(), "");
+    }
+
+    #[test]
+    fn abbreviations() {
+        let description =
+            Description::new("Bindings to C libraries, e.g. zlib, bzip2 etc. on Linux.");
+        assert_eq!(
+            description.summary,
+            "Bindings to C libraries, e.g. zlib, bzip2 etc. on Linux."
+        );
+        assert_eq!(description.assignment(), "");
+
+        let description = Description::new("Parses URLs, i.e. RFC 3986. No allocations.");
+        assert_eq!(description.summary, "Parses URLs, i.e. RFC 3986");
+        assert!(description.lines.is_some());
+    }
+
+    #[test]
+    fn first_sentence() {
+        let description = Description::new("A fast JSON parser. It never copies.");
+        assert_eq!(description.summary, "A fast JSON parser");
+        assert_eq!(
+            description.assignment(),
+            "DESCRIPTION = \"A fast JSON parser. It never copies.\"\n"
+        );
+    }
+
+    #[test]
+    fn paragraphs() {
+        let description =
+            Description::new("A fast JSON parser\n\n  without   copies\nor allocations\n");
+        assert_eq!(description.summary, "A fast JSON parser");
+        assert_eq!(
+            description.assignment(),
+            "DESCRIPTION = \"A fast JSON parser \\\nwithout copies \\\nor allocations\"\n"
+        );
+    }
+
+    #[test]
+    fn long() {
+        let description = Description::new(&"word ".repeat(30));
+        assert!(description.summary.chars().count() <= MAX_SUMMARY);
+        assert!(description.summary.ends_with("word..."));
+        assert!(description.lines.is_some());
+    }
+}
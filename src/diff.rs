@@ -0,0 +1,140 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Unified diffs of recipes for `--check`.
+
+/// lines of context around each change
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// a unified diff turning `old` into `new`, empty if they are the same
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let ops = edit_script(&old, &new);
+    if ops.iter().all(|(op, _, _)| *op == Op::Equal) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut start = 0;
+    while let Some(first) = ops[start..].iter().position(|(op, _, _)| *op != Op::Equal) {
+        // extend the hunk while the changes are close enough to share context
+        let first = start + first;
+        let mut last = first;
+        let mut idx = first;
+        while idx < ops.len() {
+            if ops[idx].0 != Op::Equal {
+                last = idx;
+            } else if idx - last > 2 * CONTEXT {
+                break;
+            }
+            idx += 1;
+        }
+        let begin = first.saturating_sub(CONTEXT);
+        let end = (last + CONTEXT + 1).min(ops.len());
+        let hunk = &ops[begin..end];
+
+        let old_len = hunk.iter().filter(|(op, _, _)| *op != Op::Insert).count();
+        let new_len = hunk.iter().filter(|(op, _, _)| *op != Op::Delete).count();
+        let (_, old_start, new_start) = hunk[0];
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_len),
+            range(new_start, new_len)
+        ));
+        for (op, old_idx, new_idx) in hunk {
+            match op {
+                Op::Equal => out.push_str(&format!(" {}\n", old[*old_idx])),
+                Op::Delete => out.push_str(&format!("-{}\n", old[*old_idx])),
+                Op::Insert => out.push_str(&format!("+{}\n", new[*new_idx])),
+            }
+        }
+        start = end;
+    }
+    out
+}
+
+/// the `start,len` of a hunk header, 1-based
+fn range(start: usize, len: usize) -> String {
+    if len == 0 {
+        format!("{},0", start)
+    } else {
+        format!("{},{}", start + 1, len)
+    }
+}
+
+/// the operations turning `old` into `new` along with the line indices
+/// they apply to, from the longest common subsequence of lines
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<(Op, usize, usize)> {
+    // lcs[i][j] is the length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((Op::Equal, i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push((Op::Delete, i, j));
+            i += 1;
+        } else {
+            ops.push((Op::Insert, i, j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identical() {
+        assert_eq!(unified("a\nb\n", "a\nb\n", "old", "new"), "");
+    }
+
+    #[test]
+    fn changed_line() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        assert_eq!(
+            unified(old, new, "a.bb", "a.bb (generated)"),
+            "--- a.bb\n+++ a.bb (generated)\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn separate_hunks() {
+        let old = (1..=20).map(|n| format!("{}\n", n)).collect::<String>();
+        let new = old.replacen("2\n", "two\n", 1).replace("19\n", "");
+        let diff = unified(&old, &new, "old", "new");
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("@@ -16,5 +16,4 @@\n 16\n 17\n 18\n-19\n 20\n"));
+    }
+}
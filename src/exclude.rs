@@ -0,0 +1,117 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use anyhow::Context as _;
+use cargo::util::CargoResult;
+use glob::Pattern;
+use regex::Regex;
+
+/// prefix marking a source pattern as a regular expression
+const REGEX_PREFIX: &str = "re:";
+
+/// A pattern matched against the source URL of a dependency. Patterns
+/// are globs unless prefixed with `re:`, in which case they are regular
+/// expressions. The host portion is matched case-insensitively.
+#[derive(Debug)]
+pub enum SourcePattern {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+impl SourcePattern {
+    pub fn parse(pattern: &str) -> CargoResult<Self> {
+        match pattern.strip_prefix(REGEX_PREFIX) {
+            Some(re) => Regex::new(&lowercase_host(re, true))
+                .map(SourcePattern::Regex)
+                .with_context(|| format!("Invalid source regex '{}'", re)),
+            None => Pattern::new(&lowercase_host(pattern, false))
+                .map(SourcePattern::Glob)
+                .with_context(|| format!("Invalid source glob '{}'", pattern)),
+        }
+    }
+
+    /// checks if `url` matches this pattern
+    pub fn matches(&self, url: &str) -> bool {
+        let url = lowercase_host(url, false);
+        match self {
+            SourcePattern::Glob(glob) => glob.matches(&url),
+            SourcePattern::Regex(re) => re.is_match(&url),
+        }
+    }
+}
+
+/// lowercases the host portion of a URL (or a pattern for one), that is
+/// everything after an optional `scheme://` up to the first `/`. For
+/// regular expressions escaped characters are left alone.
+fn lowercase_host(url: &str, is_regex: bool) -> String {
+    let (scheme, rest) = match url.find("://") {
+        Some(idx) => url.split_at(idx + 3),
+        None => ("", url),
+    };
+    let host_end = rest.find('/').unwrap_or(rest.len());
+    let (host, path) = rest.split_at(host_end);
+
+    let mut lowered = String::with_capacity(url.len());
+    lowered.push_str(scheme);
+    let mut escaped = false;
+    for c in host.chars() {
+        if escaped {
+            lowered.push(c);
+            escaped = false;
+        } else {
+            escaped = is_regex && c == '\\';
+            lowered.extend(c.to_lowercase());
+        }
+    }
+    lowered.push_str(path);
+    lowered
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        let pat = SourcePattern::parse("*github.com/our-org/*").unwrap();
+        assert!(pat.matches("https://github.com/our-org/foo.git"));
+        assert!(!pat.matches("https://github.com/other-org/foo.git"));
+        assert!(!pat.matches("https://github.com/rust-lang/crates.io-index"));
+    }
+
+    #[test]
+    fn regex() {
+        let pat = SourcePattern::parse(r"re:^https://github\.com/our-org/").unwrap();
+        assert!(pat.matches("https://github.com/our-org/foo.git"));
+        assert!(!pat.matches("https://gitlab.com/our-org/foo.git"));
+    }
+
+    #[test]
+    fn host_case_insensitive() {
+        let pat = SourcePattern::parse("https://GitHub.com/our-org/*").unwrap();
+        assert!(pat.matches("https://github.com/our-org/foo.git"));
+        assert!(pat.matches("https://GITHUB.COM/our-org/foo.git"));
+
+        let pat = SourcePattern::parse(r"re:^https://GitHub\.com/our-org/").unwrap();
+        assert!(pat.matches("https://github.com/our-org/foo.git"));
+    }
+
+    #[test]
+    fn path_case_sensitive() {
+        let pat = SourcePattern::parse("https://github.com/our-org/*").unwrap();
+        assert!(!pat.matches("https://github.com/Our-Org/foo.git"));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(SourcePattern::parse("re:(").is_err());
+        assert!(SourcePattern::parse("[").is_err());
+    }
+}
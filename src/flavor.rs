@@ -0,0 +1,94 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use anyhow::bail;
+use cargo::core::resolver::CliFeatures;
+use cargo::util::CargoResult;
+use std::str::FromStr;
+
+/// A variant of the recipe built with its own set of cargo features,
+/// given as `name:features=a,b`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Flavor {
+    pub name: String,
+    pub features: Vec<String>,
+}
+
+impl Flavor {
+    /// the default features plus the ones of this flavor
+    pub fn cli_features(&self) -> CargoResult<CliFeatures> {
+        CliFeatures::from_command_line(&self.features, false, true)
+    }
+
+    /// the flags passing the features of this flavor to cargo
+    pub fn build_flags(&self) -> String {
+        if self.features.is_empty() {
+            String::new()
+        } else {
+            format!("--features {}", self.features.join(","))
+        }
+    }
+}
+
+impl FromStr for Flavor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, features) = match s.split_once(':') {
+            Some((name, spec)) => match spec.strip_prefix("features=") {
+                Some(features) => (name, features),
+                None => bail!("invalid flavor '{}', expected name:features=a,b", s),
+            },
+            None => (s, ""),
+        };
+
+        let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+        if name.is_empty() || !name.chars().all(valid) {
+            bail!(
+                "invalid flavor name '{}', it may only contain lowercase letters, digits and '-'",
+                name
+            );
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            features: features
+                .split(',')
+                .map(str::trim)
+                .filter(|feature| !feature.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let flavor: Flavor = "full:features=tls,metrics".parse().unwrap();
+        assert_eq!(flavor.name, "full");
+        assert_eq!(flavor.features, vec!["tls", "metrics"]);
+        assert_eq!(flavor.build_flags(), "--features tls,metrics");
+
+        let minimal: Flavor = "minimal".parse().unwrap();
+        assert!(minimal.features.is_empty());
+        assert_eq!(minimal.build_flags(), "");
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!("full:tls".parse::<Flavor>().is_err());
+        assert!("Full:features=tls".parse::<Flavor>().is_err());
+        assert!(":features=tls".parse::<Flavor>().is_err());
+    }
+}
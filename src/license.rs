@@ -8,13 +8,364 @@
  * except according to those terms.
  */
 
+use crate::archive::file_sha256;
+use anyhow::{anyhow, Context as _};
+use cargo::util::CargoResult;
 use md5::Context;
-use std::fs::File;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::fs::{self, File};
 use std::io;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
 
 pub const CLOSED_LICENSE: &str = "CLOSED";
 
+/// Renames of license identifiers to the names used by the layer, e.g.
+/// those of oe-core's `common-licenses`
+#[derive(Debug, Default)]
+pub struct LicenseMap {
+    names: BTreeMap<String, String>,
+}
+
+impl LicenseMap {
+    /// loads the `from = "to"` pairs of the TOML file at `path`
+    pub fn load(path: &Path) -> CargoResult<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read license map '{}'", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("Unable to parse license map '{}'", path.display()))
+    }
+
+    fn parse(contents: &str) -> CargoResult<Self> {
+        Ok(Self {
+            names: toml::from_str(contents)?,
+        })
+    }
+
+    /// the name `license` maps to, if any
+    pub fn get(&self, license: &str) -> Option<&str> {
+        self.names.get(license).map(String::as_str)
+    }
+}
+
+/// A license expression as found in Cargo.toml: SPDX with `AND`, `OR` and
+/// parentheses, or the legacy `/` separated list of alternatives
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Expression {
+    License(String),
+    /// a license with an exception, `<license> WITH <exception>`
+    With {
+        license: String,
+        exception: String,
+    },
+    And(Vec<Expression>),
+    Or(Vec<Expression>),
+}
+
+impl Expression {
+    /// parses the `license` field of a manifest
+    pub fn parse(license: &str) -> CargoResult<Self> {
+        let tokens = tokenize(license);
+        let mut pos = 0;
+        let expr = parse_or(&tokens, &mut pos)?;
+        match tokens.get(pos) {
+            None => Ok(expr),
+            Some(token) => Err(anyhow!("unexpected '{}'", token)),
+        }
+        .with_context(|| format!("Unable to parse license expression '{}'", license))
+    }
+
+    /// the licenses the expression is made of, in order and without
+    /// duplicates, those with exceptions by their base license
+    pub fn licenses(&self) -> Vec<&str> {
+        let mut licenses = vec![];
+        self.collect_licenses(&mut licenses);
+        licenses
+    }
+
+    fn collect_licenses<'a>(&'a self, licenses: &mut Vec<&'a str>) {
+        match self {
+            Self::License(license) | Self::With { license, .. } => {
+                if !licenses.contains(&license.as_str()) {
+                    licenses.push(license);
+                }
+            }
+            Self::And(exprs) | Self::Or(exprs) => {
+                for expr in exprs {
+                    expr.collect_licenses(licenses);
+                }
+            }
+        }
+    }
+
+    /// the exceptions BitBake has no license name for
+    pub fn unknown_exceptions(&self) -> Vec<&str> {
+        match self {
+            Self::License(_) => vec![],
+            Self::With { exception, .. } => match exception_suffix(exception) {
+                Some(_) => vec![],
+                None => vec![exception.as_str()],
+            },
+            Self::And(exprs) | Self::Or(exprs) => {
+                exprs.iter().flat_map(Self::unknown_exceptions).collect()
+            }
+        }
+    }
+
+    /// replaces each license with what `f` maps it to. A license with an
+    /// exception is looked up as `<license> WITH <exception>` first,
+    /// becoming a plain license if that is mapped.
+    pub fn map_licenses(&mut self, f: &mut impl FnMut(&str) -> Option<String>) {
+        match self {
+            Self::License(license) => {
+                if let Some(mapped) = f(license) {
+                    *license = mapped;
+                }
+            }
+            Self::With { license, exception } => {
+                if let Some(mapped) = f(&format!("{} WITH {}", license, exception)) {
+                    *self = Self::License(mapped);
+                } else if let Some(mapped) = f(license) {
+                    *license = mapped;
+                }
+            }
+            Self::And(exprs) | Self::Or(exprs) => {
+                for expr in exprs {
+                    expr.map_licenses(f);
+                }
+            }
+        }
+    }
+
+    /// the expression in the syntax of BitBake's LICENSE
+    pub fn to_bitbake(&self) -> String {
+        match self {
+            Self::License(license) => license.clone(),
+            Self::With { license, exception } => format!(
+                "{}-with-{}",
+                license,
+                exception_suffix(exception).unwrap_or(exception)
+            ),
+            Self::Or(exprs) => exprs
+                .iter()
+                .map(Self::to_bitbake)
+                .collect::<Vec<_>>()
+                .join(" | "),
+            // & binds tighter than |, so alternatives need parentheses
+            Self::And(exprs) => exprs
+                .iter()
+                .map(|expr| match expr {
+                    Self::Or(_) => format!("({})", expr.to_bitbake()),
+                    _ => expr.to_bitbake(),
+                })
+                .collect::<Vec<_>>()
+                .join(" & "),
+        }
+    }
+}
+
+/// licenses most crates come with, which --aggregate-licenses doesn't point
+/// out
+const COMMON_CRATE_LICENSES: &[&str] = &[
+    "0BSD",
+    "Apache-2.0",
+    "BSD-2-Clause",
+    "BSD-3-Clause",
+    "BSL-1.0",
+    "CC0-1.0",
+    "ISC",
+    "MIT",
+    "MIT-0",
+    "Unicode-3.0",
+    "Unicode-DFS-2016",
+    "Unlicense",
+    "Zlib",
+];
+
+/// `license` ANDed with the distinct licenses of the crates in `deps`,
+/// sorted
+pub fn aggregate(license: Expression, deps: &[(String, Expression)]) -> Expression {
+    let terms_of = |expr: &Expression| match expr {
+        Expression::And(exprs) => exprs.clone(),
+        expr => vec![expr.clone()],
+    };
+    let own = terms_of(&license)
+        .iter()
+        .map(Expression::to_bitbake)
+        .collect::<Vec<_>>();
+    let mut terms = deps
+        .iter()
+        .flat_map(|(_, expr)| terms_of(expr))
+        .map(|expr| (expr.to_bitbake(), expr))
+        .filter(|(term, _)| !own.contains(term))
+        .collect::<Vec<_>>();
+    terms.sort_by(|a, b| a.0.cmp(&b.0));
+    terms.dedup_by(|a, b| a.0 == b.0);
+
+    let mut exprs = vec![license];
+    exprs.extend(terms.into_iter().map(|(_, expr)| expr));
+    flatten(exprs, true)
+}
+
+/// the comment naming the crates in `deps` that come with each of the less
+/// common licenses, empty if there are none
+pub fn uncommon_licenses_comment(deps: &[(String, Expression)]) -> String {
+    let mut crates = BTreeMap::<&str, Vec<&str>>::new();
+    for (name, expr) in deps {
+        for license in expr.licenses() {
+            if !COMMON_CRATE_LICENSES.contains(&license) {
+                let names = crates.entry(license).or_default();
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+    crates
+        .iter()
+        .map(|(license, names)| format!("# {}: {}\n", license, names.join(", ")))
+        .collect()
+}
+
+/// the SPDX exceptions oe-core's `common-licenses` has licenses for, with
+/// the suffix of their names, e.g. `Apache-2.0-with-LLVM-exception`
+const EXCEPTIONS: &[(&str, &str)] = &[
+    ("Autoconf-exception-2.0", "autoconf-exception"),
+    ("Autoconf-exception-3.0", "autoconf-exception"),
+    ("Classpath-exception-2.0", "classpath-exception"),
+    ("Font-exception-2.0", "font-exception"),
+    ("GCC-exception-2.0", "GCC-exception"),
+    ("GCC-exception-3.1", "GCC-exception"),
+    ("LLVM-exception", "LLVM-exception"),
+    ("Linux-syscall-note", "Linux-syscall-note"),
+    ("OpenSSL-exception", "OpenSSL-exception"),
+];
+
+fn exception_suffix(exception: &str) -> Option<&'static str> {
+    EXCEPTIONS
+        .iter()
+        .find(|(id, _)| *id == exception)
+        .map(|(_, suffix)| *suffix)
+}
+
+/// splits `license` into identifiers, operators and parentheses, with `/`
+/// standing for `OR`
+fn tokenize(license: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    for c in license.chars() {
+        if c.is_whitespace() || matches!(c, '(' | ')' | '/') {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+            match c {
+                '/' => tokens.push("OR".to_string()),
+                '(' | ')' => tokens.push(c.to_string()),
+                _ => {}
+            }
+        } else {
+            token.push(c);
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+fn is_operator(token: &str, operator: &str) -> bool {
+    token.eq_ignore_ascii_case(operator)
+}
+
+fn parse_or(tokens: &[String], pos: &mut usize) -> CargoResult<Expression> {
+    let mut exprs = vec![parse_and(tokens, pos)?];
+    while tokens
+        .get(*pos)
+        .map_or(false, |token| is_operator(token, "OR"))
+    {
+        *pos += 1;
+        exprs.push(parse_and(tokens, pos)?);
+    }
+    Ok(flatten(exprs, false))
+}
+
+fn parse_and(tokens: &[String], pos: &mut usize) -> CargoResult<Expression> {
+    let mut exprs = vec![parse_primary(tokens, pos)?];
+    while tokens
+        .get(*pos)
+        .map_or(false, |token| is_operator(token, "AND"))
+    {
+        *pos += 1;
+        exprs.push(parse_primary(tokens, pos)?);
+    }
+    Ok(flatten(exprs, true))
+}
+
+fn parse_primary(tokens: &[String], pos: &mut usize) -> CargoResult<Expression> {
+    let token = tokens
+        .get(*pos)
+        .ok_or_else(|| anyhow!("expected a license at the end"))?;
+    *pos += 1;
+    match token.as_str() {
+        "(" => {
+            let expr = parse_or(tokens, pos)?;
+            match tokens.get(*pos).map(String::as_str) {
+                Some(")") => {
+                    *pos += 1;
+                    Ok(expr)
+                }
+                _ => Err(anyhow!("missing ')'")),
+            }
+        }
+        ")" => Err(anyhow!("unexpected ')'")),
+        _ if is_operator(token, "AND")
+            || is_operator(token, "OR")
+            || is_operator(token, "WITH") =>
+        {
+            Err(anyhow!("expected a license before '{}'", token))
+        }
+        _ if tokens
+            .get(*pos)
+            .map_or(false, |next| is_operator(next, "WITH")) =>
+        {
+            *pos += 1;
+            match tokens.get(*pos).map(String::as_str) {
+                Some(exception) if !matches!(exception, "(" | ")") => {
+                    *pos += 1;
+                    Ok(Expression::With {
+                        license: token.clone(),
+                        exception: exception.to_string(),
+                    })
+                }
+                _ => Err(anyhow!("expected an exception after 'WITH'")),
+            }
+        }
+        _ => Ok(Expression::License(token.clone())),
+    }
+}
+
+/// a single expression as is, several joined by AND if `and`, by OR
+/// otherwise, merging the nested ones joined by the same operator
+fn flatten(mut exprs: Vec<Expression>, and: bool) -> Expression {
+    if exprs.len() == 1 {
+        return exprs.remove(0);
+    }
+    let mut flat = vec![];
+    for expr in exprs {
+        match expr {
+            Expression::And(nested) if and => flat.extend(nested),
+            Expression::Or(nested) if !and => flat.extend(nested),
+            expr => flat.push(expr),
+        }
+    }
+    if and {
+        Expression::And(flat)
+    } else {
+        Expression::Or(flat)
+    }
+}
+
 /// For a given file at path `license_file`, generate the MD5 sum
 fn file_md5<P: AsRef<Path>>(license_file: P) -> Result<String, io::Error> {
     let mut file = File::open(license_file)?;
@@ -24,53 +375,441 @@ fn file_md5<P: AsRef<Path>>(license_file: P) -> Result<String, io::Error> {
     Ok(format!("{:x}", context.compute()))
 }
 
-/// Given the top level of the crate at `crate_root`, attempt to find
-/// the license file based on the name of the license in `license_name`.
-pub fn file(crate_root: &Path, rel_dir: &Path, license_name: &str, single_license: bool) -> String {
+/// The digests of the license files in LIC_FILES_CHKSUM
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum LicChecksum {
+    #[default]
+    Md5,
+    Sha256,
+    Both,
+}
+
+impl FromStr for LicChecksum {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "md5" => Ok(Self::Md5),
+            "sha256" => Ok(Self::Sha256),
+            "both" => Ok(Self::Both),
+            _ => Err(anyhow!(
+                "unknown license checksum '{}', expected md5, sha256 or both",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for LicChecksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{}",
+            match *self {
+                LicChecksum::Md5 => "md5",
+                LicChecksum::Sha256 => "sha256",
+                LicChecksum::Both => "both",
+            }
+        )
+    }
+}
+
+impl LicChecksum {
+    /// the LIC_FILES_CHKSUM entry for the file at `path`, which is at
+    /// `rel_path` in the sources
+    fn entry(self, rel_path: &Path, path: &Path) -> String {
+        // bitbake hashes the file as is, CRLF line endings included
+        let generateme = |_| String::from("generateme");
+        self.entry_with(
+            rel_path,
+            || file_md5(path).unwrap_or_else(generateme),
+            || file_sha256(path).unwrap_or_else(generateme),
+        )
+    }
+
+    fn entry_with(
+        self,
+        rel_path: &Path,
+        md5: impl FnOnce() -> String,
+        sha256: impl FnOnce() -> String,
+    ) -> String {
+        let mut entry = format!("file://{}", rel_path.display());
+        if self != LicChecksum::Sha256 {
+            entry.push_str(&format!(";md5={}", md5()));
+        }
+        if self != LicChecksum::Md5 {
+            entry.push_str(&format!(";sha256={}", sha256()));
+        }
+        entry.push_str(" \\\n");
+        entry
+    }
+}
+
+/// The license file found for a license
+#[derive(Debug, Eq, PartialEq)]
+pub struct LicenseFile {
+    /// the LIC_FILES_CHKSUM entry
+    pub entry: String,
+    /// the less specific files next to it that would have matched, too
+    pub passed_over: Vec<String>,
+}
+
+/// names of license files that may be for any license, most common first
+const GENERIC_NAMES: &[&str] = &[
+    "LICENSE",
+    "LICENSE.txt",
+    "LICENSE.md",
+    "COPYING",
+    "COPYRIGHT",
+];
+
+/// names of license files that are for `license_name`, most specific first
+fn specific_names(license_name: &str) -> Vec<String> {
+    // the short form of e.g. LICENSE-APACHE for Apache-2.0
+    let short = license_name
+        .split(['-', '.'])
+        .next()
+        .unwrap_or(license_name)
+        .to_uppercase();
+    let mut names = vec![
+        license_name.to_string(),
+        format!("LICENSE-{}", license_name),
+        format!("LICENSES/{}.txt", license_name),
+        format!("LICENSE-{}", short),
+        format!("LICENSE-{}.txt", short),
+        format!("LICENSE-{}.md", short),
+    ];
+    if license_name == "Unlicense" {
+        names.push("UNLICENSE".to_string());
+    }
+    let mut seen = vec![];
+    names.retain(|name| {
+        let new = !seen.contains(name);
+        seen.push(name.clone());
+        new
+    });
+    names
+}
+
+/// Given the directories in `dirs`, each with the path of its LIC_FILES_CHKSUM
+/// entries, attempt to find the license file based on the name of the
+/// license in `license_name`: one specifically for it or else a generic
+/// one, in the first directory that has either. `None` if there is none.
+pub fn file(
+    dirs: &[(&Path, &Path)],
+    license_name: &str,
+    checksum: LicChecksum,
+) -> Option<LicenseFile> {
     // CLOSED is a special case license (case sensitive) per
     // http://www.yoctoproject.org/docs/2.3.2/mega-manual/mega-manual.html#sdk-license-detection
     // that means this is closed source and there is no license
     // under which this is released. So special case it
     if license_name == CLOSED_LICENSE {
-        return "".into();
-    }
-
-    // if the license exists at the top level then
-    // return the right URL to it. try to handle the special
-    // case license path we support as well
-    let special_name = format!("LICENSE-{}", license_name);
-    let lic_path = Path::new(license_name);
-    let spec_path = Path::new(&special_name);
-    let simple_path = Path::new("LICENSE");
-
-    let lic_abs_path = crate_root.join(lic_path);
-    let spec_abs_path = crate_root.join(spec_path);
-    let simple_abs_path = crate_root.join(simple_path);
-
-    if lic_abs_path.exists() {
-        let md5sum = file_md5(lic_abs_path).unwrap_or_else(|_| String::from("generateme"));
-        format!(
-            "file://{};md5={} \\\n",
-            rel_dir.join(lic_path).display(),
-            md5sum
+        return Some(LicenseFile {
+            entry: "".into(),
+            passed_over: vec![],
+        });
+    }
+
+    for (root, rel_dir) in dirs {
+        let generic = GENERIC_NAMES
+            .iter()
+            .filter(|name| root.join(name).is_file())
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        let name = match specific_names(license_name)
+            .into_iter()
+            .find(|name| root.join(name).is_file())
+        {
+            Some(name) => name,
+            None => match generic.first() {
+                Some(name) => name.clone(),
+                None => continue,
+            },
+        };
+
+        return Some(LicenseFile {
+            entry: checksum.entry(&rel_dir.join(&name), &root.join(&name)),
+            passed_over: generic.into_iter().filter(|other| *other != name).collect(),
+        });
+    }
+    None
+}
+
+/// Why the `license-file` of a manifest can't be used
+#[derive(Debug, Eq, PartialEq)]
+pub enum DeclaredFileError {
+    /// there is no such file
+    Missing,
+    /// it isn't in any of the directories the recipe's sources contain
+    Outside,
+}
+
+/// the LIC_FILES_CHKSUM entry for the `license-file` declared in the
+/// manifest in `pkg_root`, relative to the first of `dirs` containing it
+pub fn declared_file(
+    dirs: &[(&Path, &Path)],
+    pkg_root: &Path,
+    license_file: &str,
+    checksum: LicChecksum,
+) -> Result<String, DeclaredFileError> {
+    // resolve `..` without following symlinks, like cargo does
+    let mut path = PathBuf::new();
+    for part in pkg_root.join(license_file).components() {
+        match part {
+            Component::ParentDir => {
+                path.pop();
+            }
+            Component::CurDir => {}
+            part => path.push(part),
+        }
+    }
+    if !path.is_file() {
+        return Err(DeclaredFileError::Missing);
+    }
+    let (rel_dir, name) = dirs
+        .iter()
+        .find_map(|(root, rel_dir)| Some((rel_dir, path.strip_prefix(root).ok()?)))
+        .ok_or(DeclaredFileError::Outside)?;
+    Ok(checksum.entry(&rel_dir.join(name), &path))
+}
+
+/// the entry for the license file of `license_name` in `rel_dir` of the
+/// sources when it can't be found, for the maintainer to fill in
+pub fn placeholder(rel_dir: &Path, license_name: &str, checksum: LicChecksum) -> String {
+    let generateme = || String::from("generateme");
+    checksum.entry_with(&rel_dir.join(license_name), generateme, generateme)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn license_map() {
+        let map = LicenseMap::parse(
+            r#"
+            Zlib = "Zlib"
+            "Unicode-DFS-2016" = "Unicode-DFS-2016-custom"
+            "#,
         )
-    } else if spec_abs_path.exists() {
-        // the special case
-        let md5sum = file_md5(spec_abs_path).unwrap_or_else(|_| String::from("generateme"));
-        format!(
-            "file://{};md5={} \\\n",
-            rel_dir.join(spec_path).display(),
-            md5sum
+        .unwrap();
+        assert_eq!(map.get("Unicode-DFS-2016"), Some("Unicode-DFS-2016-custom"));
+        assert_eq!(map.get("MIT"), None);
+        assert!(LicenseMap::parse("MIT = 1").is_err());
+    }
+
+    fn entry(dirs: &[(&Path, &Path)], license_name: &str) -> Option<String> {
+        file(dirs, license_name, LicChecksum::Md5).map(|file| file.entry)
+    }
+
+    #[test]
+    fn license_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("LICENSE-MIT"), "MIT\n").unwrap();
+        fs::write(dir.path().join("COPYING"), "GPL\r\nv2\r\n").unwrap();
+        let dirs = [(dir.path(), Path::new("rust"))];
+
+        let mit = file(&dirs, "MIT", LicChecksum::Md5).unwrap();
+        assert_eq!(
+            mit.entry,
+            format!(
+                "file://rust/LICENSE-MIT;md5={:x} \\\n",
+                md5::compute("MIT\n")
+            )
+        );
+        assert_eq!(mit.passed_over, vec!["COPYING"]);
+        // the generic file for whatever has no file of its own
+        assert_eq!(
+            entry(&dirs, "GPL-2.0-only"),
+            Some(format!(
+                "file://rust/COPYING;md5={:x} \\\n",
+                md5::compute("GPL\r\nv2\r\n")
+            ))
+        );
+        assert_eq!(entry(&dirs, "CLOSED"), Some("".into()));
+    }
+
+    #[test]
+    fn license_file_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("member/LICENSES")).unwrap();
+        fs::write(root.join("LICENSE-APACHE"), "Apache\n").unwrap();
+        fs::write(root.join("UNLICENSE"), "Unlicense\n").unwrap();
+        fs::write(root.join("member/LICENSES/MIT.txt"), "MIT\n").unwrap();
+        let dirs = [
+            (root.join("member"), Path::new("member")),
+            (root.to_path_buf(), Path::new("")),
+        ];
+        let dirs = dirs
+            .iter()
+            .map(|(root, rel)| (root.as_path(), *rel))
+            .collect::<Vec<_>>();
+
+        let found = |license| entry(&dirs, license).unwrap();
+        assert!(found("MIT").starts_with("file://member/LICENSES/MIT.txt;"));
+        // the workspace root for what the member has no file for
+        assert!(found("Apache-2.0").starts_with("file://LICENSE-APACHE;"));
+        assert!(found("Unlicense").starts_with("file://UNLICENSE;"));
+        assert_eq!(entry(&dirs, "Zlib"), None);
+    }
+
+    #[test]
+    fn lic_checksums() {
+        let dir = tempfile::tempdir().unwrap();
+        // not valid UTF-8, hashed as the bytes they are
+        fs::write(dir.path().join("LICENSE"), b"Copyright \xa9 J\xfcrgen\n").unwrap();
+        let dirs = [(dir.path(), Path::new("sub"))];
+        let entry = |checksum| file(&dirs, "MIT", checksum).unwrap().entry;
+
+        let md5 = "md5=bef0433e5a984569399990ad63a29670";
+        let sha256 = "sha256=c557c7cbe34f6147586586718ab4647fbe624321f898e732a4f43562133b97f6";
+        assert_eq!(
+            entry(LicChecksum::Md5),
+            format!("file://sub/LICENSE;{} \\\n", md5)
+        );
+        assert_eq!(
+            entry(LicChecksum::Sha256),
+            format!("file://sub/LICENSE;{} \\\n", sha256)
+        );
+        assert_eq!(
+            entry(LicChecksum::Both),
+            format!("file://sub/LICENSE;{};{} \\\n", md5, sha256)
+        );
+        assert_eq!(
+            placeholder(Path::new(""), "LICENSE.custom", LicChecksum::Both),
+            "file://LICENSE.custom;md5=generateme;sha256=generateme \\\n"
+        );
+        assert_eq!(
+            placeholder(Path::new("sub"), "MIT", LicChecksum::Md5),
+            "file://sub/MIT;md5=generateme \\\n"
+        );
+    }
+
+    #[test]
+    fn declared_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("member/docs")).unwrap();
+        fs::write(root.join("member/docs/LICENSE.custom"), "Custom\n").unwrap();
+        fs::write(root.join("LICENSE"), "Custom\n").unwrap();
+        let member = root.join("member");
+        let dirs = [(member.as_path(), Path::new("member"))];
+
+        assert!(
+            declared_file(&dirs, &member, "./docs/LICENSE.custom", LicChecksum::Md5)
+                .unwrap()
+                .starts_with("file://member/docs/LICENSE.custom;md5=")
+        );
+        assert_eq!(
+            declared_file(&dirs, &member, "docs/LICENSE", LicChecksum::Md5),
+            Err(DeclaredFileError::Missing)
+        );
+        assert_eq!(
+            declared_file(&dirs, &member, "../LICENSE", LicChecksum::Md5),
+            Err(DeclaredFileError::Outside)
+        );
+        // unless the sources contain the workspace root, too
+        let dirs = [dirs[0], (root, Path::new(""))];
+        assert!(
+            declared_file(&dirs, &member, "../LICENSE", LicChecksum::Md5)
+                .unwrap()
+                .starts_with("file://LICENSE;md5=")
+        );
+    }
+
+    #[test]
+    fn aggregated() {
+        let deps = [
+            ("itoa", "MIT OR Apache-2.0"),
+            ("serde", "MIT/Apache-2.0"),
+            ("option-ext", "MPL-2.0"),
+            ("ring", "ISC AND OpenSSL"),
+            ("webpki", "ISC"),
+        ]
+        .iter()
+        .map(|(name, license)| (name.to_string(), Expression::parse(license).unwrap()))
+        .collect::<Vec<_>>();
+        let own = Expression::parse("MIT").unwrap();
+        assert_eq!(
+            aggregate(own, &deps).to_bitbake(),
+            "MIT & ISC & (MIT | Apache-2.0) & MPL-2.0 & OpenSSL"
+        );
+        assert_eq!(
+            uncommon_licenses_comment(&deps),
+            "# MPL-2.0: option-ext\n# OpenSSL: ring\n"
+        );
+        let own = Expression::parse("MIT OR Apache-2.0").unwrap();
+        assert_eq!(aggregate(own, &deps[..2]).to_bitbake(), "MIT | Apache-2.0");
+    }
+
+    fn bitbake(license: &str) -> String {
+        Expression::parse(license).unwrap().to_bitbake()
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(bitbake("MIT"), "MIT");
+        assert_eq!(bitbake("MIT OR Apache-2.0"), "MIT | Apache-2.0");
+        assert_eq!(bitbake("MIT/Apache-2.0"), "MIT | Apache-2.0");
+        assert_eq!(bitbake("MIT AND Zlib"), "MIT & Zlib");
+        assert_eq!(
+            bitbake("(MIT OR Apache-2.0) AND BSD-3-Clause"),
+            "(MIT | Apache-2.0) & BSD-3-Clause"
+        );
+        assert_eq!(
+            bitbake("MIT OR Apache-2.0 AND BSD-3-Clause"),
+            "MIT | Apache-2.0 & BSD-3-Clause"
+        );
+        assert_eq!(
+            bitbake("(MIT OR (Apache-2.0 OR Zlib))"),
+            "MIT | Apache-2.0 | Zlib"
+        );
+        assert_eq!(
+            Expression::parse("(MIT OR Apache-2.0) AND MIT")
+                .unwrap()
+                .licenses(),
+            vec!["MIT", "Apache-2.0"]
+        );
+        assert!(Expression::parse("MIT OR").is_err());
+        assert!(Expression::parse("(MIT").is_err());
+        assert!(Expression::parse("MIT Apache-2.0").is_err());
+        assert!(Expression::parse("").is_err());
+    }
+
+    #[test]
+    fn exceptions() {
+        assert_eq!(
+            bitbake("Apache-2.0 WITH LLVM-exception"),
+            "Apache-2.0-with-LLVM-exception"
+        );
+        assert_eq!(
+            bitbake("(Apache-2.0 WITH LLVM-exception OR MIT) AND Unicode-DFS-2016"),
+            "(Apache-2.0-with-LLVM-exception | MIT) & Unicode-DFS-2016"
+        );
+        let expr = Expression::parse("GPL-2.0-only WITH Foo-exception OR MIT").unwrap();
+        assert_eq!(expr.licenses(), vec!["GPL-2.0-only", "MIT"]);
+        assert_eq!(expr.unknown_exceptions(), vec!["Foo-exception"]);
+        assert!(Expression::parse("MIT WITH").is_err());
+        assert!(Expression::parse("WITH LLVM-exception").is_err());
+
+        let map = LicenseMap::parse(
+            r#"
+            "Apache-2.0 WITH LLVM-exception" = "Apache-2.0-LLVM"
+            "GPL-2.0-only" = "GPL-2.0"
+            "#,
         )
-    } else if simple_abs_path.exists() && single_license {
-        let md5sum = file_md5(simple_abs_path).unwrap_or_else(|_| String::from("generateme"));
-        format!(
-            "file://{};md5={} \\\n",
-            rel_dir.join(simple_path).display(),
-            md5sum
+        .unwrap();
+        let mut expr = Expression::parse(
+            "Apache-2.0 WITH LLVM-exception AND GPL-2.0-only WITH GCC-exception-2.0",
         )
-    } else {
-        // fall through
-        format!("file://{};md5=generateme \\\n", license_name)
+        .unwrap();
+        expr.map_licenses(&mut |lic| map.get(lic).map(str::to_string));
+        assert_eq!(
+            expr.to_bitbake(),
+            "Apache-2.0-LLVM & GPL-2.0-with-GCC-exception"
+        );
+        assert_eq!(expr.licenses(), vec!["Apache-2.0-LLVM", "GPL-2.0"]);
     }
 }
@@ -18,15 +18,23 @@ extern crate regex;
 extern crate structopt;
 
 use anyhow::{anyhow, Context as _};
+use cargo::core::compiler::{CompileKind, RustcTargetData};
+use cargo::core::dependency::{DepKind, Dependency};
 use cargo::core::resolver::CliFeatures;
+use cargo::core::shell::Verbosity;
 use cargo::core::{resolver::features::HasDevUnits, MaybePackage};
-use cargo::core::{GitReference, Package, PackageSet, Resolve, Workspace};
+use cargo::core::{
+    GitReference, Package, PackageId, PackageIdSpec, PackageIdSpecQuery, PackageSet,
+};
+use cargo::core::{Resolve, Shell, SourceId, Workspace};
 use cargo::ops;
 use cargo::util::{important_paths, CargoResult};
 use cargo::{core::registry::PackageRegistry, sources::CRATES_IO_DOMAIN};
-use cargo::{CliResult, GlobalContext};
+use cargo::{CliError, CliResult, GlobalContext};
 use itertools::Itertools;
+use lazy_static::lazy_static;
 use semver::Version;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::default::Default;
 use std::env;
 use std::fs::OpenOptions;
@@ -35,8 +43,32 @@ use std::path::{Path, PathBuf};
 use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
+mod archive;
+mod c_library;
+mod cache;
+mod cargo_config;
+mod compat;
+mod config;
+mod crate_license_files;
+mod description;
+mod diff;
+mod exclude;
+mod flavor;
 mod git;
 mod license;
+mod output;
+mod package_metadata;
+mod packageconfig;
+mod project_source;
+mod provenance;
+mod registry;
+mod section;
+mod spdx;
+mod src_uri;
+mod sys_deps;
+mod template;
+mod update;
+mod vendor;
 
 struct Metadata<'cfg> {
     name: &'cfg str,
@@ -46,6 +78,14 @@ struct Metadata<'cfg> {
     repository: Option<&'cfg str>,
     license: Option<&'cfg str>,
     license_file: Option<&'cfg str>,
+    authors: Vec<&'cfg str>,
+    categories: Vec<&'cfg str>,
+    keywords: Vec<&'cfg str>,
+    /// whether the package can be published to crates.io
+    publishable: bool,
+    rust_version: Option<String>,
+    /// the `bitbake` metadata table
+    bitbake: package_metadata::BitbakeMetadata,
 }
 
 impl<'cfg> Metadata<'cfg> {
@@ -81,6 +121,26 @@ impl<'cfg> Metadata<'cfg> {
                         })
                         .transpose()
                 };
+                let get_str_list = |field_name| -> CargoResult<Vec<&str>> {
+                    match metadata.get(field_name) {
+                        Some(field) => field
+                            .as_array()
+                            .with_context(|| {
+                                format!("'workspace.metadata.{field_name}' must be an array")
+                            })?
+                            .iter()
+                            .map(|item| {
+                                item.as_str().with_context(|| {
+                                    format!(
+                                        "'workspace.metadata.{field_name}' must only contain \
+                                         strings"
+                                    )
+                                })
+                            })
+                            .collect(),
+                        None => Ok(vec![]),
+                    }
+                };
 
                 Ok(Self {
                     name: get_str("name")?,
@@ -90,22 +150,59 @@ impl<'cfg> Metadata<'cfg> {
                     repository: get_str_opt("repository")?,
                     license: get_str_opt("license")?,
                     license_file: get_str_opt("license-file")?,
+                    authors: get_str_list("authors")?,
+                    categories: get_str_list("categories")?,
+                    keywords: get_str_list("keywords")?,
+                    publishable: false,
+                    // the oldest of the members, which may inherit it from
+                    // `workspace.package`
+                    rust_version: match get_str_opt("rust-version")? {
+                        Some(rust_version) => Some(rust_version.to_string()),
+                        None => ws.rust_version().map(ToString::to_string),
+                    },
+                    bitbake: package_metadata::BitbakeMetadata::from_metadata(
+                        Some(metadata),
+                        "workspace.metadata",
+                    )?,
                 })
             }
-            MaybePackage::Package(pkg) => {
-                let metadata = pkg.manifest().metadata();
-                Ok(Self {
-                    name: pkg.name().as_str(),
-                    version: pkg.version().clone(),
-                    description: metadata.description.as_deref(),
-                    homepage: metadata.homepage.as_deref(),
-                    repository: metadata.repository.as_deref(),
-                    license: metadata.license.as_deref(),
-                    license_file: metadata.license_file.as_deref(),
-                })
-            }
+            MaybePackage::Package(pkg) => Self::from_package(pkg, ws),
         }
     }
+
+    /// Uses the manifest metadata of a single package of `ws`, whose
+    /// `bitbake` metadata table provides the defaults of the package's one
+    fn from_package(pkg: &'cfg Package, ws: &Workspace<'_>) -> CargoResult<Self> {
+        let metadata = pkg.manifest().metadata();
+        let custom = pkg
+            .manifest()
+            .custom_metadata()
+            .and_then(toml::Value::as_table);
+        Ok(Self {
+            name: pkg.name().as_str(),
+            version: pkg.version().clone(),
+            description: metadata.description.as_deref(),
+            homepage: metadata.homepage.as_deref(),
+            repository: metadata.repository.as_deref(),
+            license: metadata.license.as_deref(),
+            license_file: metadata.license_file.as_deref(),
+            authors: metadata.authors.iter().map(String::as_str).collect(),
+            categories: metadata.categories.iter().map(String::as_str).collect(),
+            keywords: metadata.keywords.iter().map(String::as_str).collect(),
+            publishable: pkg.publish().as_ref().map_or(true, |registries| {
+                registries.iter().any(|r| r == "crates-io")
+            }),
+            rust_version: pkg.rust_version().map(ToString::to_string),
+            bitbake: package_metadata::BitbakeMetadata::from_metadata(
+                ws.custom_metadata().and_then(toml::Value::as_table),
+                "workspace.metadata",
+            )?
+            .layered(package_metadata::BitbakeMetadata::from_metadata(
+                custom,
+                "package.metadata",
+            )?),
+        })
+    }
 }
 
 /// Represents the package we are trying to generate a recipe for
@@ -113,25 +210,68 @@ struct Project<'cfg> {
     cfg: &'cfg GlobalContext,
     current_manifest: PathBuf,
     ws: Workspace<'cfg>,
+    /// workspace members to leave out
+    exclude: Vec<glob::Pattern>,
 }
 
 impl<'cfg> Project<'cfg> {
     /// creates our package info from the config and the `manifest_path`,
     /// which may not be provided
-    fn new(config: &GlobalContext, manifest_path: Option<String>) -> CargoResult<Project> {
-        let manifest_path = manifest_path.map_or_else(|| config.cwd().to_path_buf(), PathBuf::from);
+    fn new(config: &GlobalContext, manifest_path: Option<String>) -> CargoResult<Project<'_>> {
+        let manifest_path = manifest_path.map_or_else(
+            || config.cwd().to_path_buf(),
+            |path| config.cwd().join(path),
+        );
         let root = important_paths::find_root_manifest_for_wd(&manifest_path)?;
         let ws = Workspace::new(&root, config)?;
         Ok(Project {
             cfg: config,
             current_manifest: root,
             ws,
+            exclude: vec![],
         })
     }
 
+    /// leaves out the workspace members matching any of the `patterns`
+    fn excluding(mut self, patterns: &[String]) -> CargoResult<Self> {
+        self.exclude = patterns
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern)
+                    .with_context(|| format!("Invalid exclude pattern '{}'", pattern))
+            })
+            .collect::<CargoResult<_>>()?;
+        Ok(self)
+    }
+
+    fn is_excluded(&self, pkg: &Package) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.matches(pkg.name().as_str()))
+    }
+
     /// Returns the set of all packages in the workspace.
     fn packages(&self) -> Vec<&Package> {
-        self.ws.members().collect()
+        self.ws
+            .members()
+            .filter(|pkg| !self.is_excluded(pkg))
+            .collect()
+    }
+
+    /// Selects the workspace member matching `spec` (`name` or `name@version`)
+    fn select(&self, spec: &str) -> CargoResult<&Package> {
+        let pkg_spec = PackageIdSpec::parse(spec)
+            .with_context(|| format!("Invalid package spec '{}'", spec))?;
+        self.ws
+            .members()
+            .find(|pkg| pkg_spec.matches(pkg.package_id()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "package '{}' is not a member of the workspace, available members: {}",
+                    spec,
+                    self.ws.members().map(|pkg| pkg.name()).join(", ")
+                )
+            })
     }
 
     /// Generates a package registry by using the Cargo.lock or
@@ -145,47 +285,116 @@ impl<'cfg> Project<'cfg> {
         Ok(registry)
     }
 
-    /// Resolve the packages necessary for the workspace
-    fn resolve(&self, packages: &[&Package]) -> CargoResult<(PackageSet<'cfg>, Resolve)> {
+    /// Resolve the packages necessary for the workspace with the given
+    /// feature selection, including dev-dependencies if requested
+    fn resolve(
+        &self,
+        packages: &[&Package],
+        features: &CliFeatures,
+        dev_units: HasDevUnits,
+    ) -> CargoResult<(PackageSet<'cfg>, Resolve)> {
         // build up our registry
         let mut registry = self.registry(packages)?;
 
         // resolve our dependencies
-        let (packages, resolve) = ops::resolve_ws(&self.ws)?;
+        let (package_set, resolve) = ops::resolve_ws(&self.ws)?;
 
-        // resolve with all features set so we ensure we get all of the depends downloaded
+        // the packages whose features are being selected
+        let specs = packages
+            .iter()
+            .map(|package| package.package_id().to_spec())
+            .collect::<Vec<_>>();
+
+        // resolve with the requested features so we ensure we get all of the depends downloaded
         let resolve = ops::resolve_with_previous(
             &mut registry,
             &self.ws,
-            /* resolve it all */
-            &CliFeatures::new_all(true),
-            HasDevUnits::No,
+            features,
+            dev_units,
             /* previous */
             Some(&resolve),
             /* don't avoid any */
             None,
-            /* specs */
-            &[],
+            &specs,
             /* warn? */
             true,
         )?;
 
-        Ok((packages, resolve))
+        Ok((package_set, resolve))
+    }
+
+    /// Returns the packages of `resolve` which `packages` depend on when
+    /// built for any of `targets` or cargo's `build.target`, or all of them
+    /// if no target is given
+    fn dependencies(
+        &self,
+        resolve: &Resolve,
+        packages: &[&Package],
+        targets: &[String],
+        dev_units: HasDevUnits,
+    ) -> CargoResult<Vec<PackageId>> {
+        let kinds = CompileKind::from_requested_targets(self.cfg, targets)?;
+        if kinds.iter().all(CompileKind::is_host) {
+            return Ok(resolve.iter().collect());
+        }
+        let target_data = RustcTargetData::new(&self.ws, &kinds)?;
+        let reachable = reachable(resolve, packages, |dep| {
+            (dep.kind() != DepKind::Development || dev_units == HasDevUnits::Yes)
+                && kinds
+                    .iter()
+                    .any(|kind| target_data.dep_platform_activated(dep, *kind))
+        });
+
+        let dependencies = resolve
+            .iter()
+            .filter(|pkg_id| reachable.contains(pkg_id))
+            .collect::<Vec<_>>();
+        self.cfg.shell().status(
+            "Including",
+            format!(
+                "{} of {} packages used for {}",
+                dependencies.len(),
+                resolve.iter().count(),
+                kinds
+                    .iter()
+                    .map(|kind| target_data.short_name(kind))
+                    .join(", ")
+            ),
+        )?;
+        Ok(dependencies)
+    }
+
+    /// Returns the packages of `resolve` which `packages` link into their
+    /// build, through normal and build dependencies activated for any of
+    /// `targets` or cargo's `build.target`, or else for the host
+    fn linked_dependencies(
+        &self,
+        resolve: &Resolve,
+        packages: &[&Package],
+        targets: &[String],
+    ) -> CargoResult<HashSet<PackageId>> {
+        let kinds = CompileKind::from_requested_targets(self.cfg, targets)?;
+        let target_data = RustcTargetData::new(&self.ws, &kinds)?;
+        Ok(reachable(resolve, packages, |dep| {
+            dep.kind() != DepKind::Development
+                && kinds
+                    .iter()
+                    .any(|kind| target_data.dep_platform_activated(dep, *kind))
+        }))
     }
 
     /// packages that are part of a workspace are a sub directory from the
     /// top level which we need to record, this provides us with that
-    /// relative directory
-    fn rel_dir(&self) -> CargoResult<PathBuf> {
+    /// relative directory for the package whose Cargo.toml is at `manifest`,
+    /// or the current one if not provided
+    fn rel_dir(&self, manifest: Option<&Path>) -> CargoResult<PathBuf> {
         // this is the top level of the workspace
         let root = self.ws.root().to_path_buf();
+        let manifest = manifest.unwrap_or(&self.current_manifest);
         // path where our current package's Cargo.toml lives
-        let cwd = self.current_manifest.parent().ok_or_else(|| {
-            anyhow!(
-                "Could not get parent of directory '{}'",
-                self.current_manifest.display()
-            )
-        })?;
+        let cwd = manifest
+            .parent()
+            .ok_or_else(|| anyhow!("Could not get parent of directory '{}'", manifest.display()))?;
 
         cwd.strip_prefix(&root)
             .map(Path::to_path_buf)
@@ -193,6 +402,31 @@ impl<'cfg> Project<'cfg> {
     }
 }
 
+/// the packages of `resolve` reachable from `packages` through the
+/// dependencies `follow` accepts, including `packages` themselves
+fn reachable(
+    resolve: &Resolve,
+    packages: &[&Package],
+    follow: impl Fn(&Dependency) -> bool,
+) -> HashSet<PackageId> {
+    let mut reachable = HashSet::new();
+    let mut pending = packages
+        .iter()
+        .map(|pkg| pkg.package_id())
+        .collect::<Vec<_>>();
+    while let Some(pkg_id) = pending.pop() {
+        if !reachable.insert(pkg_id) {
+            continue;
+        }
+        for (dep_id, deps) in resolve.deps(pkg_id) {
+            if deps.iter().any(&follow) {
+                pending.push(dep_id);
+            }
+        }
+    }
+    reachable
+}
+
 #[derive(StructOpt, Debug)]
 struct Args {
     /// Silence all output
@@ -204,12 +438,618 @@ struct Args {
     verbose: usize,
 
     /// Reproducible mode: Output exact git references for git projects
-    #[structopt(short = "R")]
+    #[structopt(short = "R", long = "reproducible")]
     reproducible: bool,
 
     /// Legacy Overrides: Use legacy override syntax
     #[structopt(short = "l", long = "--legacy-overrides")]
     legacy_overrides: bool,
+
+    /// Yocto release the recipe is for, e.g. dunfell, kirkstone or
+    /// scarthgap, picking the override syntax, git protocol and PV suffix
+    /// it needs
+    #[structopt(
+        long = "yocto-release",
+        value_name = "NAME",
+        parse(try_from_str = compat::Release::by_name)
+    )]
+    yocto_release: Option<&'static compat::Release>,
+
+    /// Generate the recipe for a single workspace member (`name` or `name@version`)
+    #[structopt(short = "p", long = "package")]
+    package: Option<String>,
+
+    /// Generate one recipe for every workspace member with a binary target
+    #[structopt(long = "workspace", conflicts_with = "package")]
+    workspace: bool,
+
+    /// With --workspace, also generate recipes for library-only members
+    #[structopt(long = "include-libs", requires = "workspace")]
+    include_libs: bool,
+
+    /// Generate a recipe for a package that only builds a library
+    #[structopt(long = "lib")]
+    lib: bool,
+
+    /// Generate the recipe even if nothing would be installed, e.g. to
+    /// list the dependencies only
+    #[structopt(long = "allow-empty")]
+    allow_empty: bool,
+
+    /// Name of the recipe (PN) instead of the crate name
+    #[structopt(long = "name", value_name = "PN", conflicts_with = "workspace")]
+    name: Option<String>,
+
+    /// Generate a recipe named `<name>-<flavor>` with the default features
+    /// plus the given ones, e.g. `full:features=tls,metrics`. May be given
+    /// multiple times
+    #[structopt(long = "flavor", number_of_values = 1, conflicts_with = "workspace")]
+    flavor: Vec<flavor::Flavor>,
+
+    /// Version of the recipe (PV) instead of the one in Cargo.toml
+    #[structopt(long = "pv", value_name = "VERSION")]
+    pv: Option<String>,
+
+    /// Path to the Cargo.toml of the project, defaults to the one found
+    /// from the current directory
+    #[structopt(long = "manifest-path", value_name = "PATH")]
+    manifest_path: Option<String>,
+
+    /// Leave out workspace members matching this name or glob (e.g. `fuzz-*`),
+    /// may be given multiple times
+    #[structopt(long = "exclude", number_of_values = 1)]
+    exclude: Vec<String>,
+
+    /// Point cargo at the checkout of each git repository instead of at
+    /// every crate from it
+    #[structopt(long = "group-git-repos")]
+    group_git_repos: bool,
+
+    /// Also fetch dev-dependencies, e.g. for recipes running the tests on
+    /// target (ptest)
+    #[structopt(long = "include-dev-deps")]
+    include_dev_deps: bool,
+
+    /// Only replace the managed block of an existing recipe, keeping the
+    /// rest of the file and SRC_URI entries added to the block by hand
+    #[structopt(long = "update")]
+    update: bool,
+
+    /// With --update, drop SRC_URI entries cargo-bitbake did not generate
+    #[structopt(long = "prune-foreign", requires = "update")]
+    prune_foreign: bool,
+
+    /// Leave out comments and blank lines, keeping only a single line
+    /// recording the provenance
+    #[structopt(long = "compact")]
+    compact: bool,
+
+    /// Only include dependencies used when building for TRIPLE, may be
+    /// given multiple times (e.g. for the target and the build host)
+    #[structopt(long = "target", value_name = "TRIPLE", number_of_values = 1)]
+    target: Vec<String>,
+
+    /// Resolve dependencies with the default feature set instead of all features
+    #[structopt(long = "default-features-only")]
+    default_features_only: bool,
+
+    /// Resolve with the default features plus these (comma or space
+    /// separated, may be given multiple times) and build with them
+    #[structopt(
+        long = "features",
+        value_name = "FEATURES",
+        number_of_values = 1,
+        conflicts_with = "flavor"
+    )]
+    features: Vec<String>,
+
+    /// Resolve and build without the default features
+    #[structopt(long = "no-default-features", conflicts_with = "flavor")]
+    no_default_features: bool,
+
+    /// Expose the cargo features of the package as PACKAGECONFIG knobs,
+    /// enabling the default ones
+    #[structopt(
+        long = "packageconfig",
+        conflicts_with_all = &["flavor", "features", "no-default-features"]
+    )]
+    packageconfig: bool,
+
+    /// Run without accessing the network, using only Cargo.lock and the
+    /// local cargo cache
+    #[structopt(long = "offline")]
+    offline: bool,
+
+    /// Require Cargo.lock to be up to date, failing instead of modifying it
+    #[structopt(long = "locked")]
+    locked: bool,
+
+    /// Require Cargo.lock and the cache to be up to date (implies --locked
+    /// and --offline)
+    #[structopt(long = "frozen")]
+    frozen: bool,
+
+    /// Unstable (nightly-only) flag passed on to cargo as with -Z, may be
+    /// given multiple times
+    #[structopt(
+        short = "Z",
+        long = "unstable-flag",
+        value_name = "FLAG",
+        number_of_values = 1
+    )]
+    unstable_flags: Vec<String>,
+
+    /// Cargo configuration override (KEY=VALUE or a file path) as with
+    /// cargo --config, may be given multiple times
+    #[structopt(long = "config", value_name = "KEY=VALUE", number_of_values = 1)]
+    cli_config: Vec<String>,
+
+    /// How the project itself is fetched: git, crate or github-archive
+    #[structopt(long = "project-source", default_value = "git")]
+    project_source: project_source::ProjectSource,
+
+    /// Local copy of the project archive to compute its checksum from
+    /// instead of downloading it
+    #[structopt(long = "project-archive", parse(from_os_str))]
+    project_archive: Option<PathBuf>,
+
+    /// Path to the cargo-bitbake config file (default: cargo-bitbake.toml
+    /// at the workspace root)
+    #[structopt(long = "config-file", parse(from_os_str))]
+    config_file: Option<PathBuf>,
+
+    /// TOML file of `from = "to"` pairs renaming the license identifiers
+    /// of the crate
+    #[structopt(long = "license-map", value_name = "FILE", parse(from_os_str))]
+    license_map: Option<PathBuf>,
+
+    /// AND the licenses of all crates built into the package into LICENSE,
+    /// with a comment naming the crates with less common ones
+    #[structopt(long = "aggregate-licenses")]
+    aggregate_licenses: bool,
+
+    /// Add the license files of the crates from crates.io to
+    /// LIC_FILES_CHKSUM, which downloads all of them once
+    #[structopt(long = "crate-license-files")]
+    crate_license_files: bool,
+
+    /// Digests of the license files in LIC_FILES_CHKSUM: md5, sha256 or
+    /// both
+    #[structopt(long = "lic-checksum", value_name = "DIGEST", default_value = "md5")]
+    lic_checksum: license::LicChecksum,
+
+    /// Fail if a license has no name in the license map or oe-core's
+    /// common-licenses
+    #[structopt(long = "deny-unknown-licenses")]
+    deny_unknown_licenses: bool,
+
+    /// Warn about a license-file that doesn't exist or is outside the
+    /// package and leave its md5 to be filled in by hand instead of failing
+    #[structopt(long = "lenient")]
+    lenient: bool,
+
+    /// Fail instead of assuming the CLOSED license when Cargo.toml has
+    /// neither license nor license-file
+    #[structopt(long = "closed-is-error")]
+    closed_is_error: bool,
+
+    /// Protocol used to fetch git repositories: https, ssh or git
+    /// (default: the protocol of each repository's URL)
+    #[structopt(long = "git-protocol", value_name = "PROTOCOL")]
+    git_protocol: Option<git::GitProtocol>,
+
+    /// Name the tag of git dependencies pinned to one with ;tag= in their
+    /// SRC_URI, for a --yocto-release of styhead or later
+    #[structopt(long = "git-tags")]
+    git_tags: bool,
+
+    /// Git remote the project is fetched from (default: the one of the
+    /// repository field of Cargo.toml, or origin)
+    #[structopt(long = "remote", value_name = "NAME")]
+    remote: Option<String>,
+
+    /// Treat the project's revision as the tag NAME, e.g. in a shallow clone
+    /// without tags
+    #[structopt(long = "assume-tag", value_name = "NAME")]
+    assume_tag: Option<String>,
+
+    /// Fetch git dependencies from local repositories, e.g. file:// URLs,
+    /// instead of leaving a placeholder for them
+    #[structopt(long = "allow-local-git")]
+    allow_local_git: bool,
+
+    /// Leave a TODO for path dependencies outside the workspace instead of
+    /// failing, their sources have to be fetched by hand
+    #[structopt(long = "allow-external-paths")]
+    allow_external_paths: bool,
+
+    /// Commit (full sha or ref) of the project to fetch instead of HEAD
+    #[structopt(long = "srcrev", value_name = "SHA")]
+    srcrev: Option<String>,
+
+    /// Branch the project is fetched from (and `${AUTOREV}` follows)
+    /// instead of the one checked out
+    #[structopt(long = "branch", value_name = "NAME")]
+    branch: Option<String>,
+
+    /// Fail with a summary of all problems instead of writing a recipe when any warning is emitted
+    ///
+    /// Warnings cover:
+    ///   - Cargo.toml: no description, homepage, license (CLOSED) or rust-version
+    ///   - a package name with `_`
+    ///   - licenses: unmapped, unknown exceptions, license files that can't be found
+    ///   - git: no repository, a missing --branch, a dirty tree, a shallow clone
+    ///   - git dependencies that can't be grouped, ${AUTOREV} breaking --classextend
+    ///   - SRC_URI overrides and --exclude-crate patterns that match nothing
+    ///   - crates left out with --exclude-crate, -sys crates without known DEPENDS
+    ///   - default features --packageconfig can't expose
+    ///   - packages building both binaries and a C library
+    ///   - an empty SUMMARY or HOMEPAGE
+    #[structopt(long = "strict", verbatim_doc_comment)]
+    strict: bool,
+
+    /// Write the dependencies, license and other settings shared by all
+    /// versions to <name>.inc and keep only the version specific ones in
+    /// the recipe
+    #[structopt(long = "split-inc", conflicts_with = "template")]
+    split_inc: bool,
+
+    /// Compare the recipes with the existing files instead of writing them,
+    /// exiting with 1 if they differ and 2 if a file is missing
+    #[structopt(long = "check", conflicts_with = "format")]
+    check: bool,
+
+    /// Also report a different cargo-bitbake version in the header as a
+    /// difference with --check
+    #[structopt(long = "check-tool-version", requires = "check")]
+    check_tool_version: bool,
+
+    /// Generate the recipes and report what would be written without
+    /// writing anything
+    #[structopt(long = "dry-run", conflicts_with_all = &["check", "format"])]
+    dry_run: bool,
+
+    /// What to write: recipe files or a JSON description of them on stdout
+    #[structopt(long = "format", value_name = "FORMAT", default_value = "recipe")]
+    format: output::Format,
+
+    /// Write a <name>_%.bbappend updating the crates of an existing recipe
+    /// instead of a recipe
+    #[structopt(
+        long = "bbappend",
+        conflicts_with_all = &["template", "split-inc", "crates-inc"]
+    )]
+    bbappend: bool,
+
+    /// Name the .bbappend after the exact version instead of using `%`
+    #[structopt(long = "bbappend-exact", requires = "bbappend")]
+    bbappend_exact: bool,
+
+    /// The recipe the .bbappend applies to, whose crates that are no longer
+    /// needed it removes from SRC_URI
+    #[structopt(long = "base-recipe", value_name = "PATH", requires = "bbappend")]
+    base_recipe: Option<PathBuf>,
+
+    /// Variants to add with BBCLASSEXTEND, e.g. "native,nativesdk" for
+    /// crates used as build tools
+    #[structopt(long = "classextend", value_name = "LIST")]
+    classextend: Option<String>,
+
+    /// Name the recipe after the crate even if it contains underscores
+    /// instead of replacing them with dashes
+    #[structopt(long = "keep-underscores", conflicts_with = "name")]
+    keep_underscores: bool,
+
+    /// Also set RUST_VERSION_MIN to the rust-version of Cargo.toml for
+    /// classes checking the toolchain
+    #[structopt(long = "rust-version-var")]
+    rust_version_var: bool,
+
+    /// MAINTAINER of the recipe instead of the authors in Cargo.toml, e.g.
+    /// "Jane Doe <jane@example.com>"
+    #[structopt(long = "maintainer", value_name = "NAME")]
+    maintainer: Option<String>,
+
+    /// CVE_PRODUCT cve-check matches advisories against (default: the
+    /// crate name, only emitted when it differs from the recipe name)
+    #[structopt(long = "cve-product", value_name = "NAME")]
+    cve_product: Option<String>,
+
+    /// Regex matching the tags of releases for the upgrade check, with the
+    /// version in the `pver` group [default: v?(?P<pver>\d+(\.\d+)+)]
+    #[structopt(long = "upstream-tag-regex", value_name = "REGEX")]
+    upstream_tag_regex: Option<String>,
+
+    /// Leave out UPSTREAM_CHECK_URI and friends
+    #[structopt(long = "no-upstream-check", conflicts_with = "upstream-tag-regex")]
+    no_upstream_check: bool,
+
+    /// Recipe to add to DEPENDS, e.g. for a system library a crate links
+    /// against, may be given multiple times or comma separated
+    #[structopt(long = "depends", value_name = "RECIPE", number_of_values = 1)]
+    depends: Vec<String>,
+
+    /// TOML file of `crate = "recipe"` pairs adding to or overriding the
+    /// built-in DEPENDS of -sys crates, an empty recipe ignores the crate
+    #[structopt(long = "sys-deps-map", value_name = "FILE", parse(from_os_str))]
+    sys_deps_map: Option<PathBuf>,
+
+    /// Don't add DEPENDS for the native libraries of -sys crates
+    #[structopt(long = "no-sys-deps", conflicts_with = "sys-deps-map")]
+    no_sys_deps: bool,
+
+    /// SECTION of the recipe instead of the one derived from the
+    /// categories and keywords of the package
+    #[structopt(long = "section", value_name = "SECTION")]
+    section: Option<String>,
+
+    /// Class to inherit besides cargo, e.g. systemd, may be given multiple
+    /// times or comma separated
+    #[structopt(long = "inherit", value_name = "CLASS", number_of_values = 1)]
+    inherit: Vec<String>,
+
+    /// Don't add classes to the inherit line for what the dependency graph
+    /// uses, e.g. pkgconfig for the pkg-config crate
+    #[structopt(long = "no-auto-inherit")]
+    no_auto_inherit: bool,
+
+    /// Inherit cargo_c, or the class given with --cargo-c=CLASS, instead
+    /// of cargo for packages building a cdylib or staticlib
+    #[structopt(
+        long = "cargo-c",
+        value_name = "CLASS",
+        min_values = 0,
+        require_equals = true
+    )]
+    cargo_c: Option<Option<String>>,
+
+    /// Recipe to add to RDEPENDS of the package, e.g. for tools it runs,
+    /// may be given multiple times or comma separated
+    #[structopt(long = "rdepends", value_name = "RECIPE", number_of_values = 1)]
+    rdepends: Vec<String>,
+
+    /// Leave out crates matching this name or glob (e.g. `windows*`), e.g.
+    /// when a system library is used instead, may be given multiple times
+    #[structopt(long = "exclude-crate", value_name = "NAME", number_of_values = 1)]
+    exclude_crate: Vec<String>,
+
+    /// Bundle all dependencies into a single tarball next to the recipe
+    /// instead of fetching each of them
+    #[structopt(long = "vendor")]
+    vendor: bool,
+
+    /// Fetch the crates from their https download URLs instead of with the
+    /// crate:// fetcher, for releases that don't have it
+    #[structopt(long = "https-crates", conflicts_with = "vendor")]
+    https_crates: bool,
+
+    /// Fetch the crates of registries other than crates.io from this host
+    /// instead of the one their index names
+    #[structopt(long = "registry-host", value_name = "URL")]
+    registry_host: Option<String>,
+
+    /// Fetch the crates of registries other than crates.io from the download
+    /// URLs of their index instead of with the crate:// fetcher
+    #[structopt(long = "registry-downloads", conflicts_with = "vendor")]
+    registry_downloads: bool,
+
+    /// Write the crate:// entries and their checksums to <name>-crates.inc
+    /// and require it from the recipe
+    #[structopt(long = "crates-inc")]
+    crates_inc: bool,
+
+    /// Render the recipe from this template instead of the built-in one
+    #[structopt(long = "template", value_name = "PATH", parse(from_os_str))]
+    template: Option<PathBuf>,
+
+    /// Leave out dependencies whose source URL matches this glob (or regex
+    /// when prefixed with "re:"), may be given multiple times
+    #[structopt(long = "exclude-source", number_of_values = 1)]
+    exclude_source: Vec<String>,
+
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+
+    /// The command line arguments as given, recorded in the recipe header
+    #[structopt(skip)]
+    raw_args: Vec<String>,
+}
+
+impl Args {
+    /// The features given with --features
+    fn selected_features(&self) -> Vec<&str> {
+        self.features
+            .iter()
+            .flat_map(|list| list.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|feature| !feature.is_empty())
+            .collect()
+    }
+
+    /// Whether the features to build with are chosen explicitly rather
+    /// than resolving with all or the default ones
+    fn has_feature_selection(&self) -> bool {
+        !self.features.is_empty() || self.no_default_features
+    }
+
+    /// The feature selection dependencies are resolved with
+    fn cli_features(&self) -> CargoResult<CliFeatures> {
+        if self.has_feature_selection() {
+            let features = self
+                .selected_features()
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            CliFeatures::from_command_line(&features, false, !self.no_default_features)
+        } else {
+            Ok(CliFeatures::new_all(!self.default_features_only))
+        }
+    }
+
+    /// The cargo flags building with the --features and
+    /// --no-default-features the dependencies were resolved with
+    fn build_flags(&self) -> String {
+        let mut flags = vec![];
+        if self.no_default_features {
+            flags.push("--no-default-features".to_string());
+        }
+        let features = self.selected_features();
+        if !features.is_empty() {
+            // a single word, CARGO_BUILD_FLAGS is split on whitespace
+            // without any quote removal
+            flags.push(format!("--features {}", features.join(",")));
+        }
+        flags.join(" ")
+    }
+
+    /// Whether dev-dependencies are part of the resolve
+    fn dev_units(&self) -> HasDevUnits {
+        if self.include_dev_deps {
+            HasDevUnits::Yes
+        } else {
+            HasDevUnits::No
+        }
+    }
+
+    /// Name of the compatibility preset selected by these options
+    fn compat(&self) -> &'static str {
+        match self.yocto_release {
+            Some(release) => release.name,
+            None if self.legacy_overrides => "legacy",
+            None => "modern",
+        }
+    }
+
+    /// Whether overrides use the '_' syntax of releases before honister
+    fn uses_legacy_overrides(&self) -> bool {
+        self.legacy_overrides
+            || self
+                .yocto_release
+                .map_or(false, |release| !release.has_colon_overrides())
+    }
+
+    /// The separator of overrides, '_' before honister and ':' since
+    fn override_separator(&self) -> &'static str {
+        if self.uses_legacy_overrides() {
+            "_"
+        } else {
+            ":"
+        }
+    }
+
+    /// Whether git SRC_URIs name the branch of their revision instead of
+    /// nobranch=1, unless the release predates the fetcher asking for it
+    fn names_git_branches(&self) -> bool {
+        !self.uses_legacy_overrides()
+            && self
+                .yocto_release
+                .map_or(true, compat::Release::wants_git_branch)
+    }
+
+    /// Whether --git-tags names tags with ;tag=, which only the fetcher of
+    /// the --yocto-release styhead and later understands
+    fn names_git_tags(&self) -> bool {
+        self.git_tags
+            && self
+                .yocto_release
+                .map_or(false, compat::Release::has_git_tag_param)
+    }
+
+    /// Protocol git repositories are fetched with, if not the one of their URL
+    fn git_protocol(&self) -> Option<git::GitProtocol> {
+        match self.yocto_release {
+            Some(release) if self.git_protocol.is_none() && release.wants_https_git() => {
+                Some(git::GitProtocol::Https)
+            }
+            _ => self.git_protocol,
+        }
+    }
+
+    /// Fails if the options contradict the --yocto-release
+    fn check_yocto_release(&self) -> CargoResult<()> {
+        match self.yocto_release {
+            Some(release) if self.legacy_overrides && release.has_colon_overrides() => {
+                Err(anyhow!(
+                    "--legacy-overrides conflicts with --yocto-release {}, which uses the ':' \
+                 override syntax",
+                    release.name
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Describes the output decisions made for the --yocto-release
+    fn yocto_profile(&self) -> Option<String> {
+        let release = self.yocto_release?;
+        Some(format!(
+            "{} ({}): '{}' overrides, git protocol {}, {}, PV suffix {}",
+            release.name,
+            release.version,
+            self.override_separator(),
+            self.git_protocol().map_or_else(
+                || "from the URL".to_string(),
+                |protocol| protocol.to_string()
+            ),
+            if release.has_cargo_src_dir() {
+                "CARGO_SRC_DIR"
+            } else {
+                "S at the package"
+            },
+            if release.appends_srcrev() {
+                "+git"
+            } else {
+                ".AUTOINC+<rev>"
+            }
+        ))
+    }
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Regenerates a recipe using the options recorded in its header and
+    /// reports whether the output matches
+    #[structopt(name = "reproduce")]
+    Reproduce {
+        /// The previously generated recipe to verify
+        #[structopt(parse(from_os_str))]
+        recipe: PathBuf,
+    },
+    /// Refreshes the dependencies of an existing recipe or include file,
+    /// keeping everything else as is
+    #[structopt(name = "update")]
+    Update {
+        /// The recipe or include file to refresh
+        #[structopt(parse(from_os_str))]
+        recipe: PathBuf,
+    },
+    /// Reports which Yocto releases a recipe is compatible with
+    #[structopt(name = "compat-report")]
+    CompatReport {
+        /// The recipe to check, defaults to generating it in memory
+        #[structopt(parse(from_os_str))]
+        recipe: Option<PathBuf>,
+    },
+}
+
+/// A rendered recipe and where it should be written
+struct Recipe {
+    path: PathBuf,
+    contents: String,
+    provenance: provenance::Provenance,
+    /// number of SRC_URI entries for dependencies
+    crates: usize,
+    /// generated files the recipe requires
+    includes: Vec<Include>,
+    /// what `--format json` reports
+    data: output::RecipeData,
+    /// the dependencies bundled with --vendor
+    vendored: Option<vendor::Tarball>,
+}
+
+/// A generated file required by a recipe
+struct Include {
+    path: PathBuf,
+    contents: String,
 }
 
 #[derive(StructOpt, Debug)]
@@ -226,252 +1066,4186 @@ enum Opt {
     Bitbake(Args),
 }
 
+/// options that don't influence the generated recipes and are left out of
+/// the provenance
+const UNRECORDED_ARGS: &[&str] = &[
+    "-q",
+    "-v",
+    "-vv",
+    "-vvv",
+    "--check",
+    "--check-tool-version",
+    "--dry-run",
+];
+
+lazy_static! {
+    static ref URL_USERINFO: regex::Regex =
+        regex::Regex::new(r#"(?P<scheme>[A-Za-z][A-Za-z0-9+.-]*://)[^/@\s'"]+@"#).unwrap();
+}
+
+/// the arguments worth recording in the provenance, without the ones that
+/// don't influence the recipes and with the credentials of any URL, such as
+/// one in a `--config http.proxy=...` value, stripped
+fn recorded_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    args.into_iter()
+        .filter(|arg| !UNRECORDED_ARGS.contains(&arg.as_str()))
+        .map(|arg| URL_USERINFO.replace_all(&arg, "$scheme").into_owned())
+        .collect()
+}
+
+/// `args` up to the subcommand `name`, the first argument called that which
+/// isn't the value of an option, i.e. where the options before it parse on
+/// their own
+fn without_subcommand(mut args: Vec<String>, name: &str) -> Vec<String> {
+    let pos = (0..args.len()).find(|&pos| {
+        args[pos] == name
+            && Args::from_iter_safe(
+                std::iter::once("cargo-bitbake").chain(args[..pos].iter().map(String::as_str)),
+            )
+            .map_or(false, |args| args.cmd.is_none())
+    });
+    if let Some(pos) = pos {
+        args.truncate(pos);
+    }
+    args
+}
+
 fn main() {
     let mut config = GlobalContext::default().unwrap();
-    let Opt::Bitbake(opt) = Opt::from_args();
+    let Opt::Bitbake(mut opt) = Opt::from_args();
+    // skip the binary name and the "bitbake" subcommand name
+    opt.raw_args = recorded_args(env::args().skip(2));
+    // the subcommand comes last and isn't needed to regenerate the recipe
+    if let Some(Command::Update { .. }) = opt.cmd {
+        opt.raw_args = without_subcommand(opt.raw_args, "update");
+    }
     let result = real_main(opt, &mut config);
     if let Err(e) = result {
-        cargo::exit_with_error(e, &mut *config.shell());
+        cargo::exit_with_error(e, &mut config.shell());
     }
 }
 
 fn real_main(options: Args, config: &mut GlobalContext) -> CliResult {
-    config.configure(
-        options.verbose as u32,
-        options.quiet,
-        /* color */
-        None,
-        /* frozen */
-        false,
-        /* locked */
-        false,
-        /* offline */
-        false,
-        /* target dir */
-        &None,
-        /* unstable flags */
-        &[],
-        /* CLI config */
-        &[],
-    )?;
+    match options.cmd {
+        Some(Command::Reproduce { ref recipe }) => return reproduce(&options, recipe, config),
+        Some(Command::Update { ref recipe }) => return update_recipe(&options, recipe, config),
+        Some(Command::CompatReport { ref recipe }) => {
+            return compat_report(&options, recipe.as_deref(), config)
+        }
+        None => {}
+    }
 
-    // Build up data about the package we are attempting to generate a recipe for
-    let project = Project::new(config, None)?;
-    let metadata = Metadata::load(&project.ws)?;
+    configure(&options, config)?;
 
-    if metadata.name.contains('_') {
-        println!("Project name contains an underscore");
+    if options.format == output::Format::Json {
+        return print_json(&options, config);
+    }
+    if options.check {
+        return check(&options, config);
     }
 
-    // All packages in the workspace
-    let ws_packages = project.packages();
-    // Resolve all dependencies (generate or use Cargo.lock as necessary)
-    let (_, resolve) = project.resolve(&ws_packages)?;
+    for recipe in generate(&options, config)? {
+        // summarized along with the recipe by --dry-run
+        let summary = format!(
+            "{} SRC_URI entries for dependencies, LICENSE = \"{}\"",
+            recipe.crates, recipe.data.license
+        );
+        let recipe_path = recipe.path.clone();
 
-    // build the crate URIs
-    let mut src_uri_extras = vec![];
-    let mut src_uris = resolve
-        .iter()
-        .filter_map(|pkg| {
-            // get the source info for this package
-            let src_id = pkg.source_id();
-            if ws_packages.iter().any(|ws_pkg| ws_pkg.name() == pkg.name()) {
-                None
-            } else if src_id.is_crates_io() {
-                // this package appears in a crate registry
-                if let Some(Some(csum)) = resolve.checksums().get(&pkg) {
-                    src_uri_extras.push(format!(
-                        "SRC_URI[{name}-{version}.sha256sum] = \"{csum}\"",
-                        name = pkg.name(),
-                        version = pkg.version()
-                    ));
-                }
-                Some(format!(
-                    "    crate://{}/{}/{} \\\n",
-                    CRATES_IO_DOMAIN,
-                    pkg.name(),
-                    pkg.version()
-                ))
-            } else if src_id.is_path() {
-                // we don't want to spit out path based
-                // entries since they're within the crate
-                // we are packaging
-                None
-            } else if src_id.is_git() {
-                // Just use the default download method for git repositories
-                // found in the source URIs, since cargo currently cannot
-                // initialize submodules for git dependencies anyway.
-                let url = git::git_to_yocto_git_url(
-                    src_id.url().as_str(),
-                    Some(pkg.name().as_str()),
-                    git::GitPrefix::default(),
-                );
+        if let Some(tarball) = recipe.vendored {
+            if options.dry_run {
+                config
+                    .shell()
+                    .status("Would write", tarball.path.display())?;
+            } else {
+                write_file(config, &tarball.path, &tarball.contents)?;
+            }
+        }
 
-                // save revision
-                src_uri_extras.push(format!("SRCREV_FORMAT .= \"_{}\"", pkg.name()));
+        // the includes first so the recipe never requires a missing file
+        let files = recipe
+            .includes
+            .into_iter()
+            .map(|include| (include.path, include.contents))
+            .chain(std::iter::once((recipe.path, recipe.contents)));
+        for (path, mut contents) in files {
+            if options.update && path.exists() && is_managed(&options, &contents) {
+                contents = update_existing(&options, config, &path, &contents)?;
+            }
+            if !options.dry_run {
+                write_file(config, &path, &contents)?;
+            } else if path == recipe_path {
+                config
+                    .shell()
+                    .status("Would write", format!("{} ({})", path.display(), summary))?;
+            } else {
+                config.shell().status("Would write", path.display())?;
+            }
+        }
+    }
 
-                let precise = if options.reproducible {
-                    src_id.precise_git_fragment()
-                } else {
-                    None
-                };
+    // once at the end so it can be confirmed at a glance
+    if let Some(profile) = options.yocto_profile() {
+        config.shell().status("Profile", profile)?;
+    }
 
-                let rev = if let Some(precise) = precise {
-                    precise
-                } else {
-                    match *src_id.git_reference()? {
-                        GitReference::Tag(ref s) => s,
-                        GitReference::Rev(ref s) => {
-                            if s.len() == 40 {
-                                // avoid reduced hashes
-                                s
-                            } else {
-                                let precise = src_id.precise_git_fragment();
-                                if let Some(p) = precise {
-                                    p
-                                } else {
-                                    panic!("cannot find rev in correct format!");
-                                }
-                            }
-                        }
-                        GitReference::Branch(ref s) => {
-                            if s == "master" {
-                                "${AUTOREV}"
-                            } else {
-                                s
-                            }
-                        }
-                        GitReference::DefaultBranch => "${AUTOREV}",
-                    }
-                };
+    Ok(())
+}
 
-                src_uri_extras.push(format!("SRCREV_{} = \"{}\"", pkg.name(), rev));
-                // instruct Cargo where to find this
-                src_uri_extras.push(format!(
-                    "EXTRA_OECARGO_PATHS += \"${{WORKDIR}}/{}\"",
-                    pkg.name()
-                ));
+/// Compares what would be generated with the existing files, printing a
+/// diff of the differences
+fn check(options: &Args, config: &mut GlobalContext) -> CliResult {
+    let mut missing = false;
+    let mut differs = false;
+    for recipe in generate(options, config)? {
+        // the tarball is named after its checksum, so only its presence and
+        // integrity need checking
+        if let Some(tarball) = recipe.vendored {
+            match std::fs::read(&tarball.path) {
+                Ok(existing) if existing == tarball.contents => {}
+                Ok(_) => {
+                    config.shell().warn(format!(
+                        "{}: does not match the vendored crates",
+                        tarball.path.display()
+                    ))?;
+                    differs = true;
+                }
+                Err(_) => {
+                    config
+                        .shell()
+                        .warn(format!("{}: does not exist", tarball.path.display()))?;
+                    missing = true;
+                }
+            }
+        }
+        let files = recipe
+            .includes
+            .into_iter()
+            .map(|include| (include.path, include.contents))
+            .chain(std::iter::once((recipe.path, recipe.contents)));
+        for (path, mut contents) in files {
+            let existing = match std::fs::read_to_string(&path) {
+                Ok(existing) => existing,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    config
+                        .shell()
+                        .warn(format!("{}: does not exist", path.display()))?;
+                    missing = true;
+                    continue;
+                }
+                Err(e) => {
+                    return Err(anyhow::Error::new(e)
+                        .context(format!(
+                            "Unable to read bitbake recipe file '{}'",
+                            path.display()
+                        ))
+                        .into())
+                }
+            };
+            if options.update && is_managed(options, &contents) {
+                contents =
+                    update::update(&existing, &contents, options.compact, options.prune_foreign)?
+                        .contents;
+            }
 
-                Some(format!("    {} \\\n", url))
+            let (existing, contents) = if options.check_tool_version {
+                (existing, contents)
+            } else {
+                (
+                    provenance::without_tool_version(&existing),
+                    provenance::without_tool_version(&contents),
+                )
+            };
+            let name = path.display().to_string();
+            let diff = diff::unified(
+                &existing,
+                &contents,
+                &name,
+                &format!("{} (generated)", name),
+            );
+            if diff.is_empty() {
+                config.shell().status("Up-to-date", &name)?;
             } else {
-                Some(format!("    {} \\\n", src_id.url()))
+                config.shell().warn(format!("{}: out of date", name))?;
+                cargo::drop_eprint!(config, "{}", diff);
+                differs = true;
             }
-        })
-        .collect::<Vec<String>>();
+        }
+    }
 
-    // sort the crate list
-    src_uris.sort();
-    src_uri_extras.sort();
+    if missing {
+        Err(CliError::code(2))
+    } else if differs {
+        Err(CliError::code(1))
+    } else {
+        Ok(())
+    }
+}
 
-    // package description is used as BitBake summary
-    let summary = metadata.description.unwrap_or_else(|| {
-        println!("No 'description' field set in your Cargo.toml, using 'name' field");
-        metadata.name
-    });
+/// Prints what would be generated as a JSON document with the warnings
+/// collected instead of written to stderr
+fn print_json(options: &Args, config: &mut GlobalContext) -> CliResult {
+    let verbosity = config.shell().verbosity();
+    // warnings are part of the document even with --quiet
+    let (recipes, captured) = capture_shell(config, || generate(options, config));
+    let recipes = match recipes {
+        Ok(recipes) => recipes,
+        Err(e) => {
+            // there is no document to put the warnings in
+            eprint!("{}", captured);
+            return Err(e.into());
+        }
+    };
+    let (warnings, other) = output::split_warnings(&captured);
+    if verbosity != Verbosity::Quiet {
+        for line in other {
+            eprintln!("{}", line);
+        }
+    }
 
-    // package homepage (or source code location)
-    let homepage = metadata
-        .homepage
-        .map_or_else(
-            || {
-                println!("No 'homepage' field set in your Cargo.toml, trying 'repository' field");
-                metadata
-                    .repository
-                    .ok_or_else(|| anyhow!("No 'repository' field set in your Cargo.toml"))
-            },
-            Ok,
-        )?
-        .trim();
-
-    // package license
-    let license = metadata.license.unwrap_or_else(|| {
-        println!("No 'license' field set in your Cargo.toml, trying 'license-file' field");
-        metadata.license_file.unwrap_or_else(|| {
-            println!("No 'license-file' field set in your Cargo.toml");
-            println!("Assuming {} license", license::CLOSED_LICENSE);
-            license::CLOSED_LICENSE
-        })
+    let doc = output::Document {
+        schema_version: output::SCHEMA_VERSION,
+        recipes: recipes.into_iter().map(|recipe| recipe.data).collect(),
+        warnings,
+    };
+    let json = serde_json::to_string_pretty(&doc).context("Unable to serialize the output")?;
+    cargo::drop_println!(config, "{}", json);
+    Ok(())
+}
+
+/// runs `f` with the shell output captured, including the warnings that
+/// --quiet would drop
+fn capture_shell<T>(config: &GlobalContext, f: impl FnOnce() -> T) -> (T, String) {
+    let capture = output::Capture::default();
+    let mut shell = Shell::from_write(Box::new(capture.clone()));
+    shell.set_verbosity(match config.shell().verbosity() {
+        Verbosity::Quiet => Verbosity::Normal,
+        verbosity => verbosity,
     });
+    let shell = std::mem::replace(&mut *config.shell(), shell);
+    let result = f();
+    *config.shell() = shell;
+    (result, capture.contents())
+}
 
-    // compute the relative directory into the repo our Cargo.toml is at
-    let rel_dir = project.rel_dir()?;
+/// whether `--update` merges `contents` into the existing file rather than
+/// replacing it
+fn is_managed(options: &Args, contents: &str) -> bool {
+    options.compact || contents.contains(update::BEGIN_MARKER)
+}
 
-    // license files for the package
-    let mut lic_files = vec![];
-    let licenses: Vec<&str> = license.split('/').collect();
-    let single_license = licenses.len() == 1;
-    for lic in licenses {
-        lic_files.push(format!(
-            "    {}",
-            license::file(project.ws.root(), &rel_dir, lic, single_license)
-        ));
+/// Writes `contents` to `path` through a temporary file so the file is
+/// never left half written, leaving it alone if it is up to date
+fn write_file<C: AsRef<[u8]>>(config: &GlobalContext, path: &Path, contents: C) -> CargoResult<()> {
+    let contents = contents.as_ref();
+    if std::fs::read(path).ok().as_deref() == Some(contents) {
+        config.shell().status("Unchanged", path.display())?;
+        return Ok(());
     }
 
-    // license data in Yocto fmt
-    let license = license.split('/').map(str::trim).join(" | ");
-
-    // attempt to figure out the git repo for this project
-    let project_repo = git::ProjectRepo::new(config).unwrap_or_else(|e| {
-        println!("{}", e);
-        Default::default()
-    });
-
-    // if this is not a tag we need to include some data about the version in PV so that
-    // the sstate cache remains valid
-    let git_srcpv = if !project_repo.tag && project_repo.rev.len() > 10 {
-        let mut pv_append_key = "PV:append";
-        // Override PV override with legacy syntax if flagged
-        if options.legacy_overrides {
-            pv_append_key = "PV_append";
-        }
-        // we should be using ${SRCPV} here but due to a bitbake bug we cannot. see:
-        // https://github.com/meta-rust/meta-rust/issues/136
-        format!(
-            "{} = \".AUTOINC+{}\"",
-            pv_append_key,
-            &project_repo.rev[..10],
-        )
-    } else {
-        // its a tag so nothing needed
-        "".into()
-    };
-
-    // build up the path
-    let recipe_path = PathBuf::from(format!(
-        "{name}_{version}.bb",
-        name = metadata.name,
-        version = metadata.version,
-    ));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid bitbake recipe path '{}'", path.display()))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
 
     // Open the file where we'll write the BitBake recipe
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(&recipe_path)
+        .open(&tmp_path)
         .map_err(|e| anyhow!("Unable to open bitbake recipe file with: {}", e))?;
 
-    // write the contents out
-    write!(
-        file,
-        include_str!("bitbake.template"),
-        name = metadata.name,
-        version = metadata.version,
-        summary = summary,
-        homepage = homepage,
-        license = license,
-        lic_files = lic_files.join(""),
-        src_uri = src_uris.join(""),
-        src_uri_extras = src_uri_extras.join("\n"),
-        project_rel_dir = rel_dir.display(),
-        project_src_uri = project_repo.uri,
-        project_src_rev = project_repo.rev,
-        git_srcpv = git_srcpv,
-        cargo_bitbake_ver = env!("CARGO_PKG_VERSION"),
+    // write the contents out, not leaving the temporary file behind
+    let written = file
+        .write_all(contents)
+        .map_err(|e| anyhow!("Unable to write to bitbake recipe file with: {}", e))
+        .and_then(|_| {
+            std::fs::rename(&tmp_path, path)
+                .with_context(|| format!("Unable to replace '{}'", path.display()))
+        });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    written?;
+
+    config.shell().status("Wrote", path.display())?;
+    Ok(())
+}
+
+/// The `SRC_URI:remove` of a .bbappend for the crates of the base recipe
+/// that `src_uris`, the regenerated entries, no longer include
+fn bbappend_removals(
+    options: &Args,
+    config: &GlobalContext,
+    pn: &str,
+    src_uris: &[String],
+) -> CargoResult<String> {
+    let base_path = match options.base_recipe {
+        Some(ref path) => path,
+        None => {
+            config.shell().warn(
+                "The crates of the base recipe stay in SRC_URI and are fetched as well, \
+                 pass --base-recipe to remove those no longer needed",
+            )?;
+            return Ok(String::new());
+        }
+    };
+    let mut base = std::fs::read_to_string(base_path)
+        .with_context(|| format!("Unable to read '{}'", base_path.display()))?;
+    // the crates may be listed in the file the recipe requires
+    if base.contains(template::CRATES_INC_REQUIRE.trim_end()) {
+        let crates_inc = base_path.with_file_name(format!("{}-crates.inc", pn));
+        if let Ok(contents) = std::fs::read_to_string(crates_inc) {
+            base.push_str(&contents);
+        }
+    }
+    let fresh = src_uris
+        .iter()
+        .map(|entry| entry.trim().trim_end_matches('\\').trim())
+        .collect::<Vec<_>>();
+    let stale = update::dependency_entries(&base)
+        .into_iter()
+        .filter(|entry| !fresh.contains(&entry.as_str()))
+        .collect::<Vec<_>>();
+    if stale.is_empty() {
+        return Ok(String::new());
+    }
+    let mut remove = format!("SRC_URI{}remove = \" \\\n", options.override_separator());
+    for entry in stale {
+        remove.push_str(&format!("    {} \\\n", entry));
+    }
+    remove.push_str("\"\n");
+    Ok(remove)
+}
+
+/// Merges the freshly generated `contents` into the existing file at `path`
+fn update_existing(
+    options: &Args,
+    config: &GlobalContext,
+    path: &Path,
+    contents: &str,
+) -> CargoResult<String> {
+    let existing = std::fs::read_to_string(path)
+        .with_context(|| format!("Unable to read bitbake recipe file '{}'", path.display()))?;
+    let update = update::update(&existing, contents, options.compact, options.prune_foreign)
+        .with_context(|| format!("Unable to update '{}'", path.display()))?;
+
+    let mut shell = config.shell();
+    for entry in &update.preserved {
+        shell.status("Preserved", format!("SRC_URI entry {}", entry))?;
+    }
+    for entry in &update.pruned {
+        shell.status("Pruned", format!("SRC_URI entry {}", entry))?;
+    }
+    Ok(update.contents)
+}
+
+/// Replaces the generated parts of the recipe at `recipe_path` with freshly
+/// computed ones
+fn update_recipe(options: &Args, recipe_path: &Path, config: &mut GlobalContext) -> CliResult {
+    let existing = std::fs::read_to_string(recipe_path).with_context(|| {
+        format!(
+            "Unable to read bitbake recipe file '{}'",
+            recipe_path.display()
+        )
+    })?;
+
+    configure(options, config)?;
+
+    // the file may be named after an older version, so a run generating a
+    // single recipe is used for any file name
+    let file_name = recipe_path.file_name();
+    let mut files = generate(options, config)?
+        .into_iter()
+        .map(|recipe| {
+            recipe
+                .includes
+                .into_iter()
+                .map(|include| (include.path, include.contents))
+                .chain(std::iter::once((recipe.path, recipe.contents)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let fresh = match files
+        .iter()
+        .flatten()
+        .find(|(path, _)| path.file_name() == file_name)
+    {
+        Some((_, contents)) => contents.clone(),
+        None if files.len() == 1 => files.remove(0).pop().map(|(_, c)| c).unwrap_or_default(),
+        None => {
+            return Err(anyhow!(
+                "none of the generated recipes is named '{}'",
+                recipe_path.display()
+            )
+            .into())
+        }
+    };
+
+    let update = update::refresh(&existing, &fresh, options.prune_foreign)
+        .with_context(|| format!("Unable to update '{}'", recipe_path.display()))?;
+    {
+        let mut shell = config.shell();
+        for entry in &update.preserved {
+            shell.status("Preserved", format!("SRC_URI entry {}", entry))?;
+        }
+        for entry in &update.pruned {
+            shell.status("Pruned", format!("SRC_URI entry {}", entry))?;
+        }
+    }
+    if options.dry_run {
+        config
+            .shell()
+            .status("Would write", recipe_path.display())?;
+        return Ok(());
+    }
+    write_file(config, recipe_path, &update.contents)?;
+    Ok(())
+}
+
+/// Prints the compatibility of the recipe at `recipe_path`, or of the
+/// recipes generated with `options`, with each Yocto release
+fn compat_report(
+    options: &Args,
+    recipe_path: Option<&Path>,
+    config: &mut GlobalContext,
+) -> CliResult {
+    configure(options, config)?;
+
+    let recipes = match recipe_path {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path).with_context(|| {
+                format!("Unable to read bitbake recipe file '{}'", path.display())
+            })?;
+            vec![(path.to_path_buf(), contents)]
+        }
+        None => generate(options, config)?
+            .into_iter()
+            .flat_map(|recipe| {
+                recipe
+                    .includes
+                    .into_iter()
+                    .map(|include| (include.path, include.contents))
+                    .chain(std::iter::once((recipe.path, recipe.contents)))
+            })
+            .collect(),
+    };
+
+    for (path, contents) in recipes {
+        cargo::drop_println!(config, "{}:", path.display());
+        for report in compat::report(&contents) {
+            cargo::drop_println!(
+                config,
+                "  {} ({}): {}",
+                report.release.name,
+                report.release.version,
+                report.verdict()
+            );
+            for finding in &report.findings {
+                cargo::drop_println!(
+                    config,
+                    "    line {}: {}\n      {}",
+                    finding.line,
+                    finding.text,
+                    finding.reason
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Regenerates the recipe at `recipe_path` with the options recorded in its
+/// provenance header and compares the result against the existing file
+fn reproduce(options: &Args, recipe_path: &Path, config: &mut GlobalContext) -> CliResult {
+    let existing = std::fs::read_to_string(recipe_path).with_context(|| {
+        format!(
+            "Unable to read bitbake recipe file '{}'",
+            recipe_path.display()
+        )
+    })?;
+
+    let recorded = match provenance::Provenance::parse(&existing) {
+        Some(recorded) => recorded,
+        None => {
+            config.shell().warn(format!(
+                "{}: unverifiable, no provenance recorded (generated by an older cargo-bitbake?)",
+                recipe_path.display()
+            ))?;
+            return Ok(());
+        }
+    };
+
+    // re-parse the recorded options as if they were given on the command line
+    let mut recorded_options = Args::from_iter_safe(
+        std::iter::once("cargo-bitbake".to_string()).chain(recorded.options.iter().cloned()),
     )
-    .map_err(|e| anyhow!("Unable to write to bitbake recipe file with: {}", e))?;
+    .map_err(|e| anyhow!("Unable to parse the recorded options: {}", e))?;
+    recorded_options.raw_args = recorded.options.clone();
+    recorded_options.quiet = options.quiet;
+    recorded_options.verbose = options.verbose;
+
+    configure(&recorded_options, config)?;
+
+    // a run may generate several recipes, compare against the one we were given
+    let file_name = recipe_path.file_name();
+    let regenerated = generate(&recorded_options, config)?
+        .into_iter()
+        .find(|recipe| recipe.path.file_name() == file_name)
+        .ok_or_else(|| {
+            anyhow!(
+                "the recorded options no longer generate '{}'",
+                recipe_path.display()
+            )
+        })?;
+
+    // the generated files the recipe requires are checked along with it
+    let mut differing = vec![];
+    let files = regenerated
+        .includes
+        .iter()
+        .map(|include| {
+            (
+                recipe_path.with_file_name(include.path.file_name().unwrap_or_default()),
+                &include.contents,
+            )
+        })
+        .chain(std::iter::once((
+            recipe_path.to_path_buf(),
+            &regenerated.contents,
+        )));
+    for (path, contents) in files {
+        let existing = std::fs::read_to_string(&path).unwrap_or_default();
+        let mut contents = contents.clone();
+        if recorded_options.update && is_managed(&recorded_options, &contents) {
+            contents = update::update(
+                &existing,
+                &contents,
+                recorded_options.compact,
+                recorded_options.prune_foreign,
+            )?
+            .contents;
+        }
+        if contents != existing {
+            differing.push(path);
+        }
+    }
+    if differing.is_empty() {
+        config
+            .shell()
+            .status("Reproducible", recipe_path.display())?;
+        return Ok(());
+    }
+
+    let mut shell = config.shell();
+    for path in differing {
+        shell.warn(format!("{}: output differs", path.display()))?;
+    }
+    let diffs = recorded.differences(&regenerated.provenance);
+    if diffs.is_empty() {
+        shell.note("recorded inputs match, the project sources have likely changed")?;
+    }
+    for diff in diffs {
+        shell.note(diff)?;
+    }
+
+    Err(CliError::code(1))
+}
+
+/// Applies the global cargo options to `config`
+fn configure(options: &Args, config: &mut GlobalContext) -> CargoResult<()> {
+    // load the cargo configuration of the project rather than of the
+    // directory we were started from
+    if let Some(ref manifest_path) = options.manifest_path {
+        let manifest_path = config.cwd().join(manifest_path);
+        if let Some(dir) = manifest_path.parent() {
+            config.reload_rooted_at(dir)?;
+        }
+    }
+
+    config.configure(
+        options.verbose as u32,
+        options.quiet,
+        /* color */
+        None,
+        options.frozen,
+        options.locked,
+        options.offline,
+        /* target dir */
+        &None,
+        &options.unstable_flags,
+        &options.cli_config,
+    )?;
+
+    if config.shell().verbosity() == Verbosity::Verbose {
+        for line in cargo_config::describe(config)? {
+            config.shell().note(line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Generates the recipe for the current project in memory, failing with all
+/// of the warnings emitted along the way with --strict
+fn generate(options: &Args, config: &GlobalContext) -> CargoResult<Vec<Recipe>> {
+    if !options.strict {
+        return generate_recipes(options, config);
+    }
+
+    let (recipes, captured) = capture_shell(config, || generate_recipes(options, config));
+    {
+        let mut shell = config.shell();
+        if shell.verbosity() != Verbosity::Quiet {
+            shell.err().write_all(captured.as_bytes())?;
+        }
+    }
+    let recipes = recipes?;
+    let (warnings, _) = output::split_warnings(&captured);
+    if !warnings.is_empty() {
+        return Err(anyhow!(
+            "{} problem{} found with --strict:\n    {}",
+            warnings.len(),
+            if warnings.len() == 1 { "" } else { "s" },
+            warnings
+                .iter()
+                .map(|warning| warning.message.as_str())
+                .collect::<Vec<_>>()
+                .join("\n    ")
+        ));
+    }
+    Ok(recipes)
+}
+
+/// Generates the recipe for the current project in memory
+fn generate_recipes(options: &Args, config: &GlobalContext) -> CargoResult<Vec<Recipe>> {
+    options.check_yocto_release()?;
+    if options.git_tags && !options.names_git_tags() {
+        config.shell().warn(
+            "--git-tags needs --yocto-release styhead or later, whose git fetcher \
+             understands ;tag=, the tags are left out",
+        )?;
+    }
+    if let Some(ref name) = options.name {
+        check_pn(name)?;
+    }
+
+    // Build up data about the package we are attempting to generate a recipe for
+    let project =
+        Project::new(config, options.manifest_path.clone())?.excluding(&options.exclude)?;
+
+    let skipped = project
+        .ws
+        .members()
+        .filter(|pkg| project.is_excluded(pkg))
+        .map(|pkg| pkg.name())
+        .collect::<Vec<_>>();
+    if !skipped.is_empty() {
+        config.shell().status(
+            "Skipping",
+            format!("excluded workspace members: {}", skipped.join(", ")),
+        )?;
+    }
+
+    if !options.workspace {
+        // the member selected with -p, the whole workspace otherwise
+        let selected = options
+            .package
+            .as_deref()
+            .map(|spec| project.select(spec))
+            .transpose()?;
+        let packages = match selected {
+            Some(pkg) => vec![pkg],
+            None => project.packages(),
+        };
+        check_installable(options, &packages)?;
+        if options.flavor.is_empty() {
+            return Ok(vec![generate_recipe(
+                options, config, &project, selected, None,
+            )?]);
+        }
+
+        // one recipe per flavor, each with its own dependency closure
+        let mut recipes = vec![];
+        for flavor in &options.flavor {
+            recipes.push(generate_recipe(
+                options,
+                config,
+                &project,
+                selected,
+                Some(flavor),
+            )?);
+        }
+        let smallest = recipes
+            .iter()
+            .map(|recipe| recipe.crates)
+            .min()
+            .unwrap_or(0);
+        let sizes = options
+            .flavor
+            .iter()
+            .zip(&recipes)
+            .map(|(flavor, recipe)| {
+                format!(
+                    "{} {} crates (+{})",
+                    flavor.name,
+                    recipe.crates,
+                    recipe.crates - smallest
+                )
+            })
+            .join(", ");
+        config.shell().status("Flavors", sizes)?;
+        return Ok(recipes);
+    }
+
+    // one recipe per member that installs something
+    let mut recipes = vec![];
+    for pkg in project.packages() {
+        if !is_installable(pkg) && !options.include_libs {
+            config.shell().status(
+                "Skipping",
+                format!(
+                    "library member {} (use --include-libs to generate it)",
+                    pkg.name()
+                ),
+            )?;
+            continue;
+        }
+        recipes.push(generate_recipe(options, config, &project, Some(pkg), None)?);
+    }
+    if recipes.is_empty() {
+        check_installable(options, &project.packages())?;
+    }
+    Ok(recipes)
+}
+
+/// Fails if `pn` cannot be used as the name of a recipe
+fn check_pn(pn: &str) -> CargoResult<()> {
+    let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c);
+    if pn.is_empty() || !pn.chars().all(valid) {
+        return Err(anyhow!(
+            "--name '{}' is not a valid BitBake recipe name, it may only contain \
+             lowercase letters, digits, '+', '-' and '.'",
+            pn
+        ));
+    }
+    Ok(())
+}
+
+/// Fails if `pv` cannot be used as the version of a recipe
+fn check_pv(pv: &str) -> CargoResult<()> {
+    if pv.is_empty() {
+        return Err(anyhow!("--pv must not be empty"));
+    }
+    if let Some(c) = pv.chars().find(|c| c.is_whitespace() || *c == '_') {
+        return Err(anyhow!(
+            "--pv '{}' is not a valid BitBake version, it must not contain {:?}",
+            pv,
+            c
+        ));
+    }
+    Ok(())
+}
+
+/// Whether building `pkg` produces something a recipe can install
+fn is_installable(pkg: &Package) -> bool {
+    pkg.targets()
+        .iter()
+        .any(|target| target.is_bin() || target.is_cdylib() || target.is_staticlib())
+}
+
+/// Fails unless one of `packages` builds a binary or a C library, or the
+/// options allow packaging without one
+fn check_installable(options: &Args, packages: &[&Package]) -> CargoResult<()> {
+    if options.allow_empty || packages.iter().any(|pkg| is_installable(pkg)) {
+        return Ok(());
+    }
+
+    let is_lib = |pkg: &Package| {
+        pkg.targets()
+            .iter()
+            .any(|target| target.is_lib() && !target.proc_macro())
+    };
+    if options.lib && packages.iter().any(|pkg| is_lib(pkg)) {
+        return Ok(());
+    }
+
+    let found = packages
+        .iter()
+        .map(|pkg| {
+            let kind = if pkg.targets().iter().any(|target| target.proc_macro()) {
+                "proc-macro"
+            } else if is_lib(pkg) {
+                "lib-only"
+            } else {
+                "no library or binary"
+            };
+            format!("{} ({})", pkg.name(), kind)
+        })
+        .join(", ");
+    Err(anyhow!(
+        "no package builds a bin, cdylib or staticlib target so the recipe \
+         would not install anything, found: {}\n\
+         use -p to choose a different member, --lib to package a library \
+         or --allow-empty to generate the recipe anyway",
+        found
+    ))
+}
+
+/// The SRCREV to record for the git dependency `name` from `src_id`, with
+/// `reproducible` the commit Cargo.lock records for any kind of reference
+fn git_srcrev(name: &str, src_id: SourceId, reproducible: bool) -> CargoResult<String> {
+    let precise = if reproducible {
+        src_id.precise_git_fragment()
+    } else {
+        None
+    };
+
+    let rev = if let Some(precise) = precise {
+        precise
+    } else {
+        let reference = src_id
+            .git_reference()
+            .ok_or_else(|| anyhow!("{} is not a git dependency", name))?;
+        match *reference {
+            GitReference::Tag(ref s) => s,
+            GitReference::Rev(ref s) => {
+                if s.len() == 40 {
+                    // avoid reduced hashes
+                    s
+                } else {
+                    src_id.precise_git_fragment().ok_or_else(|| {
+                        anyhow!(
+                            "Unable to find the commit of the git dependency {} at rev '{}', \
+                             run `cargo update -p {}` to record it in Cargo.lock or use the \
+                             full 40 character sha",
+                            name,
+                            s,
+                            name
+                        )
+                    })?
+                }
+            }
+            // followed on the branch the SRC_URI names
+            GitReference::Branch(_) | GitReference::DefaultBranch => "${AUTOREV}",
+        }
+    };
+    Ok(rev.to_string())
+}
 
-    println!("Wrote: {}", recipe_path.display());
+/// Where a git dependency from `src_id` at `rev` is fetched from: the
+/// branch of its reference or, for `${AUTOREV}` to follow, the default
+/// branch of the repository unless `offline` or the release predates
+/// naming branches, asked for once per repository in `default_branches`.
+/// Tags, revisions and the exact commits of --reproducible may not be on
+/// any branch.
+fn git_pin(
+    src_id: SourceId,
+    rev: &str,
+    options: &Args,
+    offline: bool,
+    default_branches: &mut HashMap<String, Option<String>>,
+) -> git::GitPin {
+    let reference = match src_id.git_reference() {
+        Some(reference) => reference,
+        None => return git::GitPin::Detached,
+    };
+    match reference {
+        GitReference::Tag(tag) if options.names_git_tags() => git::GitPin::Tag(tag.clone()),
+        _ if rev != "${AUTOREV}" => git::GitPin::Detached,
+        GitReference::Branch(branch) => git::GitPin::Branch(branch.clone()),
+        GitReference::DefaultBranch if options.names_git_branches() && !offline => {
+            let url = src_id.url().as_str();
+            default_branches
+                .entry(url.to_string())
+                .or_insert_with(|| git::remote_default_branch(url))
+                .clone()
+                .map_or(git::GitPin::Detached, git::GitPin::Branch)
+        }
+        _ => git::GitPin::Detached,
+    }
+}
 
+/// Lists the crates left out with --exclude-crate, which are easily
+/// excluded by accident, and those the selected packages use directly
+fn warn_excluded_crates(
+    config: &GlobalContext,
+    resolve: &Resolve,
+    packages: &[&Package],
+    excluded: &[PackageId],
+    patterns: &[glob::Pattern],
+) -> CargoResult<()> {
+    let mut shell = config.shell();
+    let crates = excluded
+        .iter()
+        .filter(|pkg| patterns.iter().any(|p| p.matches(pkg.name().as_str())))
+        .collect::<Vec<_>>();
+    if !crates.is_empty() {
+        shell.warn(format!(
+            "leaving out crates matching --exclude-crate: {}",
+            crates
+                .iter()
+                .map(|pkg| format!("{} {}", pkg.name(), pkg.version()))
+                .join(", ")
+        ))?;
+    }
+    for pattern in patterns {
+        if !crates
+            .iter()
+            .any(|pkg| pattern.matches(pkg.name().as_str()))
+        {
+            shell.warn(format!(
+                "--exclude-crate '{}' does not match any dependency",
+                pattern
+            ))?;
+        }
+    }
+    for pkg in packages {
+        for (dep, _) in resolve.deps(pkg.package_id()) {
+            if crates.contains(&&dep) {
+                shell.warn(format!(
+                    "{} is a direct dependency of {}, the recipe will not build unless \
+                     it is provided some other way",
+                    dep.name(),
+                    pkg.name()
+                ))?;
+            }
+        }
+    }
     Ok(())
 }
+
+/// the MAINTAINER assignment for --maintainer or the package authors, empty
+/// if there is nobody to name
+fn maintainer_line(maintainer: Option<&str>, authors: &[&str]) -> String {
+    let maintainer = match maintainer {
+        Some(maintainer) => maintainer.trim().to_string(),
+        None => authors
+            .iter()
+            .map(|author| author.trim())
+            .filter(|author| !author.is_empty())
+            .join(", "),
+    };
+    if maintainer.is_empty() {
+        String::new()
+    } else {
+        format!("MAINTAINER = \"{}\"\n", template::escape(&maintainer))
+    }
+}
+
+/// the shell function unpacking the crates fetched with --https-crates into
+/// the directory source set up by the cargo class, along with the checksum
+/// file cargo expects there
+fn unpack_crates(crates: &[(PackageId, Option<String>)]) -> String {
+    let mut func = String::from(
+        "do_unpack[postfuncs] += \"cargo_bitbake_unpack_crates\"\n\
+         cargo_bitbake_unpack_crates() {\n\
+         \tcd ${WORKDIR}/cargo_home/bitbake\n\
+         \tfor crate in *.crate; do\n\
+         \t\ttar -xzf \"$crate\" && rm \"$crate\"\n\
+         \tdone\n",
+    );
+    for (pkg, csum) in crates {
+        func.push_str(&format!(
+            "\techo '{}' > {}-{}/.cargo-checksum.json\n",
+            serde_json::json!({ "files": {}, "package": csum }),
+            pkg.name(),
+            pkg.version()
+        ));
+    }
+    func.push('}');
+    func
+}
+
+/// the names and license expressions of `crates`, for --aggregate-licenses
+fn crate_licenses(
+    config: &GlobalContext,
+    archives: &mut archive::ArchiveCache<'_>,
+    package_set: &PackageSet<'_>,
+    crates: &[PackageId],
+    checksums: &HashMap<PackageId, Option<String>>,
+) -> CargoResult<Vec<(String, license::Expression)>> {
+    let mut licenses = vec![];
+    let mut failed = vec![];
+    for id in crates {
+        let pkg = match archives.get_one(package_set, *id, checksums) {
+            Ok(pkg) => pkg,
+            Err(e) => {
+                failed.push(format!("{} {} ({:#})", id.name(), id.version(), e));
+                continue;
+            }
+        };
+        let metadata = pkg.manifest().metadata();
+        match (&metadata.license, &metadata.license_file) {
+            (Some(license), _) => match license::Expression::parse(license) {
+                Ok(expr) => licenses.push((id.name().to_string(), expr)),
+                Err(e) => failed.push(format!("{} {} ({:#})", id.name(), id.version(), e)),
+            },
+            (None, license_file) => config.shell().warn(format!(
+                "{} {} has no SPDX license{}, leaving it out of LICENSE",
+                id.name(),
+                id.version(),
+                license_file
+                    .as_ref()
+                    .map(|file| format!(" but the license file '{}'", file))
+                    .unwrap_or_default()
+            ))?,
+        }
+    }
+    if !failed.is_empty() {
+        return Err(anyhow!(
+            "Unable to inspect the licenses of the crates (downloading them may need \
+             network access, see --offline):\n    {}",
+            failed.join("\n    ")
+        ));
+    }
+    Ok(licenses)
+}
+
+/// crates build scripts find native libraries with, calling for pkgconfig
+const PKG_CONFIG_CRATES: &[&str] = &["pkg-config", "system-deps"];
+
+/// the inherit line of the recipe, with the classes the crates using
+/// each of `PKG_CONFIG_CRATES` call for and a comment naming them, followed
+/// by the `extra` ones in order
+fn inherit_line(
+    class: &str,
+    pkg_config_users: &BTreeMap<&str, BTreeSet<&str>>,
+    extra: &[String],
+) -> String {
+    let mut classes = vec![class];
+    if !pkg_config_users.is_empty() {
+        classes.push("pkgconfig");
+    }
+    for extra in extra {
+        if !classes.contains(&extra.as_str()) {
+            classes.push(extra);
+        }
+    }
+    let line = format!("inherit {}\n", classes.join(" "));
+    if pkg_config_users.is_empty() {
+        return line;
+    }
+    format!(
+        "# pkgconfig for {}\n{}",
+        pkg_config_users
+            .iter()
+            .map(|(helper, users)| format!("{} ({})", helper, users.iter().join(", ")))
+            .join(", "),
+        line
+    )
+}
+
+/// HOMEPAGE for a Cargo.toml without homepage: the repository, the web
+/// page of the git remote `remote` (default: 'origin') of the project at
+/// `root` or the crates.io page of a crate fetched from or known on
+/// crates.io, warning at each step. Empty if there is none of them.
+fn fallback_homepage(
+    config: &GlobalContext,
+    root: &Path,
+    remote: Option<&str>,
+    metadata: &Metadata<'_>,
+    from_crates_io: bool,
+) -> CargoResult<String> {
+    let mut shell = config.shell();
+    shell.warn("No 'homepage' field set in your Cargo.toml, trying 'repository' field")?;
+    if let Some(repository) = metadata.repository {
+        return Ok(repository.trim().to_string());
+    }
+    shell.warn("No 'repository' field set in your Cargo.toml, trying the project's git remote")?;
+    if let Some(url) = git::ProjectRepo::web_url(root, remote) {
+        return Ok(url);
+    }
+    // the index lookup reports its progress on the shell
+    drop(shell);
+    if from_crates_io
+        || (metadata.publishable && registry::known_on_crates_io(config, metadata.name))
+    {
+        config
+            .shell()
+            .warn("No web page found for the project's git remote, using its crates.io page")?;
+        return Ok(format!("https://crates.io/crates/{}", metadata.name));
+    }
+    config
+        .shell()
+        .warn("No homepage found for the project, HOMEPAGE is left empty")?;
+    Ok(String::new())
+}
+
+/// `path` as BitBake wants it in S and CARGO_SRC_DIR: with forward
+/// slashes and without `.` components
+fn source_dir(path: &Path) -> String {
+    path.components()
+        .filter_map(|part| match part {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .join("/")
+}
+
+/// How the checkout of a git dependency is laid out and fetched
+#[derive(Debug, Default, PartialEq)]
+struct GitCheckout {
+    /// the directory of the crate within the repository, empty at its root
+    subdir: String,
+    /// gitsm if the repository has submodules
+    prefix: git::GitPrefix,
+    /// whether the repository uses Git LFS
+    lfs: bool,
+}
+
+/// the checkout of the git dependency `id`
+fn git_checkout(package_set: &PackageSet<'_>, id: PackageId) -> GitCheckout {
+    match package_set.get_one(id) {
+        Ok(pkg) => checkout_of(pkg.root()),
+        Err(_) => GitCheckout::default(),
+    }
+}
+
+/// the checkout of the git repository with the crate at `root`
+fn checkout_of(root: &Path) -> GitCheckout {
+    let workdir = git2::Repository::discover(root)
+        .ok()
+        .and_then(|repo| repo.workdir()?.canonicalize().ok());
+    let workdir = match workdir {
+        Some(workdir) => workdir,
+        None => return GitCheckout::default(),
+    };
+    let subdir = root
+        .canonicalize()
+        .ok()
+        .and_then(|root| root.strip_prefix(&workdir).ok().map(source_dir))
+        .unwrap_or_default();
+    // cargo checks the submodules out along with the repository
+    let prefix = if workdir.join(".gitmodules").is_file() {
+        git::GitPrefix::GitSubmodule
+    } else {
+        git::GitPrefix::Git
+    };
+    GitCheckout {
+        subdir,
+        prefix,
+        lfs: git::uses_lfs(&workdir),
+    }
+}
+
+/// the EXTRA_OECARGO_PATHS line for the crates in `subdir` of the
+/// checkout unpacked to `destsuffix`
+fn oecargo_path(destsuffix: &str, subdir: &str) -> String {
+    if subdir.is_empty() {
+        format!("EXTRA_OECARGO_PATHS += \"${{WORKDIR}}/{}\"", destsuffix)
+    } else {
+        format!(
+            "EXTRA_OECARGO_PATHS += \"${{WORKDIR}}/{}/{}\"",
+            destsuffix, subdir
+        )
+    }
+}
+
+/// S and CARGO_SRC_DIR for the package in `src_dir` of the sources
+/// unpacked to `unpack_dir`, either with S at the top of the sources or,
+/// `combined`, at the package itself
+fn source_location(unpack_dir: &str, src_dir: &str, combined: bool) -> (String, String) {
+    let top = format!("${{WORKDIR}}/{}", unpack_dir);
+    if combined && !src_dir.is_empty() {
+        (format!("{}/{}", top, src_dir), String::new())
+    } else {
+        (top, src_dir.to_string())
+    }
+}
+
+/// the sorted and de-duplicated recipe names given as repeated or comma
+/// separated option values
+fn recipe_names(values: &[String]) -> Vec<&str> {
+    values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .sorted()
+        .dedup()
+        .collect()
+}
+
+/// Warns about what breaks or hurts the variants added with --classextend
+fn check_classextend(
+    config: &GlobalContext,
+    contents: &str,
+    includes: &[Include],
+    summary: &str,
+    homepage: &str,
+) -> CargoResult<()> {
+    let mut shell = config.shell();
+    for (field, value) in [("SUMMARY", summary), ("HOMEPAGE", homepage)] {
+        if value.is_empty() {
+            shell.warn(format!(
+                "{} is empty, which breaks the extended variants",
+                field
+            ))?;
+        }
+    }
+    if std::iter::once(contents)
+        .chain(includes.iter().map(|include| include.contents.as_str()))
+        .any(|contents| contents.contains("${AUTOREV}"))
+    {
+        shell.warn(
+            "the recipe uses ${AUTOREV}, which makes the native variants rebuild and \
+             miss the sstate cache, pin the git dependencies with --reproducible",
+        )?;
+    }
+    Ok(())
+}
+
+/// host of the SRC_URI entries standing in for local git repositories
+const LOCAL_GIT_PLACEHOLDER: &str = "TODO.invalid";
+
+/// Applies the override configured for `pkg` (by crate name or repository
+/// URL) on top of its generated SRC_URI entry, describing the change if
+/// there was one
+fn apply_src_uri_override(
+    bitbake_config: &config::Config,
+    pkg: PackageId,
+    uri: &mut src_uri::SrcUri,
+) -> Option<String> {
+    let overrides = &bitbake_config.src_uri_overrides;
+    let (key, src_override) = overrides
+        .get_key_value(pkg.name().as_str())
+        .or_else(|| overrides.get_key_value(pkg.source_id().url().as_str()))?;
+    let before = uri.to_string();
+    src_override.apply(uri);
+    Some(format!("{} ({}): {} -> {}", pkg.name(), key, before, uri))
+}
+
+/// Generates the recipe for the workspace member `selected`, or the whole
+/// workspace if not provided
+fn generate_recipe(
+    options: &Args,
+    config: &GlobalContext,
+    project: &Project,
+    selected: Option<&Package>,
+    flavor: Option<&flavor::Flavor>,
+) -> CargoResult<Recipe> {
+    let metadata = match selected {
+        Some(pkg) => Metadata::from_package(pkg, &project.ws)?,
+        None => Metadata::load(&project.ws)?,
+    };
+    let bitbake_config = config::Config::load(project.ws.root(), options.config_file.as_deref())?;
+
+    // the recipe name (PN), which can differ from the crate's
+    // underscores separate PN from PV in recipe file names
+    let pn = match options.name {
+        Some(ref name) => name.clone(),
+        None if options.keep_underscores => metadata.name.to_string(),
+        None => {
+            let pn = metadata.name.replace('_', "-");
+            if pn != metadata.name {
+                config.shell().note(format!(
+                    "naming the recipe {} after crate {} (use --keep-underscores to keep the \
+                     crate name)",
+                    pn, metadata.name
+                ))?;
+            }
+            pn
+        }
+    };
+    let pn = match flavor {
+        Some(flavor) => format!("{}-{}", pn, flavor.name),
+        None => pn.to_string(),
+    };
+    if pn.contains('_') {
+        config.shell().warn(
+            "Project name contains an underscore, use --name to set a recipe name without one",
+        )?;
+    }
+
+    // All packages in the workspace
+    let ws_packages = project.packages();
+    // The packages the recipe is generated for
+    let packages = match selected {
+        Some(pkg) => vec![pkg],
+        None => ws_packages.clone(),
+    };
+    // Resolve all dependencies (generate or use Cargo.lock as necessary)
+    let features = match flavor {
+        Some(flavor) => {
+            config.shell().status(
+                "Resolving",
+                if flavor.features.is_empty() {
+                    format!(
+                        "dependencies of flavor {} with the default features",
+                        flavor.name
+                    )
+                } else {
+                    format!(
+                        "dependencies of flavor {} with the default features and {}",
+                        flavor.name,
+                        flavor.features.join(", ")
+                    )
+                },
+            )?;
+            flavor.cli_features()?
+        }
+        None => {
+            let features = options.selected_features();
+            config.shell().status(
+                "Resolving",
+                match (options.no_default_features, features.is_empty()) {
+                    (true, true) => "dependencies without the default features".to_string(),
+                    (true, false) => format!(
+                        "dependencies with only the features {}",
+                        features.join(", ")
+                    ),
+                    (false, false) => format!(
+                        "dependencies with the default features and {}",
+                        features.join(", ")
+                    ),
+                    (false, true) if options.default_features_only => {
+                        "dependencies with the default features".to_string()
+                    }
+                    (false, true) => "dependencies with all features enabled".to_string(),
+                },
+            )?;
+            options.cli_features()?
+        }
+    };
+    let lockfile = project.ws.root().join("Cargo.lock");
+    let offline = options.offline || options.frozen;
+    // cargo may still generate a missing Cargo.lock offline from the local
+    // cargo cache
+    let had_lockfile = lockfile.exists();
+    let (package_set, resolve) = project
+        .resolve(&packages, &features, options.dev_units())
+        .with_context(|| match (offline, had_lockfile) {
+            (true, false) => format!(
+                "Unable to resolve dependencies offline and '{}' does not exist, \
+                 generating it needs network access to the registry index of the \
+                 dependencies missing from the local cargo cache",
+                lockfile.display()
+            ),
+            (true, true) => "Unable to resolve dependencies offline, network access is \
+                             needed to update the registry index or fetch git \
+                             dependencies that are missing from the local cargo cache"
+                .to_string(),
+            (false, _) => "Unable to resolve dependencies".to_string(),
+        })?;
+
+    // the dependencies built for the requested targets
+    let dependencies =
+        project.dependencies(&resolve, &packages, &options.target, options.dev_units())?;
+
+    // a crate archive already in the local cache that disagrees with
+    // Cargo.lock means the recipe could never fetch what we'd emit
+    let mut archives = archive::ArchiveCache::new(config)?;
+    for (pkg, csum) in resolve.checksums() {
+        if let Some(csum) = csum {
+            archives.verify_cached(*pkg, csum)?;
+        }
+    }
+
+    // dependencies that are fetched by other means and left out of the recipe
+    let source_patterns = options
+        .exclude_source
+        .iter()
+        .chain(&bitbake_config.exclude_sources)
+        .map(|pattern| exclude::SourcePattern::parse(pattern))
+        .collect::<CargoResult<Vec<_>>>()?;
+    let crate_patterns = if options.exclude_crate.is_empty() {
+        &metadata.bitbake.skip_crates
+    } else {
+        &options.exclude_crate
+    };
+    let crate_patterns = crate_patterns
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid crate pattern '{}'", pattern))
+        })
+        .collect::<CargoResult<Vec<_>>>()?;
+    let is_excluded_crate = |pkg: &PackageId| {
+        crate_patterns
+            .iter()
+            .any(|pattern| pattern.matches(pkg.name().as_str()))
+    };
+    let excluded = dependencies
+        .iter()
+        .copied()
+        .filter(|pkg| !ws_packages.iter().any(|ws_pkg| ws_pkg.name() == pkg.name()))
+        .filter(|pkg| {
+            let src_id = pkg.source_id();
+            // sparse indexes match the same patterns as git ones
+            is_excluded_crate(pkg)
+                || source_patterns.iter().any(|pattern| {
+                    pattern.matches(src_id.url().as_str())
+                        || pattern.matches(&registry::index_url(src_id))
+                })
+        })
+        .collect::<Vec<_>>();
+    if !crate_patterns.is_empty() {
+        warn_excluded_crates(config, &resolve, &packages, &excluded, &crate_patterns)?;
+    }
+    for pkg in excluded.iter().filter(|pkg| !is_excluded_crate(pkg)) {
+        config.shell().status(
+            "Excluding",
+            format!(
+                "{} {} from {}",
+                pkg.name(),
+                pkg.version(),
+                pkg.source_id().url()
+            ),
+        )?;
+    }
+
+    // warn about overrides that no longer match anything so they get cleaned up
+    for key in bitbake_config.src_uri_overrides.keys() {
+        let used = resolve
+            .iter()
+            .any(|pkg| pkg.name() == key.as_str() || pkg.source_id().url().as_str() == key);
+        if !used {
+            config.shell().warn(format!(
+                "SRC_URI override '{}' does not match any crate or repository in the dependency graph",
+                key
+            ))?;
+        }
+    }
+
+    // the names bitbake sees in SRCREV_<name> and the checksum flags
+    let fetcher_names =
+        src_uri::FetcherNames::new(dependencies.iter().map(|pkg| pkg.name().as_str()));
+    for (original, name) in fetcher_names.renamed() {
+        config.shell().warn(format!(
+            "Naming the crate {} {} in SRC_URI and its variables, bitbake can't use \
+             the name as is",
+            original, name
+        ))?;
+    }
+
+    // crates [patch] replaces, which are fetched from where the patch points
+    let patches = project.ws.root_patch()?;
+    let patched = dependencies
+        .iter()
+        .filter(|pkg| {
+            patches.values().flatten().any(|dep| {
+                dep.package_name() == pkg.name() && dep.source_id().url() == pkg.source_id().url()
+            })
+        })
+        .copied()
+        .collect::<BTreeSet<_>>();
+
+    // registries besides crates.io and where to fetch their crates from
+    let mut registries = BTreeMap::new();
+    for src_id in dependencies
+        .iter()
+        .map(|pkg| pkg.source_id())
+        .filter(|src_id| src_id.is_remote_registry() && !src_id.is_crates_io())
+    {
+        if registries.contains_key(&src_id) {
+            continue;
+        }
+        let index = registry::index_url(src_id);
+        let dl = registry::download_template(config, src_id);
+        let registry =
+            registry::Registry::new(&index, dl.as_deref(), options.registry_host.as_deref());
+        // unless the options say where to fetch the crates from
+        let told_where = options.registry_downloads || options.registry_host.is_some();
+        if dl.is_none() {
+            config.shell().warn(format!(
+                "Unable to read the configuration of the registry {}, fetching its crates \
+                 from {}",
+                index, registry.host
+            ))?;
+        } else if !told_where && registry.serves_http() {
+            config.shell().warn(format!(
+                "The registry {} serves its crates over http, the crate:// fetcher asks \
+                 https://{} instead, pass --registry-downloads or --registry-host",
+                index, registry.host
+            ))?;
+        } else if !told_where && !registry.crate_fetcher_works() {
+            config.shell().warn(format!(
+                "The registry {} doesn't serve its crates where the crate:// fetcher looks \
+                 for them on {}, pass --registry-downloads or --registry-host",
+                index, registry.host
+            ))?;
+        }
+        registries.insert(src_id, registry);
+    }
+
+    // build the crate URIs
+    let mut src_uri_extras = vec![];
+    let mut applied_overrides = vec![];
+    let mut git_deps = vec![];
+    // git dependencies following a default branch that couldn't be found,
+    // and the default branches asked for once per repository
+    let mut unknown_branches = vec![];
+    let mut default_branches = HashMap::new();
+    // git dependencies without a commit to pin, reported once all are known
+    let mut srcrev_errors = vec![];
+    let mut unpinned_crates = vec![];
+    // git dependencies whose repository has submodules or uses Git LFS
+    let mut submodule_crates = vec![];
+    let mut lfs_crates = vec![];
+    let mut crate_data = vec![];
+    let mut git_data = vec![];
+    let mut https_crates = vec![];
+    // path dependencies whose sources aren't part of the project's
+    let mut external_paths = vec![];
+    let mut src_uris = dependencies
+        .iter()
+        .copied()
+        .filter_map(|pkg| {
+            // get the source info for this package
+            let src_id = pkg.source_id();
+            if ws_packages.iter().any(|ws_pkg| ws_pkg.name() == pkg.name())
+                || excluded.contains(&pkg)
+            {
+                None
+            } else if src_id.is_remote_registry() {
+                // this package appears in a crate registry
+                let csum = resolve.checksums().get(&pkg).cloned().flatten();
+                let name = pkg.name().to_string();
+                let version = pkg.version().to_string();
+                let registry = registries.get(&src_id);
+                let download = match registry {
+                    None if options.https_crates => {
+                        Some(src_uri::crates_io_download(&name, &version))
+                    }
+                    Some(registry) if options.https_crates || options.registry_downloads => {
+                        let url = registry.download_url(&name, &version, csum.as_deref());
+                        Some(src_uri::registry_download(&url, &name, &version))
+                    }
+                    _ => None,
+                };
+                let mut uri = match download {
+                    Some(uri) => {
+                        https_crates.push((pkg, csum.clone()));
+                        uri
+                    }
+                    None => src_uri::SrcUri::parse(&format!(
+                        "crate://{}/{}/{}",
+                        registry.map_or(CRATES_IO_DOMAIN, |registry| registry.host.as_str()),
+                        name,
+                        version
+                    )),
+                };
+                if patched.contains(&pkg) {
+                    src_uri_extras.push(format!("# patched: {} -> {}", name, uri.url));
+                }
+                // the fetchers name the entry after the crate unless told otherwise
+                let fetcher_name = fetcher_names.get(&name);
+                if fetcher_name != name {
+                    let suffix = if uri.param("downloadfilename").is_some() {
+                        ".crate"
+                    } else {
+                        ""
+                    };
+                    uri.set_param("name", &format!("{}-{}{}", fetcher_name, version, suffix));
+                }
+                if let Some(ref csum) = csum {
+                    src_uri_extras.push(format!(
+                        "SRC_URI[{name}.sha256sum] = \"{csum}\"",
+                        name = uri
+                            .param("name")
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("{}-{}", pkg.name(), pkg.version())),
+                    ));
+                }
+                applied_overrides.extend(apply_src_uri_override(&bitbake_config, pkg, &mut uri));
+                crate_data.push(output::CrateData {
+                    name: pkg.name().to_string(),
+                    version: pkg.version().to_string(),
+                    src_uri: uri.to_string(),
+                    sha256: csum,
+                });
+                Some(format!("    {} \\\n", uri))
+            } else if src_id.is_path() {
+                // we don't want to spit out path based
+                // entries since they're within the crate
+                // we are packaging, unless they're outside of it
+                if let Ok(path) = src_id.url().to_file_path() {
+                    if !path.starts_with(project.ws.root()) {
+                        external_paths.push((pkg.name().to_string(), path));
+                    }
+                }
+                None
+            } else if src_id.is_git() {
+                let rev = match git_srcrev(pkg.name().as_str(), src_id, options.reproducible) {
+                    Ok(rev) => rev,
+                    Err(e) => {
+                        srcrev_errors.push(e.to_string());
+                        return None;
+                    }
+                };
+                if options.reproducible && src_id.precise_git_fragment().is_none() {
+                    unpinned_crates.push(pkg.name().to_string());
+                }
+                let pin = git_pin(src_id, &rev, options, offline, &mut default_branches);
+                let checkout = git_checkout(&package_set, pkg);
+                if checkout.prefix == git::GitPrefix::GitSubmodule {
+                    submodule_crates.push(pkg.name().to_string());
+                }
+                if checkout.lfs {
+                    lfs_crates.push(pkg.name().to_string());
+                }
+                if pin == git::GitPin::Detached
+                    && rev == "${AUTOREV}"
+                    && options.names_git_branches()
+                    && src_id.git_reference() == Some(&GitReference::DefaultBranch)
+                {
+                    unknown_branches.push(pkg.name().to_string());
+                }
+
+                // emitted per repository once every crate has been seen
+                git_deps.push(git::GitDependency {
+                    name: pkg.name().to_string(),
+                    url: src_id.url().to_string(),
+                    rev,
+                    pin,
+                    subdir: checkout.subdir,
+                    prefix: checkout.prefix,
+                    lfs: checkout.lfs,
+                });
+                None
+            } else {
+                let mut uri = src_uri::SrcUri::parse(src_id.url().as_str());
+                applied_overrides.extend(apply_src_uri_override(&bitbake_config, pkg, &mut uri));
+                crate_data.push(output::CrateData {
+                    name: pkg.name().to_string(),
+                    version: pkg.version().to_string(),
+                    src_uri: uri.to_string(),
+                    sha256: None,
+                });
+                Some(format!("    {} \\\n", uri))
+            }
+        })
+        .collect::<Vec<String>>();
+
+    if !external_paths.is_empty() && !options.allow_external_paths {
+        return Err(anyhow!(
+            "The path dependencies {} are outside the workspace at '{}', so their sources \
+             won't be in ${{WORKDIR}}; make them git or registry dependencies or pass \
+             --allow-external-paths",
+            external_paths
+                .iter()
+                .map(|(name, path)| format!("{} ('{}')", name, path.display()))
+                .join(", "),
+            project.ws.root().display()
+        ));
+    }
+    for (name, path) in &external_paths {
+        src_uri_extras.push(format!(
+            "# TODO: fetch the path dependency {} from {} and add where it is unpacked \
+             to EXTRA_OECARGO_PATHS",
+            name,
+            path.display()
+        ));
+    }
+
+    if !srcrev_errors.is_empty() {
+        return Err(anyhow!(srcrev_errors.join("\n")));
+    }
+
+    if !unpinned_crates.is_empty() {
+        config.shell().warn(format!(
+            "Unable to pin the git dependencies {} with --reproducible since Cargo.lock \
+             doesn't record their commit, run `cargo update` to record it",
+            unpinned_crates.join(", ")
+        ))?;
+    }
+
+    if !submodule_crates.is_empty() {
+        config.shell().warn(format!(
+            "Fetching the git dependencies {} with gitsm:// since their repositories \
+             have submodules",
+            submodule_crates.join(", ")
+        ))?;
+    }
+
+    if !lfs_crates.is_empty() {
+        config.shell().warn(format!(
+            "Fetching the git dependencies {} with lfs=1 since their repositories use \
+             Git LFS, the build host needs git-lfs",
+            lfs_crates.join(", ")
+        ))?;
+    }
+
+    if !unknown_branches.is_empty() {
+        config.shell().warn(format!(
+            "Unable to determine the default branch of the git dependencies {}, their \
+             ${{AUTOREV}} follows master unless a SRC_URI override sets branch=",
+            unknown_branches.join(", ")
+        ))?;
+    }
+
+    // one checkout per repository and revision shared by all of its crates
+    let (groups, warnings) = git::group_by_repository(git_deps);
+    if options.group_git_repos {
+        for warning in warnings {
+            config.shell().warn(warning)?;
+        }
+    }
+    for group in groups {
+        let name = fetcher_names.get(&group.name);
+        // a local repository only exists on this machine
+        let local = git::local_path(&group.url).filter(|_| !options.allow_local_git);
+        let placeholder = format!("https://{}/{}.git", LOCAL_GIT_PLACEHOLDER, group.name);
+        let url = git::git_to_yocto_git_url(
+            if local.is_some() {
+                &placeholder
+            } else {
+                &group.url
+            },
+            Some(name.as_str()),
+            group.prefix,
+            options.git_protocol(),
+            &group.pin,
+        );
+        let mut uri = src_uri::SrcUri::parse(&url);
+        if local.is_some() {
+            // the host is lower case after parsing, the placeholder stands out
+            // in capitals
+            uri.url = uri.url.replacen(
+                &LOCAL_GIT_PLACEHOLDER.to_ascii_lowercase(),
+                LOCAL_GIT_PLACEHOLDER,
+                1,
+            );
+        }
+        if group.lfs {
+            uri.set_param("lfs", "1");
+        }
+        // an override of any of the crates applies to their checkout
+        let group_pkgs = dependencies
+            .iter()
+            .copied()
+            .filter(|pkg| {
+                pkg.source_id().url().as_str() == group.url
+                    && group.crates.iter().any(|name| pkg.name() == name.as_str())
+            })
+            .collect::<Vec<_>>();
+        for pkg in &group_pkgs {
+            applied_overrides.extend(apply_src_uri_override(&bitbake_config, *pkg, &mut uri));
+        }
+        match local {
+            // unless an override says where else to fetch it from
+            Some(path)
+                if uri
+                    .url
+                    .to_ascii_lowercase()
+                    .contains(&LOCAL_GIT_PLACEHOLDER.to_ascii_lowercase()) =>
+            {
+                config.shell().warn(format!(
+                    "The git dependency {} is fetched from the local repository {}, its \
+                     SRC_URI entry is a placeholder; override it or pass --allow-local-git",
+                    group.crates.join(", "),
+                    path
+                ))?;
+                src_uri_extras.push(format!(
+                    "# TODO: fetch {} from where others can, not from {}",
+                    group.name, path
+                ));
+            }
+            _ => {}
+        }
+
+        for pkg in group_pkgs.iter().filter(|pkg| patched.contains(*pkg)) {
+            src_uri_extras.push(format!("# patched: {} -> {}", pkg.name(), uri.url));
+        }
+        src_uri_extras.push(format!("SRCREV_FORMAT .= \"_{}\"", name));
+        src_uri_extras.push(format!("SRCREV_{} = \"{}\"", name, group.rev));
+        // cargo finds the crates in the directories beneath the paths
+        let destsuffix = uri.param("destsuffix").unwrap_or(name.as_str());
+        if options.group_git_repos {
+            src_uri_extras.push(oecargo_path(destsuffix, ""));
+        } else {
+            for subdir in &group.subdirs {
+                src_uri_extras.push(oecargo_path(destsuffix, subdir));
+            }
+        }
+        src_uris.push(format!("    {} \\\n", uri));
+        git_data.push(output::GitData {
+            name,
+            src_uri: uri.to_string(),
+            srcrev: group.rev,
+        });
+    }
+
+    for applied in &applied_overrides {
+        config
+            .shell()
+            .verbose(|shell| shell.status("Applied", format!("SRC_URI override: {}", applied)))?;
+    }
+
+    // sort the crate list
+    src_uris.sort();
+    let crates = src_uris.len();
+    src_uri_extras.sort();
+
+    // with --vendor a single tarball replaces all of the dependencies
+    let vendored = if options.vendor {
+        let bundled = dependencies
+            .iter()
+            .copied()
+            .filter(|pkg| {
+                !pkg.source_id().is_path()
+                    && !excluded.contains(pkg)
+                    && !ws_packages.iter().any(|ws_pkg| ws_pkg.name() == pkg.name())
+            })
+            .collect::<Vec<_>>();
+        let tarball = vendor::tarball(
+            config,
+            &mut archives,
+            &package_set,
+            &bundled,
+            resolve.checksums(),
+            &pn,
+        )?;
+        config.shell().status(
+            "Vendored",
+            format!("{} crates into {}", bundled.len(), tarball.path.display()),
+        )?;
+        src_uris = vec![format!("    file://{} \\\n", tarball.path.display())];
+        src_uri_extras = vec![
+            format!(
+                "FILESEXTRAPATHS{}prepend := \"${{THISDIR}}:\"",
+                options.override_separator()
+            ),
+            format!("# sha256 of {}: {}", tarball.path.display(), tarball.sha256),
+        ];
+        Some(tarball)
+    } else {
+        None
+    };
+
+    // with --crates-inc the crates are listed in their own file
+    let (crate_src_uris, src_uris): (Vec<_>, Vec<_>) = src_uris.into_iter().partition(|uri| {
+        let url = uri.trim_start();
+        options.crates_inc
+            && (url.starts_with("crate://")
+                || src_uri::SrcUri::parse(url.trim_end_matches(" \\\n")).param("subdir")
+                    == Some(src_uri::DOWNLOAD_SUBDIR))
+    });
+    let (mut crate_checksums, mut src_uri_extras): (Vec<_>, Vec<_>) = src_uri_extras
+        .into_iter()
+        .partition(|extra| options.crates_inc && extra.starts_with("SRC_URI["));
+
+    // the downloaded crates still need to be unpacked the way the crate
+    // fetcher does it
+    if !https_crates.is_empty() && vendored.is_none() {
+        let extras = if options.crates_inc {
+            &mut crate_checksums
+        } else {
+            &mut src_uri_extras
+        };
+        if !extras.is_empty() {
+            extras.push(String::new());
+        }
+        extras.push(unpack_crates(&https_crates));
+    }
+
+    // the first sentence of the package description is used as BitBake
+    // summary and all of it as description
+    let bitbake = &metadata.bitbake;
+    if !bitbake.unknown_keys().is_empty() {
+        config.shell().warn(format!(
+            "Ignoring the unknown keys of the bitbake metadata table: {}",
+            bitbake.unknown_keys().join(", ")
+        ))?;
+    }
+    let mut description = description::Description::new(match metadata.description {
+        Some(description) => description,
+        None if bitbake.summary.is_some() => bitbake.summary.as_deref().unwrap_or_default(),
+        None => {
+            config
+                .shell()
+                .warn("No 'description' field set in your Cargo.toml, using 'name' field")?;
+            metadata.name
+        }
+    });
+    if let Some(ref summary) = bitbake.summary {
+        description.summary = summary.split_whitespace().join(" ");
+    }
+    let summary = description.summary.as_str();
+
+    // only a problem when the recipe is meant to state it
+    if metadata.rust_version.is_none() && (options.strict || options.rust_version_var) {
+        config
+            .shell()
+            .warn("No 'rust-version' field set in your Cargo.toml")?;
+    }
+
+    // package homepage (or source code location)
+    let homepage = match bitbake.homepage.as_deref().or(metadata.homepage) {
+        Some(homepage) => homepage.trim().to_string(),
+        None => fallback_homepage(
+            config,
+            project.ws.root(),
+            options.remote.as_deref(),
+            &metadata,
+            options.project_source == project_source::ProjectSource::Crate,
+        )?,
+    };
+    let homepage = homepage.as_str();
+
+    // package license, where a license-file or an expression that doesn't
+    // parse is a path or text rather than identifiers
+    let mut is_spdx = false;
+    let mut license = match (metadata.license, metadata.license_file) {
+        (Some(license), _) => match license::Expression::parse(license) {
+            Ok(expr) => {
+                is_spdx = true;
+                expr
+            }
+            Err(e) => {
+                config.shell().warn(format!(
+                    "Unable to parse the license '{}' in your Cargo.toml ({:#}), using it \
+                     in LICENSE as it is",
+                    license, e
+                ))?;
+                license::Expression::License(license.to_string())
+            }
+        },
+        (None, Some(license_file)) => {
+            config
+                .shell()
+                .warn("No 'license' field set in your Cargo.toml, trying 'license-file' field")?;
+            license::Expression::License(license_file.to_string())
+        }
+        (None, None) => {
+            let mut shell = config.shell();
+            shell.warn("No 'license' field set in your Cargo.toml, trying 'license-file' field")?;
+            shell.warn("No 'license-file' field set in your Cargo.toml")?;
+            if options.closed_is_error {
+                return Err(anyhow!(
+                    "Refusing to assume the {} license with --closed-is-error, add a 'license' \
+                     field with the SPDX expression of the license (e.g. license = \"MIT\") or \
+                     a 'license-file' field with the path of the license text to '{}'",
+                    license::CLOSED_LICENSE,
+                    match selected {
+                        Some(pkg) => pkg.manifest_path(),
+                        None => project.ws.root_manifest(),
+                    }
+                    .display()
+                ));
+            }
+            license::Expression::License(license::CLOSED_LICENSE.to_string())
+        }
+    };
+
+    // compute the relative directory into the repo our Cargo.toml is at
+    let rel_dir = project.rel_dir(selected.map(Package::manifest_path))?;
+    // and the one into the fetched sources: a crate archive only contains
+    // the package itself, git checkouts and GitHub archives the whole
+    // repository, which the workspace may only be a part of
+    let ws_src_dir = match options.project_source {
+        project_source::ProjectSource::Crate => None,
+        _ => Some(git::ProjectRepo::rel_dir(project.ws.root()).unwrap_or_default()),
+    };
+    let src_dir = ws_src_dir
+        .as_ref()
+        .map_or_else(String::new, |ws_dir| source_dir(&ws_dir.join(&rel_dir)));
+    // before CARGO_SRC_DIR, S has to be the package directory itself
+    let combined_s = options
+        .yocto_release
+        .map_or(false, |release| !release.has_cargo_src_dir());
+    let lic_dir = if combined_s { "" } else { src_dir.as_str() };
+
+    // rename the licenses as configured, or to the names oe-core uses
+    let license_map = match options.license_map {
+        Some(ref path) => Some((path, license::LicenseMap::load(path)?)),
+        None => None,
+    };
+    let mut unmapped = vec![];
+    license.map_licenses(&mut |lic| {
+        if let Some(mapped) = license_map.as_ref().and_then(|(_, map)| map.get(lic)) {
+            return Some(mapped.to_string());
+        }
+        // a license with an exception falls back to its base license
+        if lic.contains(" WITH ") || !is_spdx || lic == license::CLOSED_LICENSE {
+            return None;
+        }
+        let name = spdx::common_license(lic);
+        if name.is_none() && !unmapped.iter().any(|unknown| unknown == lic) {
+            unmapped.push(lic.to_string());
+        }
+        name.map(str::to_string)
+    });
+    if !unmapped.is_empty() {
+        let problem = format!(
+            "No mapping in {}oe-core's common-licenses for the licenses: {}",
+            match license_map {
+                Some((path, _)) => format!("'{}' or ", path.display()),
+                None => String::new(),
+            },
+            unmapped.join(", ")
+        );
+        if options.deny_unknown_licenses {
+            return Err(anyhow!(problem));
+        }
+        config.shell().warn(problem)?;
+    }
+
+    let unknown_exceptions = license.unknown_exceptions();
+    if !unknown_exceptions.is_empty() {
+        config.shell().warn(format!(
+            "No BitBake license known for the exceptions {}, name the licenses with them \
+             in a --license-map as '<license> WITH <exception>'",
+            unknown_exceptions.join(", ")
+        ))?;
+    }
+
+    // a closed recipe has no license files to checksum, and is only worth a
+    // warning if CLOSED was assumed rather than set in Cargo.toml
+    let closed = license == license::Expression::License(license::CLOSED_LICENSE.to_string());
+    if closed && metadata.license.is_none() {
+        config.shell().warn(format!(
+            "The recipe is marked LICENSE = \"{}\", which turns off BitBake's license \
+             checks for it; set 'license' or 'license-file' in your Cargo.toml",
+            license::CLOSED_LICENSE
+        ))?;
+    }
+
+    // license files for the package
+    let mut lic_files = vec![];
+    let licenses = if closed { vec![] } else { license.licenses() };
+    // members may share the license files at the top of the workspace,
+    // unless the sources only contain the package
+    let pkg_root = project.ws.root().join(&rel_dir);
+    let mut lic_search = vec![(pkg_root.clone(), PathBuf::from(lic_dir))];
+    match ws_src_dir {
+        Some(ref ws_dir) if !combined_s && !rel_dir.as_os_str().is_empty() => lic_search.push((
+            project.ws.root().to_path_buf(),
+            PathBuf::from(source_dir(ws_dir)),
+        )),
+        _ => {}
+    }
+    let lic_search = lic_search
+        .iter()
+        .map(|(root, rel)| (root.as_path(), rel.as_path()))
+        .collect::<Vec<_>>();
+    // the license-file of the manifest is used as declared rather than
+    // looked up by name
+    let declared = match metadata.license_file {
+        Some(license_file) if !is_spdx && !closed => Some(license_file),
+        _ => None,
+    };
+    if let Some(license_file) = declared {
+        let entry = match license::declared_file(
+            &lic_search,
+            &pkg_root,
+            license_file,
+            options.lic_checksum,
+        ) {
+            Ok(entry) => entry,
+            Err(e) => {
+                let manifest = pkg_root.join("Cargo.toml");
+                let problem = match e {
+                    license::DeclaredFileError::Missing => format!(
+                        "The license-file '{}' declared in '{}' doesn't exist",
+                        license_file,
+                        manifest.display()
+                    ),
+                    license::DeclaredFileError::Outside => format!(
+                        "The license-file '{}' declared in '{}' is outside of the package, \
+                         where the sources of the recipe don't reach",
+                        license_file,
+                        manifest.display()
+                    ),
+                };
+                if !options.lenient {
+                    return Err(anyhow!(problem));
+                }
+                config.shell().warn(format!(
+                    "{}, the md5 in LIC_FILES_CHKSUM has to be filled in by hand",
+                    problem
+                ))?;
+                license::placeholder(Path::new(lic_dir), license_file, options.lic_checksum)
+            }
+        };
+        lic_files.push(format!("    {}", entry));
+    }
+    for lic in licenses.iter().filter(|_| declared.is_none()) {
+        let entry = match license::file(&lic_search, lic, options.lic_checksum) {
+            Some(file) => {
+                if !file.passed_over.is_empty() {
+                    config.shell().warn(format!(
+                        "Using the license file specific to {} rather than {}",
+                        lic,
+                        file.passed_over.join(", ")
+                    ))?;
+                }
+                file.entry
+            }
+            None => {
+                config.shell().warn(format!(
+                    "Unable to find the license file of {} in '{}', the md5 in \
+                     LIC_FILES_CHKSUM has to be filled in by hand",
+                    lic,
+                    pkg_root.display()
+                ))?;
+                license::placeholder(Path::new(lic_dir), lic, options.lic_checksum)
+            }
+        };
+        // several licenses may share a generic file
+        let entry = format!("    {}", entry);
+        if !lic_files.contains(&entry) {
+            lic_files.push(entry);
+        }
+    }
+
+    if options.crate_license_files && !closed {
+        let crates = dependencies
+            .iter()
+            .copied()
+            .filter(|pkg| pkg.source_id().is_remote_registry() && !excluded.contains(pkg))
+            .collect::<Vec<_>>();
+        let files = crate_license_files::crate_license_files(
+            config,
+            &mut archives,
+            &package_set,
+            &crates,
+            resolve.checksums(),
+            options.lic_checksum,
+        )?;
+        lic_files.extend(files.entries.iter().map(|entry| format!("    {}", entry)));
+        if !files.missing.is_empty() {
+            config.shell().warn(format!(
+                "No license file found for the crates: {}",
+                files.missing.join(", ")
+            ))?;
+        }
+    }
+
+    // what ends up in the binary along with the package itself
+    let (license, license_comment) = if options.aggregate_licenses {
+        let crates = dependencies
+            .iter()
+            .copied()
+            .filter(|pkg| !pkg.source_id().is_path() && !excluded.contains(pkg))
+            .collect::<Vec<_>>();
+        let mut crate_licenses = crate_licenses(
+            config,
+            &mut archives,
+            &package_set,
+            &crates,
+            resolve.checksums(),
+        )?;
+        for (_, expr) in crate_licenses.iter_mut() {
+            expr.map_licenses(&mut |lic| match license_map
+                .as_ref()
+                .and_then(|(_, map)| map.get(lic))
+            {
+                Some(mapped) => Some(mapped.to_string()),
+                None => spdx::common_license(lic).map(str::to_string),
+            });
+        }
+        (
+            license::aggregate(license, &crate_licenses),
+            license::uncommon_licenses_comment(&crate_licenses),
+        )
+    } else {
+        (license, String::new())
+    };
+
+    // license data in Yocto fmt
+    let license = license.to_bitbake();
+
+    // attempt to figure out the git repo for this project
+    let project_repo = git::ProjectRepo::new(
+        project.ws.root(),
+        options.git_protocol(),
+        options.remote.as_deref(),
+        metadata.repository,
+    );
+    let mut project_repo = match project_repo {
+        Ok(repo) => repo,
+        // asking for a remote that isn't there is a mistake
+        Err(e) if options.remote.is_some() => return Err(e),
+        Err(e) => {
+            config.shell().warn(e)?;
+            Default::default()
+        }
+    };
+    if let Some(ref srcrev) = options.srcrev {
+        project_repo.pin(project.ws.root(), srcrev)?;
+    } else if project_repo.dirty {
+        config.shell().warn(format!(
+            "Working tree has uncommitted changes; SRCREV {} does not include them",
+            project_repo.rev
+        ))?;
+    }
+    if let Some(ref tag) = options.assume_tag {
+        project_repo.assume_tag(project.ws.root(), tag)?;
+    } else if project_repo.shallow && !project_repo.tag && !project_repo.uri.is_empty() {
+        config.shell().warn(
+            "shallow clone: cannot determine if HEAD is a tag, pass --assume-tag <NAME> \
+             for a release",
+        )?;
+    }
+    if let Some(ref branch) = options.branch {
+        if !git::ProjectRepo::has_branch(project.ws.root(), &project_repo.remote, branch) {
+            config.shell().warn(format!(
+                "branch '{}' does not exist in the project's repository",
+                branch
+            ))?;
+        }
+        project_repo.set_branch(branch);
+    } else if project_repo.branch == "HEAD"
+        && !project_repo.tag
+        && !project_repo.uri.is_empty()
+        && options.srcrev.is_none()
+    {
+        config.shell().warn(format!(
+            "HEAD is detached at {}, which no branch contains, fetching it with nobranch=1",
+            project_repo.rev
+        ))?;
+    }
+
+    // an explicit --srcrev is pinned by its sha alone and an explicit
+    // --branch is kept
+    if options.names_git_tags()
+        && project_repo.tag
+        && options.srcrev.is_none()
+        && options.branch.is_none()
+    {
+        if let Some(tag) = project_repo
+            .tag_name(&metadata.version.to_string())
+            .map(str::to_string)
+        {
+            project_repo.set_tag(&tag);
+        }
+    }
+
+    // if this is not a tag we need to include some data about the version in PV so that
+    // the sstate cache remains valid
+    let git_srcpv = if options.project_source != project_source::ProjectSource::Git {
+        // archives are versioned by PV already
+        "".into()
+    } else if !project_repo.tag && project_repo.rev.len() > 10 {
+        let pv_append_key = format!("PV{}append", options.override_separator());
+        if options
+            .yocto_release
+            .map_or(false, compat::Release::appends_srcrev)
+        {
+            // the revision is added to the package version by bitbake
+            format!("{} = \"+git\"", pv_append_key)
+        } else {
+            // we should be using ${SRCPV} here but due to a bitbake bug we cannot. see:
+            // https://github.com/meta-rust/meta-rust/issues/136
+            format!(
+                "{} = \".AUTOINC+{}\"",
+                pv_append_key,
+                &project_repo.rev[..10],
+            )
+        }
+    } else {
+        // its a tag so nothing needed
+        "".into()
+    };
+
+    // how the project's own sources are fetched
+    let version = metadata.version.to_string();
+    let archive = match options.project_source {
+        project_source::ProjectSource::Git => None,
+        project_source::ProjectSource::Crate => {
+            Some(project_source::Archive::crate_io(metadata.name, &version))
+        }
+        project_source::ProjectSource::GithubArchive => Some(project_source::Archive::github(
+            metadata.repository.unwrap_or(homepage),
+            metadata.name,
+            &version,
+            &project_repo.rev,
+        )?),
+    };
+    // without a repository there is nothing to check
+    let upstream_check =
+        if options.no_upstream_check || (archive.is_none() && project_repo.uri.is_empty()) {
+            String::new()
+        } else {
+            project_source::upstream_check(
+                archive.as_ref(),
+                options
+                    .upstream_tag_regex
+                    .as_deref()
+                    .unwrap_or(project_source::DEFAULT_TAG_REGEX),
+            )
+        };
+    let (project_src, project_src_checksum, unpack_dir) = match archive {
+        None => (
+            format!(
+                "SRC_URI += \"{}\"\nSRCREV = \"{}\"",
+                project_repo.uri, project_repo.rev
+            ),
+            String::new(),
+            "git".to_string(),
+        ),
+        Some(archive) => {
+            let sha256 = archive.sha256(config, options.project_archive.as_deref(), offline)?;
+            let checksum = format!("SRC_URI[{}] = \"{}\"", archive.checksum_key, sha256);
+            (
+                format!("SRC_URI += \"{}\"\n{}", archive.src_uri, checksum),
+                checksum,
+                archive.unpack_dir,
+            )
+        }
+    };
+    let (s_dir, project_rel_dir) = source_location(&unpack_dir, &src_dir, combined_s);
+    let project_src = format!("{}\nS = \"{}\"", project_src, s_dir);
+
+    // the version of the recipe, which can differ from the crate's
+    let pv = match options.pv {
+        Some(ref pv) => {
+            check_pv(pv)?;
+            pv.clone()
+        }
+        None => metadata.version.to_string(),
+    };
+
+    // build up the path
+    let recipe_path = if options.bbappend {
+        PathBuf::from(format!(
+            "{}_{}.bbappend",
+            pn,
+            if options.bbappend_exact { &pv } else { "%" }
+        ))
+    } else {
+        PathBuf::from(format!("{name}_{version}.bb", name = pn, version = pv,))
+    };
+
+    // record everything needed to regenerate this exact recipe
+    let provenance = provenance::Provenance {
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        compat: options.compat().to_string(),
+        lockfile_md5: provenance::lockfile_md5(&lockfile).context("Unable to hash Cargo.lock")?,
+        options: options.raw_args.clone(),
+    };
+
+    let layout = template::WorkspaceLayout {
+        root_name: project
+            .ws
+            .root()
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        member_dir: rel_dir.display().to_string(),
+        members: ws_packages
+            .iter()
+            .map(|pkg| pkg.name().to_string())
+            .collect(),
+        is_virtual: project.ws.is_virtual(),
+        bin_targets: packages
+            .iter()
+            .flat_map(|pkg| pkg.targets())
+            .filter(|target| target.is_bin())
+            .map(|target| target.name().to_string())
+            .collect(),
+    };
+
+    let mut ctx = template::Context::new();
+    ctx.set("name", template::escape(&pn));
+    ctx.set("crate_name", metadata.name);
+    ctx.set("crate_version", &metadata.version);
+    ctx.set("version", &pv);
+    ctx.set(
+        "pv_comment",
+        match options.pv {
+            Some(_) => format!(
+                "# PV overridden with --pv, the crate version is {}\n",
+                metadata.version
+            ),
+            None => String::new(),
+        },
+    );
+    ctx.set("summary", template::escape(summary));
+    ctx.set("description", description.assignment());
+    ctx.set("homepage", template::escape(homepage));
+    ctx.set("license", template::escape(&license));
+    ctx.set("license_comment", &license_comment);
+    ctx.set("lic_files", lic_files.join(""));
+    // only placeholders for license files that couldn't be read need fixing
+    ctx.set(
+        "lic_files_fixme",
+        if lic_files.iter().any(|entry| entry.contains("=generateme")) {
+            "# FIXME: update generateme with the real MD5 of the license file\n"
+        } else {
+            ""
+        },
+    );
+    ctx.set("src_uri", src_uris.join(""));
+    ctx.set(
+        "src_uri_remove",
+        if options.bbappend {
+            bbappend_removals(options, config, &pn, &src_uris)?
+        } else {
+            String::new()
+        },
+    );
+    // left out when --crates-inc moved every entry to the crates file
+    ctx.set(
+        "src_uri_assignment",
+        if src_uris.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n{}\"\n", update::SRC_URI_START, src_uris.join(""))
+        },
+    );
+    ctx.set("src_uri_extras", src_uri_extras.join("\n"));
+    ctx.set("crates_src_uri", crate_src_uris.join(""));
+    ctx.set("crates_checksums", crate_checksums.join("\n"));
+    ctx.set(
+        "crates_inc",
+        if options.crates_inc {
+            template::CRATES_INC_REQUIRE
+        } else {
+            ""
+        },
+    );
+    ctx.set("project_rel_dir", &project_rel_dir);
+    ctx.set("project_src", &project_src);
+    ctx.set("project_src_checksum", &project_src_checksum);
+    ctx.set("project_src_uri", &project_repo.uri);
+    ctx.set("project_src_rev", &project_repo.rev);
+    ctx.set("git_srcpv", &git_srcpv);
+    ctx.set("upstream_check", &upstream_check);
+    ctx.set(
+        "filesextrapaths",
+        format!(
+            "FILESEXTRAPATHS{}prepend := \"${{THISDIR}}/${{PN}}:\"",
+            options.override_separator()
+        ),
+    );
+    // what pins the project's sources to this version
+    ctx.set(
+        "project_pin",
+        if project_src_checksum.is_empty() {
+            format!("SRCREV = \"{}\"", project_repo.rev)
+        } else {
+            project_src_checksum.clone()
+        },
+    );
+    // the whole graph counts, pkg-config is mostly a build dependency of
+    // some -sys crate deep down
+    let mut pkg_config_users = BTreeMap::new();
+    if !options.no_auto_inherit {
+        for pkg in &dependencies {
+            for (dep, _) in resolve.deps(*pkg) {
+                let helper = dep.name().as_str();
+                if PKG_CONFIG_CRATES.contains(&helper)
+                    && !PKG_CONFIG_CRATES.contains(&pkg.name().as_str())
+                    && dependencies.contains(&dep)
+                {
+                    pkg_config_users
+                        .entry(helper)
+                        .or_insert_with(BTreeSet::new)
+                        .insert(pkg.name().as_str());
+                }
+            }
+        }
+    }
+    let c_libraries = packages
+        .iter()
+        .filter_map(|pkg| c_library::CLibrary::of(pkg))
+        .collect::<Vec<_>>();
+    for lib in c_libraries.iter().filter(|lib| lib.has_bin) {
+        config.shell().warn(format!(
+            "{} builds both binaries and a C library, check which package \
+             each of them should go to",
+            lib.package
+        ))?;
+    }
+    let class = match options.cargo_c {
+        Some(ref class) if !c_libraries.is_empty() => {
+            class.as_deref().unwrap_or(c_library::CARGO_C_CLASS)
+        }
+        _ => {
+            if !c_libraries.is_empty() {
+                config.shell().note(
+                    "the package builds a C library, use --cargo-c to build and \
+                     install it with cargo-c",
+                )?;
+            }
+            "cargo"
+        }
+    };
+    // the classes asked for, in a stable order after the ones implied
+    let inherit = options
+        .inherit
+        .iter()
+        .chain(&bitbake.inherit)
+        .flat_map(|classes| classes.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|class| !class.is_empty())
+        .map(str::to_string)
+        .sorted()
+        .dedup()
+        .collect::<Vec<_>>();
+    ctx.set("inherit", inherit_line(class, &pkg_config_users, &inherit));
+    ctx.set("extra", bitbake.extra_lines());
+    ctx.set(
+        "c_library",
+        c_libraries
+            .iter()
+            .map(|lib| lib.files(options.override_separator()))
+            .collect::<String>(),
+    );
+    let sys_depends = if options.no_sys_deps {
+        sys_deps::SysDepends::default()
+    } else {
+        let map = match options.sys_deps_map {
+            Some(ref path) => sys_deps::SysDepsMap::load(path)?,
+            None => sys_deps::SysDepsMap::default(),
+        };
+        // dev-dependencies and crates of other platforms aren't linked
+        let linked = project.linked_dependencies(&resolve, &packages, &options.target)?;
+        sys_deps::SysDepends::new(
+            &map,
+            dependencies
+                .iter()
+                .filter(|pkg| linked.contains(pkg) && !excluded.contains(pkg))
+                .filter(|pkg| !ws_packages.iter().any(|ws_pkg| ws_pkg.name() == pkg.name()))
+                .map(|pkg| pkg.name().as_str()),
+        )
+    };
+    if !sys_depends.unknown.is_empty() {
+        config.shell().warn(format!(
+            "no DEPENDS known for the -sys crates {}, add the recipes providing \
+             their libraries with --depends or --sys-deps-map",
+            sys_depends.unknown.join(", ")
+        ))?;
+    }
+    let depends = if options.depends.is_empty() {
+        &bitbake.depends
+    } else {
+        &options.depends
+    };
+    let depends = depends
+        .iter()
+        .chain(sys_depends.recipes.keys())
+        .cloned()
+        .collect::<Vec<_>>();
+    let depends = recipe_names(&depends);
+    ctx.set(
+        "depends",
+        if depends.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "{}DEPENDS += \"{}\"\n",
+                sys_depends.comment(),
+                depends.join(" ")
+            )
+        },
+    );
+    let rdepends = recipe_names(if options.rdepends.is_empty() {
+        &bitbake.rdepends
+    } else {
+        &options.rdepends
+    });
+    ctx.set(
+        "rdepends",
+        if rdepends.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "RDEPENDS{}${{PN}} += \"{}\"\n",
+                options.override_separator(),
+                rdepends.join(" ")
+            )
+        },
+    );
+    ctx.set(
+        "rust_version",
+        match metadata.rust_version {
+            Some(ref rust_version) if options.rust_version_var => format!(
+                "# Requires rustc >= {0}\nRUST_VERSION_MIN = \"{0}\"\n",
+                rust_version
+            ),
+            Some(ref rust_version) => format!("# Requires rustc >= {}\n", rust_version),
+            None => String::new(),
+        },
+    );
+    ctx.set(
+        "maintainer",
+        maintainer_line(
+            options
+                .maintainer
+                .as_deref()
+                .or(bitbake.maintainer.as_deref()),
+            &metadata.authors,
+        ),
+    );
+    let section = options
+        .section
+        .as_deref()
+        .or(bitbake.section.as_deref())
+        .or_else(|| section::section(&metadata.categories, &metadata.keywords));
+    ctx.set(
+        "section",
+        match section {
+            Some(section) => format!("SECTION = \"{}\"\n", template::escape(section)),
+            None => String::new(),
+        },
+    );
+    // the upstream name, whatever the recipe is called
+    let cve_product = options
+        .cve_product
+        .as_deref()
+        .or(bitbake.cve_product.as_deref());
+    ctx.set(
+        "cve_product",
+        match cve_product {
+            Some(product) => format!("CVE_PRODUCT = \"{}\"\n", template::escape(product)),
+            None if metadata.name != pn => format!("CVE_PRODUCT = \"{}\"\n", metadata.name),
+            None => String::new(),
+        },
+    );
+    let classextend = options.classextend.as_deref().map(|list| {
+        list.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|class| !class.is_empty())
+            .join(" ")
+    });
+    ctx.set(
+        "classextend",
+        match classextend {
+            Some(ref classes) if !classes.is_empty() => {
+                format!("BBCLASSEXTEND = \"{}\"\n", classes)
+            }
+            _ => String::new(),
+        },
+    );
+    ctx.set(
+        "cargo_build_flags",
+        match flavor.map_or_else(|| options.build_flags(), flavor::Flavor::build_flags) {
+            flags if !flags.is_empty() => format!(
+                "CARGO_BUILD_FLAGS{}append = \" {}\"\n",
+                options.override_separator(),
+                flags
+            ),
+            _ => String::new(),
+        },
+    );
+    ctx.set(
+        "packageconfig",
+        if options.packageconfig {
+            match packages.as_slice() {
+                [pkg] => {
+                    let package_config = packageconfig::package_config(
+                        pkg.summary().features(),
+                        options.override_separator(),
+                    );
+                    if !package_config.unmapped_defaults.is_empty() {
+                        config.shell().warn(format!(
+                            "the default features {} of {} have no PACKAGECONFIG knob and are \
+                             not built",
+                            package_config.unmapped_defaults.join(", "),
+                            pkg.name()
+                        ))?;
+                    }
+                    package_config.lines
+                }
+                _ => {
+                    config.shell().warn(
+                        "--packageconfig needs a single package, select it with -p or --workspace",
+                    )?;
+                    String::new()
+                }
+            }
+        } else {
+            String::new()
+        },
+    );
+    ctx.set(
+        "dev_deps_comment",
+        if options.include_dev_deps {
+            "# dev-dependencies are included (--include-dev-deps) so the tests can run on target\n"
+        } else {
+            ""
+        },
+    );
+    ctx.set("cargo_bitbake_ver", env!("CARGO_PKG_VERSION"));
+    ctx.set("provenance", provenance.render());
+    ctx.set_workspace(&layout);
+    let mut includes = vec![];
+    if options.crates_inc {
+        includes.push(Include {
+            path: PathBuf::from(format!("{}-crates.inc", pn)),
+            contents: ctx.render(template::CRATES_INC_TEMPLATE)?,
+        });
+    }
+    let mut contents = match options.template {
+        None if options.bbappend => ctx.render(template::BBAPPEND_TEMPLATE)?,
+        None if options.split_inc => {
+            includes.push(Include {
+                path: PathBuf::from(format!("{}.inc", pn)),
+                contents: ctx.render(template::SPLIT_INC_TEMPLATE)?,
+            });
+            ctx.render(template::SPLIT_BB_TEMPLATE)?
+        }
+        Some(ref path) => {
+            let template = std::fs::read_to_string(path)
+                .with_context(|| format!("Unable to read template '{}'", path.display()))?;
+            ctx.render(&template)
+                .with_context(|| format!("Unable to render template '{}'", path.display()))?
+        }
+        None => ctx.render(template::DEFAULT_TEMPLATE)?,
+    };
+    if options.crates_inc
+        && !contents.contains(template::CRATES_INC_REQUIRE)
+        && !includes
+            .iter()
+            .any(|include| include.contents.contains(template::CRATES_INC_REQUIRE))
+    {
+        config.shell().warn(format!(
+            "the template has no {{crates_inc}} placeholder, {}-crates.inc is not required",
+            pn
+        ))?;
+    }
+    if classextend.is_some() {
+        check_classextend(
+            config,
+            &contents,
+            &includes,
+            &template::escape(summary),
+            &template::escape(homepage),
+        )?;
+    }
+    if options.compact {
+        contents = template::compact(&contents, &provenance.render_compact());
+        for include in &mut includes {
+            // keep the header line of the include
+            let marker = include
+                .contents
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            include.contents = template::compact(&include.contents, &marker);
+        }
+    }
+
+    let data = output::RecipeData {
+        path: recipe_path.display().to_string(),
+        name: pn,
+        version: pv,
+        crate_name: metadata.name.to_string(),
+        crate_version: metadata.version.to_string(),
+        summary: summary.to_string(),
+        description: description.lines.as_ref().map(|lines| lines.join("\n")),
+        homepage: homepage.to_string(),
+        license,
+        crates: crate_data,
+        git_dependencies: git_data,
+        project: output::ProjectData {
+            src_uri: project_repo.uri,
+            rev: project_repo.rev,
+            branch: project_repo.branch,
+            tag: project_repo.tag,
+        },
+    };
+
+    Ok(Recipe {
+        path: recipe_path,
+        contents,
+        provenance,
+        crates,
+        includes,
+        data,
+        vendored,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    /// resolves a workspace with a Windows-only path dependency, with
+    /// `build.target` optionally set in its cargo config
+    fn resolved_names(build_target: Option<&str>) -> Vec<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            r#"
+            [package]
+            name = "app"
+            version = "0.1.0"
+
+            [dependencies]
+            common = { path = "common" }
+
+            [target.'cfg(windows)'.dependencies]
+            winonly = { path = "winonly" }
+            "#,
+        )
+        .unwrap();
+        for name in ["common", "winonly"] {
+            fs::create_dir_all(root.join(name).join("src")).unwrap();
+            fs::write(root.join(name).join("src/lib.rs"), "").unwrap();
+            fs::write(
+                root.join(name).join("Cargo.toml"),
+                format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name),
+            )
+            .unwrap();
+        }
+        if let Some(target) = build_target {
+            fs::create_dir_all(root.join(".cargo")).unwrap();
+            fs::write(
+                root.join(".cargo/config.toml"),
+                format!("[build]\ntarget = \"{}\"\n", target),
+            )
+            .unwrap();
+        }
+
+        let mut config =
+            GlobalContext::new(Shell::new(), root.to_path_buf(), root.join("cargo-home"));
+        config
+            .configure(0, true, None, false, false, true, &None, &[], &[])
+            .unwrap();
+        let project = Project::new(&config, None).unwrap();
+        let packages = project.packages();
+        let (_, resolve) = project
+            .resolve(&packages, &CliFeatures::new_all(true), HasDevUnits::No)
+            .unwrap();
+        project
+            .dependencies(&resolve, &packages, &[], HasDevUnits::No)
+            .unwrap()
+            .into_iter()
+            .map(|pkg| pkg.name().to_string())
+            .sorted()
+            .collect()
+    }
+
+    /// generates the recipes of a binary crate with the `package` table
+    /// `manifest`, passing `args`
+    fn generate_for(manifest: &str, args: &[&str]) -> Vec<Recipe> {
+        try_generate_for(manifest, args).unwrap()
+    }
+
+    fn try_generate_for(manifest: &str, args: &[&str]) -> CargoResult<Vec<Recipe>> {
+        try_generate_in(&[("", manifest)], args)
+    }
+
+    /// generates the recipes of a project with a binary crate for each of
+    /// the `(directory, manifest)` pairs in `manifests`, passing `args`
+    fn try_generate_in(manifests: &[(&str, &str)], args: &[&str]) -> CargoResult<Vec<Recipe>> {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for (dir, manifest) in manifests {
+            let dir = root.join(dir);
+            fs::create_dir_all(dir.join("src")).unwrap();
+            if manifest.contains("[package]") {
+                fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+            }
+            fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        }
+        try_generate_at(root, args)
+    }
+
+    /// generates the recipes of the project at `root`, with its cargo home
+    /// in `<root>/cargo-home`, passing `args`
+    fn try_generate_at(root: &Path, args: &[&str]) -> CargoResult<Vec<Recipe>> {
+        let mut config = GlobalContext::new(
+            Shell::from_write(Box::new(std::io::sink())),
+            root.to_path_buf(),
+            root.join("cargo-home"),
+        );
+        let options = Args::from_iter_safe(std::iter::once(&"cargo-bitbake").chain(args)).unwrap();
+        configure(&options, &mut config)?;
+        generate(&options, &config)
+    }
+
+    #[test]
+    fn config_credentials_stay_out_of_the_provenance() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        let args = [
+            "--config",
+            r#"http.proxy="http://user:pw@proxy:3128""#,
+            "--config=net.git-fetch-with-cli=true",
+            "-v",
+        ];
+        let mut config = GlobalContext::new(
+            Shell::from_write(Box::new(std::io::sink())),
+            root.to_path_buf(),
+            root.join("cargo-home"),
+        );
+        let mut options =
+            Args::from_iter_safe(std::iter::once(&"cargo-bitbake").chain(&args)).unwrap();
+        options.raw_args = recorded_args(args.iter().map(|arg| arg.to_string()));
+        configure(&options, &mut config).unwrap();
+        let recipe = generate(&options, &config).unwrap().remove(0);
+
+        assert!(!recipe.contents.contains("user:pw"));
+        assert!(recipe
+            .contents
+            .contains("#   option: http.proxy=\"http://proxy:3128\"\n"));
+        assert!(recipe
+            .contents
+            .contains("#   option: --config=net.git-fetch-with-cli=true\n"));
+        assert!(!recipe.contents.contains("#   option: -v\n"));
+    }
+
+    #[test]
+    fn update_subcommand_left_out() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            without_subcommand(
+                args(&["--pv", "update", "--compact", "update", "app_0.1.0.bb"]),
+                "update"
+            ),
+            args(&["--pv", "update", "--compact"])
+        );
+        // a recipe may be called like the subcommand
+        assert_eq!(
+            without_subcommand(args(&["--compact", "update", "update"]), "update"),
+            args(&["--compact"])
+        );
+    }
+
+    #[test]
+    fn offline_without_lockfile() {
+        // path dependencies resolve offline without a Cargo.lock
+        let recipe = generate_for(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+            &["--offline"],
+        )
+        .remove(0);
+        assert!(recipe.contents.contains("SRC_URI"));
+
+        // while a registry dependency missing from the cache needs the index
+        let err = try_generate_for(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+             [dependencies]\nno-such-crate-in-the-cache = \"1\"\n",
+            &["--offline"],
+        )
+        .err()
+        .unwrap();
+        assert!(format!("{:#}", err).contains("Cargo.lock' does not exist"));
+    }
+
+    #[test]
+    fn pathological_manifest() {
+        let recipe = generate_for(
+            r#"
+            [package]
+            name = "app"
+            version = "0.1.0"
+            description = "A \"fast\" JSON parser\nwith zero copies and ${@bb.fatal('x')} \\"
+            homepage = "  https://example.com/app \n"
+            license = "MIT"
+            "#,
+            &[],
+        )
+        .remove(0);
+        assert!(recipe
+            .contents
+            .contains("SUMMARY = \"A 'fast' JSON parser\"\n"));
+        assert!(recipe.contents.contains(
+            "DESCRIPTION = \"A 'fast' JSON parser \\\nwith zero copies and $\\{@bb.fatal('x')}\"\n"
+        ));
+        assert!(recipe
+            .contents
+            .contains("HOMEPAGE = \"https://example.com/app\"\n"));
+    }
+
+    #[test]
+    fn closed_license() {
+        let recipe = generate_for(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+             homepage = \"https://example.com\"\n",
+            &[],
+        )
+        .remove(0);
+        assert!(recipe.contents.contains("LICENSE = \"CLOSED\"\n"));
+        assert!(recipe.contents.contains("LIC_FILES_CHKSUM = \" \\\n\"\n"));
+        assert!(!recipe.contents.contains("file://"));
+        match try_generate_for(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+             homepage = \"https://example.com\"\n",
+            &["--closed-is-error"],
+        ) {
+            Err(e) => assert!(e.to_string().contains("add a 'license' field")),
+            Ok(_) => panic!("--closed-is-error must fail"),
+        }
+
+        // a CLOSED license set on purpose is no problem
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"CLOSED\"\n\
+                        rust-version = \"1.74\"\nhomepage = \"https://example.com\"\n";
+        let recipe = generate_for(manifest, &[]).remove(0);
+        assert!(recipe.contents.contains("LICENSE = \"CLOSED\"\n"));
+        assert!(!recipe.contents.contains("file://"));
+        match try_generate_for(manifest, &["--strict"]) {
+            Err(e) => assert!(!format!("{:#}", e).contains("CLOSED")),
+            Ok(_) => panic!("--strict must fail without a git repository"),
+        }
+    }
+
+    #[test]
+    fn missing_license_file() {
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+                        homepage = \"https://example.com\"\n\
+                        license-file = \"docs/LICENSE.custom\"\n";
+        match try_generate_for(manifest, &[]) {
+            Err(e) => assert!(e
+                .to_string()
+                .starts_with("The license-file 'docs/LICENSE.custom' declared in '")),
+            Ok(_) => panic!("a missing license-file must fail"),
+        }
+        let recipe = generate_for(manifest, &["--lenient"]).remove(0);
+        assert!(recipe
+            .contents
+            .contains("file://docs/LICENSE.custom;md5=generateme"));
+        assert!(recipe
+            .contents
+            .contains("# FIXME: update generateme with the real MD5 of the license file\n"));
+
+        // only a placeholder asks for fixing
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::write(
+            dir.path().join("docs/LICENSE.custom"),
+            "All rights reserved\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("Cargo.toml"), manifest).unwrap();
+        let recipe = try_generate_at(dir.path(), &[]).unwrap().remove(0);
+        assert!(recipe.contents.contains("file://docs/LICENSE.custom;md5="));
+        assert!(!recipe.contents.contains("generateme"));
+        assert!(!recipe.contents.contains("FIXME"));
+
+        // the placeholder is where the package is within the sources
+        let recipe = try_generate_in(
+            &[
+                ("", "[workspace]\nmembers = [\"app\"]\n"),
+                ("app", manifest),
+            ],
+            &["--lenient", "--workspace"],
+        )
+        .unwrap_or_else(|e| panic!("{:#}", e))
+        .remove(0);
+        assert!(recipe
+            .contents
+            .contains("file://app/docs/LICENSE.custom;md5=generateme"));
+    }
+
+    #[test]
+    fn unparsable_license() {
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+                        license = \"MIT Apache-2.0\"\nrust-version = \"1.74\"\n\
+                        homepage = \"https://example.com\"\n";
+        let recipe = generate_for(manifest, &[]).remove(0);
+        assert!(recipe.contents.contains("LICENSE = \"MIT Apache-2.0\"\n"));
+        match try_generate_for(manifest, &["--strict"]) {
+            Err(e) => assert!(format!("{:#}", e).contains("Unable to parse the license")),
+            Ok(_) => panic!("--strict must fail for a license that doesn't parse"),
+        }
+    }
+
+    #[test]
+    fn homepage_fallback() {
+        let known = registry_with(&[("app", "0.0.1")], "file://{dir}/crates");
+        let unknown = registry_with(&[], "file://{dir}/crates");
+        let generate_with = |manifest: &str, mirror: &tempfile::TempDir| {
+            generate_with_mirror(manifest, mirror, &[]).remove(0)
+        };
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n";
+        let recipe = generate_with(manifest, &known);
+        assert!(recipe
+            .contents
+            .contains("HOMEPAGE = \"https://crates.io/crates/app\"\n"));
+        // a crate crates.io doesn't know has no page there
+        let recipe = generate_with(manifest, &unknown);
+        assert!(recipe.contents.contains("HOMEPAGE = \"\"\n"));
+        let manifest = format!("{}publish = false\n", manifest);
+        let recipe = generate_with(&manifest, &known);
+        assert!(recipe.contents.contains("HOMEPAGE = \"\"\n"));
+        assert!(try_generate_for(&manifest, &["--strict"]).is_err());
+    }
+
+    #[test]
+    fn rust_version() {
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+                        rust-version = \"1.74\"\nhomepage = \"https://example.com\"\n";
+        let recipe = generate_for(manifest, &[]).remove(0);
+        assert!(recipe.contents.contains("# Requires rustc >= 1.74\n"));
+        assert!(!recipe.contents.contains("RUST_VERSION_MIN"));
+        let recipe = generate_for(manifest, &["--rust-version-var"]).remove(0);
+        assert!(recipe.contents.contains("RUST_VERSION_MIN = \"1.74\"\n"));
+    }
+
+    #[test]
+    fn feature_build_flags() {
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+                        homepage = \"https://example.com\"\n\
+                        [features]\ndefault = [\"a\"]\na = []\nb = []\n";
+        let recipe = generate_for(manifest, &[]).remove(0);
+        assert!(!recipe.contents.contains("CARGO_BUILD_FLAGS"));
+        let recipe = generate_for(manifest, &["--features", "b"]).remove(0);
+        assert!(recipe
+            .contents
+            .contains("CARGO_BUILD_FLAGS:append = \" --features b\"\n"));
+        let recipe =
+            generate_for(manifest, &["--no-default-features", "--features", "a b"]).remove(0);
+        assert!(recipe
+            .contents
+            .contains("CARGO_BUILD_FLAGS:append = \" --no-default-features --features a,b\"\n"));
+    }
+
+    #[test]
+    fn underscores() {
+        let manifest = "[package]\nname = \"foo_bar\"\nversion = \"1.2.3\"\n\
+                        homepage = \"https://example.com\"\n";
+        let recipe = generate_for(manifest, &[]).remove(0);
+        assert_eq!(recipe.path, PathBuf::from("foo-bar_1.2.3.bb"));
+        assert_eq!(recipe.data.crate_name, "foo_bar");
+        let recipe = generate_for(manifest, &["--keep-underscores"]).remove(0);
+        assert_eq!(recipe.path, PathBuf::from("foo_bar_1.2.3.bb"));
+    }
+
+    #[test]
+    fn c_library() {
+        let manifest = "[package]\nname = \"foo\"\nversion = \"1.0.0\"\n\
+                        homepage = \"https://example.com\"\n\
+                        [lib]\npath = \"src/main.rs\"\ncrate-type = [\"cdylib\"]\n";
+        let recipe = generate_for(manifest, &["--cargo-c"]).remove(0);
+        assert!(recipe.contents.contains("\ninherit cargo_c\n"));
+        assert!(recipe
+            .contents
+            .contains("FILES:${PN} += \"${libdir}/libfoo.so.*\"\n"));
+        let recipe = generate_for(manifest, &["--cargo-c=cargo_c_custom"]).remove(0);
+        assert!(recipe.contents.contains("\ninherit cargo_c_custom\n"));
+
+        let plain = "[package]\nname = \"foo\"\nversion = \"1.0.0\"\n\
+                     homepage = \"https://example.com\"\n";
+        let recipe = generate_for(plain, &["--cargo-c"]).remove(0);
+        assert!(recipe.contents.contains("\ninherit cargo\n"));
+        assert!(!recipe.contents.contains("FILES:"));
+    }
+
+    #[test]
+    fn cve_product() {
+        let manifest = "[package]\nname = \"foo_bar\"\nversion = \"1.2.3\"\n\
+                        homepage = \"https://example.com\"\n";
+        let recipe = generate_for(manifest, &[]).remove(0);
+        assert!(recipe.contents.contains("CVE_PRODUCT = \"foo_bar\"\n"));
+        let recipe = generate_for(manifest, &["--keep-underscores"]).remove(0);
+        assert!(!recipe.contents.contains("CVE_PRODUCT"));
+        let recipe =
+            generate_for(manifest, &["--keep-underscores", "--cve-product", "foo"]).remove(0);
+        assert!(recipe.contents.contains("CVE_PRODUCT = \"foo\"\n"));
+
+        let manifest = format!(
+            "{}[package.metadata.bitbake]\ncve_product = \"vendor:foo\"\n",
+            manifest
+        );
+        let recipe = generate_for(&manifest, &[]).remove(0);
+        assert!(recipe.contents.contains("CVE_PRODUCT = \"vendor:foo\"\n"));
+    }
+
+    #[test]
+    fn bitbake_metadata() {
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+                        description = \"An app. It does things.\"\n\
+                        homepage = \"https://example.com\"\n\
+                        [package.metadata.bitbake]\n\
+                        summary = \"The app\"\n\
+                        homepage = \"https://app.example.com\"\n\
+                        depends = [\"openssl\"]\n\
+                        rdepends = [\"bash\"]\n\
+                        inherit = [\"systemd\"]\n\
+                        extra = \"SYSTEMD_SERVICE:${PN} = \\\"app.service\\\"\"\n";
+        let recipe = generate_for(manifest, &[]).remove(0);
+        assert!(recipe.contents.contains("SUMMARY = \"The app\"\n"));
+        assert!(recipe
+            .contents
+            .contains("DESCRIPTION = \"An app. It does things.\"\n"));
+        assert!(recipe
+            .contents
+            .contains("HOMEPAGE = \"https://app.example.com\"\n"));
+        assert!(recipe.contents.contains("\ninherit cargo systemd\n"));
+        assert!(recipe.contents.contains("DEPENDS += \"openssl\"\n"));
+        assert!(recipe.contents.contains("RDEPENDS:${PN} += \"bash\"\n"));
+        assert!(recipe
+            .contents
+            .contains("\nSYSTEMD_SERVICE:${PN} = \"app.service\"\n"));
+
+        // the command line wins
+        let recipe = generate_for(manifest, &["--depends", "zlib"]).remove(0);
+        assert!(recipe.contents.contains("DEPENDS += \"zlib\"\n"));
+        // while the classes add up
+        let recipe = generate_for(
+            manifest,
+            &["--inherit", "useradd,systemd", "--inherit", "cargo"],
+        )
+        .remove(0);
+        assert!(recipe
+            .contents
+            .contains("\ninherit cargo systemd useradd\n"));
+    }
+
+    #[test]
+    fn recipe_section() {
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+                        homepage = \"https://example.com\"\n\
+                        categories = [\"command-line-utilities\"]\n";
+        let recipe = generate_for(manifest, &[]).remove(0);
+        assert!(recipe.contents.contains("SECTION = \"console/utils\"\n"));
+        let recipe = generate_for(manifest, &["--section", "utils"]).remove(0);
+        assert!(recipe.contents.contains("SECTION = \"utils\"\n"));
+        let recipe = generate_for(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+             homepage = \"https://example.com\"\ncategories = [\"encoding\"]\n",
+            &[],
+        )
+        .remove(0);
+        assert!(!recipe.contents.contains("SECTION"));
+    }
+
+    #[test]
+    fn workspace_bitbake_metadata() {
+        let member = |name: &str, bitbake: &str| {
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\
+                 homepage = \"https://example.com\"\nlicense = \"MIT\"\n{}",
+                name, bitbake
+            )
+        };
+        let agent = member("agent", "");
+        let cli = member("cli", "[package.metadata.bitbake]\ndepends = [\"zlib\"]\n");
+        let recipes = try_generate_in(
+            &[
+                (
+                    "",
+                    "[workspace]\nmembers = [\"agent\", \"cli\"]\n\
+                     [workspace.metadata.bitbake]\ndepends = [\"openssl\"]\n\
+                     maintainer = \"Team <team@example.com>\"\n",
+                ),
+                ("agent", &agent),
+                ("cli", &cli),
+            ],
+            &["--workspace"],
+        );
+        let recipes = match recipes {
+            Ok(recipes) => recipes,
+            Err(e) => panic!("{:#}", e),
+        };
+        let recipe = |name: &str| {
+            recipes
+                .iter()
+                .find(|recipe| recipe.data.name == name)
+                .unwrap()
+                .contents
+                .as_str()
+        };
+        assert!(recipe("agent").contains("DEPENDS += \"openssl\"\n"));
+        assert!(recipe("cli").contains("DEPENDS += \"zlib\"\n"));
+        assert!(!recipe("cli").contains("openssl"));
+        for name in ["agent", "cli"] {
+            assert!(recipe(name).contains("MAINTAINER = \"Team <team@example.com>\"\n"));
+        }
+    }
+
+    #[test]
+    fn common_licenses() {
+        let manifest = "[package]\nname = \"foo\"\nversion = \"1.0.0\"\n\
+                        homepage = \"https://example.com\"\n\
+                        license = \"GPL-2.0+ AND (MIT OR LicenseRef-Foo)\"\n";
+        let recipe = generate_for(manifest, &[]).remove(0);
+        assert!(recipe
+            .contents
+            .contains("LICENSE = \"GPL-2.0-or-later & (MIT | LicenseRef-Foo)\"\n"));
+        match try_generate_for(manifest, &["--deny-unknown-licenses"]) {
+            Err(err) => assert!(err.to_string().contains("LicenseRef-Foo")),
+            Ok(_) => panic!("unknown license accepted"),
+        }
+    }
+
+    #[test]
+    fn pn() {
+        assert!(check_pn("rust-my-tool").is_ok());
+        assert!(check_pn("gtk+3").is_ok());
+        assert!(check_pn("").is_err());
+        assert!(check_pn("my_tool").is_err());
+        assert!(check_pn("MyTool").is_err());
+    }
+
+    #[test]
+    fn pv() {
+        assert!(check_pv("1.2.3+git").is_ok());
+        assert!(check_pv("").is_err());
+        assert!(check_pv("1.0 beta").is_err());
+        assert!(check_pv("1_0").is_err());
+    }
+
+    #[test]
+    fn maintainer() {
+        assert_eq!(maintainer_line(None, &[]), "");
+        assert_eq!(maintainer_line(None, &["", " "]), "");
+        assert_eq!(
+            maintainer_line(None, &["A <a@example.com>", "B"]),
+            "MAINTAINER = \"A <a@example.com>, B\"\n"
+        );
+        assert_eq!(
+            maintainer_line(Some("Jane Doe <jane@example.com>"), &["A"]),
+            "MAINTAINER = \"Jane Doe <jane@example.com>\"\n"
+        );
+    }
+
+    #[test]
+    fn recipe_list() {
+        let values = vec![
+            "zlib".to_string(),
+            "openssl, dbus".to_string(),
+            "zlib,".to_string(),
+        ];
+        assert_eq!(recipe_names(&values), vec!["dbus", "openssl", "zlib"]);
+    }
+
+    #[test]
+    fn subdirectories() {
+        for (path, src_dir) in [
+            ("", ""),
+            (".", ""),
+            ("agent", "agent"),
+            ("./agent/", "agent"),
+            ("rust/agent", "rust/agent"),
+        ] {
+            let src_dir_of_path = source_dir(Path::new(path));
+            assert_eq!(src_dir_of_path, src_dir);
+            assert_eq!(
+                source_location("git", &src_dir_of_path, false),
+                ("${WORKDIR}/git".to_string(), src_dir.to_string())
+            );
+        }
+        assert_eq!(
+            source_location("git", "", true),
+            ("${WORKDIR}/git".to_string(), String::new())
+        );
+        assert_eq!(
+            source_location("git", "agent", true),
+            ("${WORKDIR}/git/agent".to_string(), String::new())
+        );
+        assert_eq!(
+            source_location("foo-1.0", "rust/agent", true),
+            ("${WORKDIR}/foo-1.0/rust/agent".to_string(), String::new())
+        );
+    }
+
+    /// a git repository with the library crates `names` in a workspace and
+    /// the `(path, contents)` pairs of `files`
+    fn git_repo_with(names: &[&str], files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let members = names
+            .iter()
+            .map(|name| format!("\"{}\"", name))
+            .collect::<Vec<_>>();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            format!("[workspace]\nmembers = [{}]\n", members.join(", ")),
+        )
+        .unwrap();
+        for name in names {
+            fs::create_dir_all(dir.path().join(name).join("src")).unwrap();
+            fs::write(
+                dir.path().join(name).join("Cargo.toml"),
+                format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name),
+            )
+            .unwrap();
+            fs::write(dir.path().join(name).join("src/lib.rs"), "").unwrap();
+        }
+        for (path, contents) in files {
+            fs::write(dir.path().join(path), contents).unwrap();
+        }
+
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "init", &tree, &[])
+            .unwrap();
+        dir
+    }
+
+    #[test]
+    fn project_git_tags() {
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\
+                        [[bin]]\nname = \"app\"\npath = \"main.rs\"\n";
+        let dir = git_repo_with(
+            &[],
+            &[("Cargo.toml", manifest), ("main.rs", "fn main() {}\n")],
+        );
+        let repo = git2::Repository::open(dir.path()).unwrap();
+        repo.remote("origin", "https://github.com/team/app.git")
+            .unwrap();
+        let head = repo.head().unwrap().peel(git2::ObjectType::Commit).unwrap();
+        repo.tag_lightweight("v0.1.0", &head, false).unwrap();
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        let generate_with = |args: &[&str]| {
+            try_generate_at(dir.path(), args)
+                .unwrap_or_else(|e| panic!("{:#}", e))
+                .remove(0)
+                .contents
+        };
+
+        // the branch checked out at a tag is fetched from the tag
+        let contents = generate_with(&["--yocto-release", "styhead", "--git-tags"]);
+        assert!(contents.contains(
+            "SRC_URI += \"git://github.com/team/app.git;protocol=https;nobranch=1;tag=v0.1.0\"\n"
+        ));
+
+        // unless given a --branch
+        let contents = generate_with(&[
+            "--yocto-release",
+            "styhead",
+            "--git-tags",
+            "--branch",
+            &branch,
+        ]);
+        assert!(contents.contains(&format!(
+            "SRC_URI += \"git://github.com/team/app.git;protocol=https;branch={}\"\n",
+            branch
+        )));
+        assert!(!contents.contains(";tag="));
+    }
+
+    #[test]
+    fn git_dependency_paths() {
+        let repo = git_repo_with(&["hal", "hal-sys"], &[]);
+        let other = git_repo_with(&["net"], &[]);
+        let url = format!("file://{}", repo.path().display());
+        let other_url = format!("file://{}", other.path().display());
+        let fetched = format!("git://{};protocol=file", repo.path().display());
+        let other_fetched = format!("git://{};protocol=file", other.path().display());
+        let manifest = format!(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+             [dependencies]\nhal = {{ git = \"{0}\" }}\nhal-sys = {{ git = \"{0}\" }}\n\
+             net = {{ git = \"{1}\" }}\n",
+            url, other_url
+        );
+
+        // crates of one repository share its checkout, which is unpacked
+        // where cargo is told to look for each of them
+        let args = ["--legacy-overrides", "--allow-local-git"];
+        let recipe = generate_for(&manifest, &args).remove(0);
+        assert_eq!(recipe.contents.matches(&fetched).count(), 1);
+        assert!(recipe.contents.contains(&format!(
+            "    {};nobranch=1;name=hal;destsuffix=hal \\\n",
+            fetched
+        )));
+        assert!(recipe.contents.contains(&format!(
+            "    {};nobranch=1;name=net;destsuffix=net \\\n",
+            other_fetched
+        )));
+        for line in [
+            "SRCREV_FORMAT .= \"_hal\"\n",
+            "SRCREV_FORMAT .= \"_net\"\n",
+            "SRCREV_hal = \"${AUTOREV}\"\n",
+            "SRCREV_net = \"${AUTOREV}\"\n",
+            "EXTRA_OECARGO_PATHS += \"${WORKDIR}/hal/hal\"\n",
+            "EXTRA_OECARGO_PATHS += \"${WORKDIR}/hal/hal-sys\"\n",
+            "EXTRA_OECARGO_PATHS += \"${WORKDIR}/net/net\"\n",
+        ] {
+            assert_eq!(recipe.contents.matches(line).count(), 1, "{}", line);
+        }
+        assert!(!recipe.contents.contains("hal-sys;"));
+        assert_eq!(recipe.contents.matches("SRCREV_FORMAT").count(), 2);
+
+        // or at the checkout with --group-git-repos
+        let recipe = generate_for(
+            &manifest,
+            &[
+                "--legacy-overrides",
+                "--allow-local-git",
+                "--group-git-repos",
+            ],
+        )
+        .remove(0);
+        assert_eq!(recipe.contents.matches(&fetched).count(), 1);
+        assert!(recipe
+            .contents
+            .contains("EXTRA_OECARGO_PATHS += \"${WORKDIR}/hal\"\n"));
+        assert!(recipe
+            .contents
+            .contains("EXTRA_OECARGO_PATHS += \"${WORKDIR}/net\"\n"));
+        assert_eq!(recipe.contents.matches("EXTRA_OECARGO_PATHS").count(), 2);
+    }
+
+    #[test]
+    fn short_git_rev() {
+        let src_id = SourceId::from_url("git+https://github.com/a/hal?rev=abc1234").unwrap();
+        let e = git_srcrev("hal", src_id, false).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Unable to find the commit of the git dependency hal at rev 'abc1234', run \
+             `cargo update -p hal` to record it in Cargo.lock or use the full 40 character sha"
+        );
+        let full = "0123456789abcdef0123456789abcdef01234567";
+        let src_id =
+            SourceId::from_url(&format!("git+https://github.com/a/hal?rev={}", full)).unwrap();
+        assert_eq!(git_srcrev("hal", src_id, false).unwrap(), full);
+    }
+
+    #[test]
+    fn reproducible_git_revs() {
+        let sha = "0123456789abcdef0123456789abcdef01234567";
+        for query in ["?branch=master", "", "?branch=release/2.4"] {
+            let url = format!("git+https://github.com/a/hal{}", query);
+            let locked = SourceId::from_url(&format!("{}#{}", url, sha)).unwrap();
+            assert_eq!(git_srcrev("hal", locked, true).unwrap(), sha, "{}", url);
+        }
+        // without a commit in Cargo.lock the branch is followed as before
+        let unlocked = SourceId::from_url("git+https://github.com/a/hal?branch=master").unwrap();
+        assert_eq!(git_srcrev("hal", unlocked, true).unwrap(), "${AUTOREV}");
+        let locked = SourceId::from_url(&format!("git+https://github.com/a/hal#{}", sha)).unwrap();
+        assert_eq!(git_srcrev("hal", locked, false).unwrap(), "${AUTOREV}");
+
+        // as the warnings suggest it either way
+        for flag in ["-R", "--reproducible"] {
+            assert!(
+                Args::from_iter_safe(["cargo-bitbake", flag])
+                    .unwrap()
+                    .reproducible
+            );
+        }
+    }
+
+    #[test]
+    fn branch_git_pins() {
+        for args in [
+            &["cargo-bitbake"][..],
+            &["cargo-bitbake", "--legacy-overrides"],
+        ] {
+            let options = Args::from_iter_safe(args).unwrap();
+            for branch in ["main", "master", "develop"] {
+                let src_id =
+                    SourceId::from_url(&format!("git+https://github.com/a/hal?branch={}", branch))
+                        .unwrap();
+                let rev = git_srcrev("hal", src_id, false).unwrap();
+                assert_eq!(rev, "${AUTOREV}");
+                assert_eq!(
+                    git_pin(src_id, &rev, &options, true, &mut HashMap::new()),
+                    git::GitPin::Branch(branch.to_string())
+                );
+            }
+        }
+
+        // tags are only named for the fetcher of styhead and later
+        let src_id = SourceId::from_url("git+https://github.com/a/hal?tag=v1.0.0").unwrap();
+        for (release, pin) in [
+            ("scarthgap", git::GitPin::Detached),
+            ("styhead", git::GitPin::Tag("v1.0.0".to_string())),
+        ] {
+            let options =
+                Args::from_iter_safe(["cargo-bitbake", "--git-tags", "--yocto-release", release])
+                    .unwrap();
+            assert_eq!(
+                git_pin(src_id, "0123456789", &options, true, &mut HashMap::new()),
+                pin
+            );
+        }
+
+        // the default branch of a repository is asked for once
+        let options = Args::from_iter_safe(["cargo-bitbake"]).unwrap();
+        let src_id = SourceId::from_url("git+https://git.invalid/a/hal").unwrap();
+        let rev = git_srcrev("hal", src_id, false).unwrap();
+        let mut default_branches = HashMap::new();
+        assert_eq!(
+            git_pin(src_id, &rev, &options, false, &mut default_branches),
+            git::GitPin::Detached
+        );
+        assert_eq!(default_branches.len(), 1);
+        default_branches.insert(src_id.url().to_string(), Some("trunk".to_string()));
+        assert_eq!(
+            git_pin(src_id, &rev, &options, false, &mut default_branches),
+            git::GitPin::Branch("trunk".to_string())
+        );
+    }
+
+    #[test]
+    fn local_git_dependency() {
+        let repo = git_repo_with(&["hal"], &[]);
+        let manifest = format!(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+             [dependencies]\nhal = {{ git = \"file://{}\" }}\n",
+            repo.path().display()
+        );
+        let recipe = generate_for(&manifest, &["--legacy-overrides"]).remove(0);
+        assert!(recipe.contents.contains(
+            "    git://TODO.invalid/hal.git;protocol=https;nobranch=1;name=hal;destsuffix=hal \\\n"
+        ));
+        assert!(recipe.contents.contains(&format!(
+            "# TODO: fetch hal from where others can, not from {}\n",
+            repo.path().display()
+        )));
+        assert!(!recipe
+            .contents
+            .contains(&format!("file://{}", repo.path().display())));
+        match try_generate_for(&manifest, &["--legacy-overrides", "--strict"]) {
+            Err(e) => assert!(e.to_string().contains("The git dependency hal is fetched")),
+            Ok(_) => panic!("--strict must fail for a local git dependency"),
+        }
+    }
+
+    #[test]
+    fn git_checkouts() {
+        let repo = git_repo_with(&["hal"], &[(".gitmodules", "")]);
+        assert_eq!(
+            checkout_of(&repo.path().join("hal")),
+            GitCheckout {
+                subdir: "hal".into(),
+                prefix: git::GitPrefix::GitSubmodule,
+                lfs: false,
+            }
+        );
+        let lfs = git_repo_with(&["hal"], &[(".gitattributes", "*.bin filter=lfs\n")]);
+        assert_eq!(
+            checkout_of(lfs.path()),
+            GitCheckout {
+                subdir: String::new(),
+                prefix: git::GitPrefix::Git,
+                lfs: true,
+            }
+        );
+    }
+
+    /// a git index registry at `file://<dir>/index` serving `crates` as
+    /// `(name, version)` from `dl`, where `{dir}` is replaced by the
+    /// registry's directory, with the crate archives in `<dir>/crates`
+    fn registry_with(crates: &[(&str, &str)], dl: &str) -> tempfile::TempDir {
+        use sha2::{Digest, Sha256};
+
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("index");
+        fs::create_dir_all(dir.path().join("crates")).unwrap();
+        fs::create_dir_all(&index).unwrap();
+        let dl = dl.replace("{dir}", &dir.path().display().to_string());
+        fs::write(
+            index.join("config.json"),
+            serde_json::json!({ "dl": dl }).to_string(),
+        )
+        .unwrap();
+        for (name, version) in crates {
+            let manifest = format!(
+                "[package]\nname = \"{}\"\nversion = \"{}\"\n",
+                name, version
+            );
+            let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+                vec![],
+                flate2::Compression::default(),
+            ));
+            for (path, contents) in [("Cargo.toml", manifest.as_str()), ("src/lib.rs", "")] {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(contents.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                archive
+                    .append_data(
+                        &mut header,
+                        format!("{}-{}/{}", name, version, path),
+                        contents.as_bytes(),
+                    )
+                    .unwrap();
+            }
+            let archive = archive.into_inner().unwrap().finish().unwrap();
+            fs::write(
+                dir.path()
+                    .join("crates")
+                    .join(format!("{}-{}.crate", name, version)),
+                &archive,
+            )
+            .unwrap();
+            let entry = serde_json::json!({
+                "name": name,
+                "vers": version,
+                "deps": [],
+                "cksum": format!("{:x}", Sha256::digest(&archive)),
+                "features": {},
+                "yanked": false,
+            });
+            let path = match name.len() {
+                1 => index.join("1"),
+                2 => index.join("2"),
+                3 => index.join("3").join(&name[..1]),
+                _ => index.join(&name[..2]).join(&name[2..4]),
+            };
+            fs::create_dir_all(&path).unwrap();
+            // one line per version
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path.join(name))
+                .unwrap()
+                .write_all(format!("{}\n", entry).as_bytes())
+                .unwrap();
+        }
+
+        let repo = git2::Repository::init(&index).unwrap();
+        let mut git_index = repo.index().unwrap();
+        git_index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        let tree = repo.find_tree(git_index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "init", &tree, &[])
+            .unwrap();
+        dir
+    }
+
+    /// the `--config` arguments replacing crates.io with the registry at
+    /// `index`
+    fn mirror_args(index: &str) -> Vec<String> {
+        vec![
+            "--config".to_string(),
+            "source.crates-io.replace-with=\"mirror\"".to_string(),
+            "--config".to_string(),
+            format!("source.mirror.registry=\"{}\"", index),
+        ]
+    }
+
+    /// generates the recipes of a binary crate with the `package` table
+    /// `manifest` with crates.io replaced by `mirror`, a registry made by
+    /// `registry_with`, passing `args`
+    fn generate_with_mirror(
+        manifest: &str,
+        mirror: &tempfile::TempDir,
+        args: &[&str],
+    ) -> Vec<Recipe> {
+        let mirror = mirror_args(&format!("file://{}/index", mirror.path().display()));
+        let args = mirror
+            .iter()
+            .map(String::as_str)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>();
+        generate_for(manifest, &args)
+    }
+
+    #[test]
+    fn alternate_registries() {
+        let mirror = registry_with(&[("itoa", "1.0.0")], "file://{dir}/crates");
+        let internal = registry_with(
+            &[("corp-util", "1.2.0")],
+            "file://{dir}/crates/{crate}-{version}.crate",
+        );
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+                        [dependencies]\nitoa = \"1\"\n\
+                        corp-util = { version = \"1\", registry = \"internal\" }\n";
+        let internal_config = format!(
+            "registries.internal.index=\"file://{}/index\"",
+            internal.path().display()
+        );
+        let args = ["--config", &internal_config];
+        let sha256 = |dir: &tempfile::TempDir, name: &str| {
+            let archive = fs::read(dir.path().join("crates").join(name)).unwrap();
+            format!("{:x}", <sha2::Sha256 as sha2::Digest>::digest(&archive))
+        };
+
+        // crates of both are fetched with the crate fetcher, from crates.io
+        // for those replaced by a mirror
+        let recipe = generate_with_mirror(
+            manifest,
+            &mirror,
+            &[
+                &args[..],
+                &["--registry-host", "https://crates.corp.example.com/"],
+            ]
+            .concat(),
+        )
+        .remove(0);
+        assert!(recipe
+            .contents
+            .contains("    crate://crates.io/itoa/1.0.0 \\\n"));
+        assert!(recipe
+            .contents
+            .contains("    crate://crates.corp.example.com/corp-util/1.2.0 \\\n"));
+        assert!(recipe.contents.contains(&format!(
+            "SRC_URI[itoa-1.0.0.sha256sum] = \"{}\"\n",
+            sha256(&mirror, "itoa-1.0.0.crate")
+        )));
+        assert!(recipe.contents.contains(&format!(
+            "SRC_URI[corp-util-1.2.0.sha256sum] = \"{}\"\n",
+            sha256(&internal, "corp-util-1.2.0.crate")
+        )));
+        assert!(!recipe.contents.contains("/index"));
+
+        // or from where the registry serves them
+        let recipe = generate_with_mirror(
+            manifest,
+            &mirror,
+            &[&args[..], &["--registry-downloads"]].concat(),
+        )
+        .remove(0);
+        assert!(recipe
+            .contents
+            .contains("    crate://crates.io/itoa/1.0.0 \\\n"));
+        assert!(recipe.contents.contains(&format!(
+            "    file://{}/crates/corp-util-1.2.0.crate;downloadfilename=corp-util-1.2.0.crate;\
+             name=corp-util-1.2.0.crate;subdir=cargo_home/bitbake \\\n",
+            internal.path().display()
+        )));
+        assert!(recipe
+            .contents
+            .contains("SRC_URI[corp-util-1.2.0.crate.sha256sum]"));
+        assert!(recipe
+            .contents
+            .contains("corp-util-1.2.0/.cargo-checksum.json"));
+        assert!(!recipe.contents.contains("itoa-1.0.0/.cargo-checksum.json"));
+    }
+
+    /// serves the registry in `dir` over http from `listener`, like a
+    /// sparse index and the crates.io download API
+    fn serve(listener: std::net::TcpListener, dir: PathBuf) {
+        use std::io::{BufRead, BufReader};
+
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = String::new();
+                {
+                    let mut reader = BufReader::new(&stream);
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).map_or(false, |n| n > 0) {
+                        if request.is_empty() {
+                            request = line.clone();
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        line.clear();
+                    }
+                }
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let segments = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+                let file = match segments[..] {
+                    ["api", "v1", "crates", name, version, "download"] => dir
+                        .join("crates")
+                        .join(format!("{}-{}.crate", name, version)),
+                    _ => dir.join(path.trim_start_matches('/')),
+                };
+                let (status, body) = match fs::read(file) {
+                    Ok(body) => ("200 OK", body),
+                    Err(_) => ("404 Not Found", vec![]),
+                };
+                let mut response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .into_bytes();
+                response.extend(body);
+                let _ = stream.write_all(&response);
+            }
+        });
+    }
+
+    #[test]
+    fn sparse_registries() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let registry = registry_with(
+            &[("itoa", "1.0.0"), ("corp-util", "1.2.0")],
+            &format!("http://{}/api/v1/crates", host),
+        );
+        serve(listener, registry.path().to_path_buf());
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+                        [dependencies]\nitoa = \"1\"\n\
+                        corp-util = { version = \"1\", registry = \"internal\" }\n";
+        let generate_with = |protocol: &str, index: &str| {
+            let protocol = format!("registries.crates-io.protocol=\"{}\"", protocol);
+            let internal = format!("registries.internal.index=\"{}\"", index);
+            let mirror = mirror_args(index);
+            let args = ["--config", &protocol, "--config", &internal]
+                .iter()
+                .copied()
+                .chain(mirror.iter().map(String::as_str))
+                .collect::<Vec<_>>();
+            generate_for(manifest, &args).remove(0).contents
+        };
+
+        // the same crates from a git and a sparse index give the same entries
+        let git = generate_with(
+            "git",
+            &format!("file://{}/index", registry.path().display()),
+        );
+        let sparse = generate_with("sparse", &format!("sparse+http://{}/index/", host));
+        let entries = |recipe: &str| {
+            recipe
+                .lines()
+                .filter(|line| line.starts_with("    crate://") || line.starts_with("SRC_URI["))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(entries(&git).len(), 4);
+        assert_eq!(entries(&git), entries(&sparse));
+        assert!(sparse.contains("    crate://crates.io/itoa/1.0.0 \\\n"));
+        assert!(sparse.contains(&format!(
+            "    crate://{}/api/v1/crates/corp-util/1.2.0 \\\n",
+            host
+        )));
+        assert!(!sparse.contains("sparse+"));
+    }
+
+    #[test]
+    fn patched_crates() {
+        let mirror = registry_with(
+            &[("ring", "0.1.0"), ("ring", "0.2.0")],
+            "file://{dir}/crates/{crate}-{version}.crate",
+        );
+        let fork = git_repo_with(&["ring"], &[]);
+        let manifest = format!(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+             [dependencies]\nring = \"0.1\"\nring2 = {{ package = \"ring\", version = \"0.2\" }}\n\
+             [patch.crates-io]\nring = {{ git = \"file://{}\" }}\n",
+            fork.path().display()
+        );
+
+        // only the fork cargo builds is fetched
+        let recipe = generate_with_mirror(&manifest, &mirror, &["--allow-local-git"]).remove(0);
+        let fetched = format!("git://{};protocol=file", fork.path().display());
+        assert!(recipe.contents.contains(&format!("    {};", fetched)));
+        assert!(!recipe.contents.contains("crate://crates.io/ring/0.1.0"));
+        assert!(!recipe.contents.contains("SRC_URI[ring-0.1.0.sha256sum]"));
+        assert!(recipe.contents.contains(&format!(
+            "# patched: ring -> git://{}\n",
+            fork.path().display()
+        )));
+        // another version of the crate the patch doesn't replace isn't
+        assert!(recipe
+            .contents
+            .contains("    crate://crates.io/ring/0.2.0 \\\n"));
+        assert!(!recipe.contents.contains("# patched: ring -> crate://"));
+    }
+
+    #[test]
+    fn crates_inc_only() {
+        let mirror = registry_with(&[("itoa", "1.0.0")], "file://{dir}/crates");
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+                        [dependencies]\nitoa = \"1\"\n";
+
+        // the recipe has no SRC_URI assignment left for the dependencies
+        let recipe = generate_with_mirror(manifest, &mirror, &["--crates-inc"]).remove(0);
+        assert!(recipe.contents.contains("require ${BPN}-crates.inc\n"));
+        assert!(!recipe.contents.contains("SRC_URI += \" \\\n"));
+        assert!(recipe.includes[0]
+            .contents
+            .contains("    crate://crates.io/itoa/1.0.0 \\\n"));
+    }
+
+    #[test]
+    fn bbappend_base_recipe() {
+        let mirror = registry_with(&[("itoa", "1.0.0")], "file://{dir}/crates");
+        let base = tempfile::tempdir().unwrap();
+        let base_path = base.path().join("app_0.1.0.bb");
+        fs::write(
+            &base_path,
+            "SRC_URI += \"git://example.com/app.git;protocol=https;branch=main\"\n\
+             SRC_URI += \" \\\n    crate://crates.io/itoa/0.9.0 \\\n\
+             \x20   crate://crates.io/itoa/1.0.0 \\\n    file://0001-fix.patch \\\n\"\n\
+             SRC_URI[itoa-0.9.0.sha256sum] = \"aaa\"\n",
+        )
+        .unwrap();
+        let base_arg = base_path.display().to_string();
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+                        [dependencies]\nitoa = \"1\"\n";
+        let args = ["--bbappend", "--base-recipe", &base_arg];
+
+        // the crates the base recipe no longer needs aren't fetched
+        let recipe = generate_with_mirror(manifest, &mirror, &args).remove(0);
+        assert_eq!(recipe.path, PathBuf::from("app_%.bbappend"));
+        assert!(recipe
+            .contents
+            .contains("SRC_URI += \" \\\n    crate://crates.io/itoa/1.0.0 \\\n\"\n"));
+        assert!(recipe
+            .contents
+            .contains("SRC_URI:remove = \" \\\n    crate://crates.io/itoa/0.9.0 \\\n\"\n"));
+        assert!(!recipe.contents.contains("0001-fix.patch"));
+        assert!(!recipe.contents.contains("app.git"));
+    }
+
+    #[test]
+    fn reproducible_vendor_tarball() {
+        let mirror = registry_with(
+            &[("itoa", "1.0.0"), ("corp-util", "1.2.0")],
+            "file://{dir}/crates/{crate}-{version}.crate",
+        );
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+                        [dependencies]\nitoa = \"1\"\ncorp-util = \"1\"\n";
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.path().join("Cargo.toml"), manifest).unwrap();
+        let mirror = mirror_args(&format!("file://{}/index", mirror.path().display()));
+        let args = std::iter::once("--vendor")
+            .chain(mirror.iter().map(String::as_str))
+            .collect::<Vec<_>>();
+
+        // the crates unpacked by the first run are dated an hour later for
+        // the second one
+        let first = try_generate_at(dir.path(), &args).unwrap().remove(0);
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(3600);
+        let mut dirs = vec![dir.path().join("cargo-home/registry/src")];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    fs::File::options()
+                        .write(true)
+                        .open(&path)
+                        .and_then(|file| file.set_modified(later))
+                        .unwrap();
+                }
+            }
+        }
+        let second = try_generate_at(dir.path(), &args).unwrap().remove(0);
+        let (first, second) = (first.vendored.unwrap(), second.vendored.unwrap());
+        assert_eq!(first.contents, second.contents);
+        assert_eq!(first.sha256, second.sha256);
+        assert_eq!(first.path, second.path);
+        assert!(first
+            .path
+            .to_string_lossy()
+            .starts_with(&format!("app-crates-{}", &first.sha256[..16])));
+    }
+
+    #[test]
+    fn sys_deps_of_linked_crates() {
+        let mirror = registry_with(
+            &[
+                ("libz-sys", "1.1.0"),
+                ("openssl-sys", "0.9.0"),
+                ("libsqlite3-sys", "0.28.0"),
+            ],
+            "file://{dir}/crates/{crate}-{version}.crate",
+        );
+        let manifest = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+                        [dependencies]\nlibz-sys = \"1\"\n\
+                        [dev-dependencies]\nopenssl-sys = \"0.9\"\n\
+                        [target.'cfg(windows)'.dependencies]\nlibsqlite3-sys = \"0.28\"\n";
+        let recipe = generate_with_mirror(manifest, &mirror, &["--include-dev-deps"]).remove(0);
+        assert!(recipe.contents.contains("DEPENDS += \"zlib\"\n"));
+        assert!(!recipe.contents.contains("openssl\""));
+        assert!(!recipe.contents.contains("sqlite3\""));
+    }
+
+    #[test]
+    fn external_path_dependencies() {
+        let app = "[package]\nname = \"app\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n\
+                   [dependencies]\nshared = { path = \"../shared\" }\n\
+                   inner = { path = \"inner\" }\n";
+        let lib = |name: &str| {
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n[lib]\npath = \"src/main.rs\"\n",
+                name
+            )
+        };
+        let manifests = [
+            ("app", app),
+            ("app/inner", &lib("inner")),
+            ("shared", &lib("shared")),
+        ];
+        let args = ["--manifest-path", "app/Cargo.toml"];
+
+        // their sources won't be there when building
+        let e = match try_generate_in(&manifests, &args) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("external path dependencies must fail"),
+        };
+        assert!(e.starts_with("The path dependencies shared ('"), "{}", e);
+        assert!(e.contains("--allow-external-paths"));
+        assert!(!e.contains("inner"));
+
+        // unless they're fetched by hand
+        let recipe = try_generate_in(
+            &manifests,
+            &[&args[..], &["--allow-external-paths"]].concat(),
+        )
+        .unwrap()
+        .remove(0);
+        let todo = recipe
+            .contents
+            .lines()
+            .find(|line| line.starts_with("# TODO: fetch the path dependency shared from "))
+            .unwrap();
+        assert!(todo.ends_with("/shared and add where it is unpacked to EXTRA_OECARGO_PATHS"));
+        assert!(!recipe.contents.contains("inner"));
+    }
+
+    #[test]
+    fn pkgconfig_inherit() {
+        assert_eq!(
+            inherit_line("cargo", &BTreeMap::new(), &[]),
+            "inherit cargo\n"
+        );
+        let users = [
+            ("pkg-config", ["openssl-sys", "libz-sys"].into()),
+            ("system-deps", ["glib-sys"].into()),
+        ]
+        .into();
+        assert_eq!(
+            inherit_line("cargo", &users, &[]),
+            "# pkgconfig for pkg-config (libz-sys, openssl-sys), system-deps (glib-sys)\n\
+             inherit cargo pkgconfig\n"
+        );
+        assert_eq!(
+            inherit_line("cargo", &users, &["systemd".into(), "pkgconfig".into()]),
+            "# pkgconfig for pkg-config (libz-sys, openssl-sys), system-deps (glib-sys)\n\
+             inherit cargo pkgconfig systemd\n"
+        );
+    }
+
+    #[test]
+    fn write_file_replaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = GlobalContext::new(
+            Shell::new(),
+            dir.path().to_path_buf(),
+            dir.path().join("cargo-home"),
+        );
+        let path = dir.path().join("app.inc");
+        fs::write(&path, "old\n").unwrap();
+        write_file(&config, &path, "new\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert!(!dir.path().join(".app.inc.tmp").exists());
+
+        // an up to date file is not rewritten
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        write_file(&config, &path, "new\n").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        // nor is the temporary file left behind when it can't replace it
+        let path = dir.path().join("taken");
+        fs::create_dir_all(path.join("sub")).unwrap();
+        assert!(write_file(&config, &path, "new\n").is_err());
+        assert!(!dir.path().join(".taken.tmp").exists());
+    }
+
+    #[test]
+    fn all_platforms_without_target() {
+        assert_eq!(resolved_names(None), vec!["app", "common", "winonly"]);
+    }
+
+    #[test]
+    fn config_build_target() {
+        assert_eq!(
+            resolved_names(Some("aarch64-unknown-linux-gnu")),
+            vec!["app", "common"]
+        );
+        assert_eq!(
+            resolved_names(Some("x86_64-pc-windows-gnu")),
+            vec!["app", "common", "winonly"]
+        );
+    }
+}
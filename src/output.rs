@@ -0,0 +1,181 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Machine-readable output of `--format json`.
+//!
+//! The document is versioned with `schema_version`, which is bumped
+//! whenever a field is removed or changes meaning. New fields may be
+//! added without a bump.
+
+use anyhow::anyhow;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Version of the JSON document
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// What cargo-bitbake writes
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// the recipe files
+    Recipe,
+    /// a JSON document on stdout
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "recipe" => Ok(Format::Recipe),
+            "json" => Ok(Format::Json),
+            _ => Err(anyhow!("unknown format '{}', expected recipe or json", s)),
+        }
+    }
+}
+
+/// The document printed with `--format json`
+#[derive(Debug, Serialize)]
+pub struct Document {
+    pub schema_version: u32,
+    pub recipes: Vec<RecipeData>,
+    pub warnings: Vec<Warning>,
+}
+
+/// Everything computed for one recipe
+#[derive(Debug, Default, Serialize)]
+pub struct RecipeData {
+    /// file name of the recipe
+    pub path: String,
+    /// PN
+    pub name: String,
+    /// PV
+    pub version: String,
+    pub crate_name: String,
+    pub crate_version: String,
+    pub summary: String,
+    /// the whole description, `null` if the summary already says it all
+    pub description: Option<String>,
+    pub homepage: String,
+    /// the LICENSE expression
+    pub license: String,
+    pub crates: Vec<CrateData>,
+    pub git_dependencies: Vec<GitData>,
+    pub project: ProjectData,
+}
+
+/// A dependency fetched as an archive
+#[derive(Debug, Serialize)]
+pub struct CrateData {
+    pub name: String,
+    pub version: String,
+    pub src_uri: String,
+    /// `null` if the registry provides none
+    pub sha256: Option<String>,
+}
+
+/// A dependency fetched from a git repository
+#[derive(Debug, Serialize)]
+pub struct GitData {
+    /// name of the checkout, the first of the crates from the repository
+    pub name: String,
+    pub src_uri: String,
+    /// the commit, `${AUTOREV}` for branch tracking dependencies
+    pub srcrev: String,
+}
+
+/// Where the project itself is fetched from
+#[derive(Debug, Default, Serialize)]
+pub struct ProjectData {
+    pub src_uri: String,
+    pub rev: String,
+    pub branch: String,
+    /// whether `rev` is tagged
+    pub tag: bool,
+}
+
+/// A warning emitted while generating
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct Warning {
+    pub message: String,
+}
+
+/// A shell output capturing everything written to it
+#[derive(Clone, Default)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// splits captured shell output into the warnings and the other lines
+pub fn split_warnings(captured: &str) -> (Vec<Warning>, Vec<&str>) {
+    let mut warnings = vec![];
+    let mut other = vec![];
+    for line in captured.lines() {
+        match line.strip_prefix("warning: ") {
+            Some(message) => warnings.push(Warning {
+                message: message.to_string(),
+            }),
+            None => other.push(line),
+        }
+    }
+    (warnings, other)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn warnings() {
+        let (warnings, other) =
+            split_warnings("   Resolving dependencies\nwarning: no homepage\n       Wrote a.bb\n");
+        assert_eq!(
+            warnings,
+            vec![Warning {
+                message: "no homepage".to_string()
+            }]
+        );
+        assert_eq!(
+            other,
+            vec!["   Resolving dependencies", "       Wrote a.bb"]
+        );
+    }
+
+    #[test]
+    fn schema() {
+        let doc = Document {
+            schema_version: SCHEMA_VERSION,
+            recipes: vec![RecipeData::default()],
+            warnings: vec![],
+        };
+        let value = serde_json::to_value(&doc).unwrap();
+        assert_eq!(value["schema_version"], 1);
+        assert_eq!(value["recipes"][0]["project"]["tag"], false);
+    }
+}
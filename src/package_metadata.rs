@@ -0,0 +1,159 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Recipe customizations from the `bitbake` table in the metadata of a
+//! package or workspace.
+
+use anyhow::Context as _;
+use cargo::util::CargoResult;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The `[package.metadata.bitbake]` table, or `[workspace.metadata.bitbake]`
+/// with the defaults of the members. The command line options take
+/// precedence over both.
+#[derive(Debug, Default, Deserialize)]
+pub struct BitbakeMetadata {
+    /// SUMMARY instead of the first sentence of the description
+    pub summary: Option<String>,
+    /// HOMEPAGE instead of the package's homepage or repository
+    pub homepage: Option<String>,
+    /// SECTION instead of the one of the categories, see `--section`
+    pub section: Option<String>,
+    /// CVE_PRODUCT, see `--cve-product`
+    pub cve_product: Option<String>,
+    /// MAINTAINER instead of the authors, see `--maintainer`
+    pub maintainer: Option<String>,
+    /// DEPENDS, see `--depends`
+    #[serde(default)]
+    pub depends: Vec<String>,
+    /// RDEPENDS of the package, see `--rdepends`
+    #[serde(default)]
+    pub rdepends: Vec<String>,
+    /// classes inherited along with cargo
+    #[serde(default)]
+    pub inherit: Vec<String>,
+    /// lines appended to the recipe as they are
+    pub extra: Option<String>,
+    /// crates left out, see `--exclude-crate`
+    #[serde(default)]
+    pub skip_crates: Vec<String>,
+    /// keys this version doesn't know about
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl BitbakeMetadata {
+    /// the `bitbake` table of `metadata`, the `metadata` table of a package
+    /// or workspace, which `table` names in errors
+    pub fn from_metadata(metadata: Option<&toml::Table>, table: &str) -> CargoResult<Self> {
+        match metadata.and_then(|metadata| metadata.get("bitbake")) {
+            Some(bitbake) => bitbake
+                .clone()
+                .try_into()
+                .with_context(|| format!("Invalid '{}.bitbake' table", table)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// these values with those set in `over` taking their place, e.g. the
+    /// ones of a member over those of the workspace
+    pub fn layered(self, over: Self) -> Self {
+        let list = |list: Vec<String>, over: Vec<String>| {
+            if over.is_empty() {
+                list
+            } else {
+                over
+            }
+        };
+        let mut unknown = self.unknown;
+        unknown.extend(over.unknown);
+        Self {
+            summary: over.summary.or(self.summary),
+            homepage: over.homepage.or(self.homepage),
+            section: over.section.or(self.section),
+            cve_product: over.cve_product.or(self.cve_product),
+            maintainer: over.maintainer.or(self.maintainer),
+            depends: list(self.depends, over.depends),
+            rdepends: list(self.rdepends, over.rdepends),
+            inherit: list(self.inherit, over.inherit),
+            extra: over.extra.or(self.extra),
+            skip_crates: list(self.skip_crates, over.skip_crates),
+            unknown,
+        }
+    }
+
+    /// the keys of the table that are ignored
+    pub fn unknown_keys(&self) -> Vec<&str> {
+        self.unknown.keys().map(String::as_str).collect()
+    }
+
+    /// the `extra` lines, ending in a newline
+    pub fn extra_lines(&self) -> String {
+        match self.extra.as_deref().map(str::trim_end) {
+            Some(extra) if !extra.is_empty() => format!("{}\n", extra),
+            _ => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(contents: &str) -> CargoResult<BitbakeMetadata> {
+        let metadata: toml::Table = toml::from_str(contents).unwrap();
+        BitbakeMetadata::from_metadata(Some(&metadata), "package.metadata")
+    }
+
+    #[test]
+    fn table() {
+        let bitbake = parse(
+            "[bitbake]\nsummary = \"A tool\"\ndepends = [\"openssl\"]\n\
+             inherit = [\"systemd\"]\nextra = \"SYSTEMD_SERVICE:${PN} = \\\"app.service\\\"\\n\\n\"\n\
+             future_key = 1\n",
+        )
+        .unwrap();
+        assert_eq!(bitbake.summary.as_deref(), Some("A tool"));
+        assert_eq!(bitbake.depends, vec!["openssl"]);
+        assert_eq!(bitbake.inherit, vec!["systemd"]);
+        assert!(bitbake.rdepends.is_empty());
+        assert_eq!(
+            bitbake.extra_lines(),
+            "SYSTEMD_SERVICE:${PN} = \"app.service\"\n"
+        );
+        assert_eq!(bitbake.unknown_keys(), vec!["future_key"]);
+    }
+
+    #[test]
+    fn layered() {
+        let workspace = parse(
+            "[bitbake]\ndepends = [\"openssl\"]\ninherit = [\"systemd\"]\n\
+             maintainer = \"Team <team@example.com>\"\n",
+        )
+        .unwrap();
+        let member = parse("[bitbake]\ndepends = [\"zlib\"]\nsummary = \"A member\"\n").unwrap();
+        let bitbake = workspace.layered(member);
+        assert_eq!(bitbake.depends, vec!["zlib"]);
+        assert_eq!(bitbake.inherit, vec!["systemd"]);
+        assert_eq!(bitbake.summary.as_deref(), Some("A member"));
+        assert_eq!(
+            bitbake.maintainer.as_deref(),
+            Some("Team <team@example.com>")
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let e = parse("[bitbake]\ndepends = \"openssl\"\n").unwrap_err();
+        assert_eq!(e.to_string(), "Invalid 'package.metadata.bitbake' table");
+        assert!(parse("[other]\n").unwrap().unknown_keys().is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! PACKAGECONFIG knobs for the cargo features of a package, for
+//! `--packageconfig`.
+
+use cargo::core::{FeatureMap, FeatureValue};
+
+/// The PACKAGECONFIG scaffolding of a package
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct PackageConfig {
+    pub lines: String,
+    /// default features that can't be expressed as a knob
+    pub unmapped_defaults: Vec<String>,
+}
+
+/// one PACKAGECONFIG entry per feature in `features`, enabling the ones in
+/// `default` by default, overrides separated by `sep`. The features are
+/// passed to cargo without the default ones so that disabling a knob takes
+/// effect.
+pub fn package_config(features: &FeatureMap, sep: &str) -> PackageConfig {
+    let mut config = PackageConfig::default();
+    if features.keys().all(|feature| *feature == "default") {
+        return config;
+    }
+
+    let mut defaults = vec![];
+    for value in features.get("default").into_iter().flatten() {
+        match value {
+            FeatureValue::Feature(feature) => defaults.push(feature.as_str()),
+            other => config.unmapped_defaults.push(other.to_string()),
+        }
+    }
+
+    config.lines = format!("PACKAGECONFIG ??= \"{}\"\n", defaults.join(" "));
+    for feature in features.keys().filter(|feature| **feature != "default") {
+        // enable, disable, DEPENDS and RDEPENDS, the latter left for the
+        // maintainer to fill in
+        config.lines.push_str(&format!(
+            "PACKAGECONFIG[{0}] = \"--features {0},,\"\n",
+            feature
+        ));
+    }
+    config.lines.push_str(&format!(
+        "CARGO_BUILD_FLAGS{}append = \" --no-default-features ${{PACKAGECONFIG_CONFARGS}}\"\n",
+        sep
+    ));
+    config
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cargo::util::interning::InternedString;
+
+    fn features(table: &[(&str, &[&str])]) -> FeatureMap {
+        table
+            .iter()
+            .map(|(name, values)| {
+                (
+                    InternedString::new(name),
+                    values
+                        .iter()
+                        .map(|value| FeatureValue::new(InternedString::new(value)))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn knobs() {
+        let config = package_config(
+            &features(&[
+                ("default", &["tls", "serde/std"]),
+                ("tls", &[]),
+                ("full", &["tls", "metrics"]),
+                ("metrics", &[]),
+            ]),
+            ":",
+        );
+        assert_eq!(
+            config.lines,
+            "PACKAGECONFIG ??= \"tls\"\n\
+             PACKAGECONFIG[full] = \"--features full,,\"\n\
+             PACKAGECONFIG[metrics] = \"--features metrics,,\"\n\
+             PACKAGECONFIG[tls] = \"--features tls,,\"\n\
+             CARGO_BUILD_FLAGS:append = \" --no-default-features ${PACKAGECONFIG_CONFARGS}\"\n"
+        );
+        assert_eq!(config.unmapped_defaults, vec!["serde/std"]);
+    }
+
+    #[test]
+    fn no_features() {
+        assert_eq!(
+            package_config(&features(&[("default", &[])]), ":"),
+            PackageConfig::default()
+        );
+    }
+}
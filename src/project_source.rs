@@ -0,0 +1,305 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::cache::Cache;
+use anyhow::{anyhow, bail, Context as _};
+use cargo::sources::CRATES_IO_DOMAIN;
+use cargo::util::network::http::http_handle;
+use cargo::util::CargoResult;
+use cargo::GlobalContext;
+use sha2::{Digest, Sha256};
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How the sources of the project itself are fetched
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProjectSource {
+    /// a checkout of the project's git repository
+    Git,
+    /// the `.crate` published on crates.io
+    Crate,
+    /// the tarball GitHub generates for a commit
+    GithubArchive,
+}
+
+impl FromStr for ProjectSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "git" => Ok(Self::Git),
+            "crate" => Ok(Self::Crate),
+            "github-archive" => Ok(Self::GithubArchive),
+            _ => bail!(
+                "unknown project source '{}', expected git, crate or github-archive",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for ProjectSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{}",
+            match *self {
+                ProjectSource::Git => "git",
+                ProjectSource::Crate => "crate",
+                ProjectSource::GithubArchive => "github-archive",
+            }
+        )
+    }
+}
+
+/// A downloadable archive of the project's sources
+#[derive(Debug, Eq, PartialEq)]
+pub struct Archive {
+    /// the entry for SRC_URI
+    pub src_uri: String,
+    /// where the archive can be downloaded from to compute its checksum
+    pub download_url: String,
+    /// the key for the SRC_URI checksum flag, e.g. `name-1.0.sha256sum`
+    pub checksum_key: String,
+    /// the directory the archive unpacks into, relative to `${WORKDIR}`
+    pub unpack_dir: String,
+    /// the page listing the released versions, for upgrade checks
+    pub check_uri: String,
+    /// whether `check_uri` lists git tags rather than crate versions
+    pub check_tags: bool,
+}
+
+impl Archive {
+    /// the archive of the crate `name` at `version` as published on crates.io
+    pub fn crate_io(name: &str, version: &str) -> Self {
+        Self {
+            src_uri: format!("crate://{}/{}/{}", CRATES_IO_DOMAIN, name, version),
+            download_url: format!(
+                "https://{}/api/v1/crates/{}/{}/download",
+                CRATES_IO_DOMAIN, name, version
+            ),
+            checksum_key: format!("{}-{}.sha256sum", name, version),
+            unpack_dir: format!("{}-{}", name, version),
+            check_uri: format!("https://{}/api/v1/crates/{}", CRATES_IO_DOMAIN, name),
+            check_tags: false,
+        }
+    }
+
+    /// the archive GitHub generates for commit `rev` of the repository at
+    /// `repository`
+    pub fn github(repository: &str, name: &str, version: &str, rev: &str) -> CargoResult<Self> {
+        let path = repository
+            .trim()
+            .trim_end_matches('/')
+            .strip_prefix("https://github.com/")
+            .ok_or_else(|| {
+                anyhow!(
+                    "the github-archive project source needs a https://github.com repository, not '{}'",
+                    repository
+                )
+            })?;
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let repo = match path.split('/').collect::<Vec<_>>()[..] {
+            [_, repo] => repo,
+            _ => bail!(
+                "Unable to determine the GitHub repository of '{}'",
+                repository
+            ),
+        };
+        if rev.is_empty() {
+            bail!("the github-archive project source needs the git revision of the project");
+        }
+
+        let download_url = format!("https://github.com/{}/archive/{}.tar.gz", path, rev);
+        Ok(Self {
+            src_uri: format!(
+                "{};downloadfilename={}-{}.tar.gz",
+                download_url, name, version
+            ),
+            download_url,
+            checksum_key: "sha256sum".into(),
+            unpack_dir: format!("{}-{}", repo, rev),
+            check_uri: format!("https://github.com/{}/tags", path),
+            check_tags: true,
+        })
+    }
+
+    /// computes the sha256 of the archive, either from the copy at `local`,
+    /// the digest cached from a previous run or by downloading it
+    pub fn sha256(
+        &self,
+        config: &GlobalContext,
+        local: Option<&Path>,
+        offline: bool,
+    ) -> CargoResult<String> {
+        let mut cache = checksum_cache(config)?;
+
+        let sha256 = if let Some(local) = local {
+            let contents = fs::read(local)
+                .with_context(|| format!("Unable to read project archive '{}'", local.display()))?;
+            format!("{:x}", Sha256::digest(&contents))
+        } else if let Some(sha256) = cache.entries.get(&self.download_url) {
+            return Ok(sha256.clone());
+        } else if offline {
+            bail!(
+                "the sha256 of '{}' is unknown and cannot be downloaded while offline, \
+                 download it and pass it with --project-archive",
+                self.download_url
+            );
+        } else {
+            format!(
+                "{:x}",
+                Sha256::digest(download(config, &self.download_url)?)
+            )
+        };
+
+        cache
+            .entries
+            .insert(self.download_url.clone(), sha256.clone());
+        cache.save()?;
+        Ok(sha256)
+    }
+}
+
+/// fetches the contents of `url`
+fn download(config: &GlobalContext, url: &str) -> CargoResult<Vec<u8>> {
+    let mut handle = http_handle(config)?;
+    handle.get(true)?;
+    handle.follow_location(true)?;
+    handle.url(url)?;
+
+    let mut body = Vec::new();
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|data| {
+            body.extend_from_slice(data);
+            Ok(data.len())
+        })?;
+        transfer
+            .perform()
+            .with_context(|| format!("Unable to download '{}'", url))?;
+    }
+
+    let code = handle.response_code()?;
+    if code != 200 {
+        bail!("Unable to download '{}': HTTP status {}", url, code);
+    }
+    Ok(body)
+}
+
+/// digests of previously hashed project archives, keyed by download URL
+fn checksum_cache(config: &GlobalContext) -> CargoResult<Cache<String>> {
+    Cache::load(
+        config,
+        "project-checksums.toml",
+        "checksum cache",
+        "the project archive is hashed again",
+    )
+}
+
+/// default for the tags of releases, e.g. `v1.2.3` or `1.2.3`
+pub const DEFAULT_TAG_REGEX: &str = r"v?(?P<pver>\d+(\.\d+)+)";
+
+/// the versions in the crates.io API, leaving out pre-releases
+const CRATE_VERSION_REGEX: &str = r#""num":"(?P<pver>\d+(\.\d+)+)""#;
+
+/// where `devtool check-upgrade-status` looks for new versions of the
+/// project fetched as `archive`, or from its git repository if `None`,
+/// with `tag_regex` matching the tags of releases
+pub fn upstream_check(archive: Option<&Archive>, tag_regex: &str) -> String {
+    match archive {
+        None => format!("UPSTREAM_CHECK_GITTAGREGEX = \"{}\"\n", tag_regex),
+        Some(archive) if archive.check_tags => format!(
+            "UPSTREAM_CHECK_URI = \"{}\"\nUPSTREAM_CHECK_REGEX = \"releases/tag/{}\"\n",
+            archive.check_uri, tag_regex
+        ),
+        // single quotes for the double quotes of the JSON
+        Some(archive) => format!(
+            "UPSTREAM_CHECK_URI = \"{}\"\nUPSTREAM_CHECK_REGEX = '{}'\n",
+            archive.check_uri, CRATE_VERSION_REGEX
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crates_io() {
+        let archive = Archive::crate_io("foo", "1.2.3");
+        assert_eq!(archive.src_uri, "crate://crates.io/foo/1.2.3");
+        assert_eq!(
+            archive.download_url,
+            "https://crates.io/api/v1/crates/foo/1.2.3/download"
+        );
+        assert_eq!(archive.checksum_key, "foo-1.2.3.sha256sum");
+        assert_eq!(archive.unpack_dir, "foo-1.2.3");
+        assert_eq!(
+            upstream_check(Some(&archive), DEFAULT_TAG_REGEX),
+            "UPSTREAM_CHECK_URI = \"https://crates.io/api/v1/crates/foo\"\n\
+             UPSTREAM_CHECK_REGEX = '\"num\":\"(?P<pver>\\d+(\\.\\d+)+)\"'\n"
+        );
+    }
+
+    #[test]
+    fn github() {
+        let archive = Archive::github(
+            "https://github.com/cardoe/cargo-bitbake.git",
+            "cargo-bitbake",
+            "0.3.16",
+            "abc123",
+        )
+        .unwrap();
+        assert_eq!(
+            archive.src_uri,
+            "https://github.com/cardoe/cargo-bitbake/archive/abc123.tar.gz;downloadfilename=cargo-bitbake-0.3.16.tar.gz"
+        );
+        assert_eq!(archive.checksum_key, "sha256sum");
+        assert_eq!(archive.unpack_dir, "cargo-bitbake-abc123");
+        assert_eq!(
+            upstream_check(Some(&archive), "(?P<pver>\\d+)"),
+            "UPSTREAM_CHECK_URI = \"https://github.com/cardoe/cargo-bitbake/tags\"\n\
+             UPSTREAM_CHECK_REGEX = \"releases/tag/(?P<pver>\\d+)\"\n"
+        );
+    }
+
+    #[test]
+    fn github_not_github() {
+        assert!(Archive::github("https://gitlab.com/a/b", "b", "1.0.0", "abc").is_err());
+    }
+
+    #[test]
+    fn corrupt_cache() {
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir_all(home.path().join("bitbake")).unwrap();
+        fs::write(home.path().join("bitbake/project-checksums.toml"), "[[[").unwrap();
+        let config = GlobalContext::new(
+            cargo::core::Shell::new(),
+            home.path().to_path_buf(),
+            home.path().to_path_buf(),
+        );
+        let (cache, captured) = crate::capture_shell(&config, || checksum_cache(&config));
+        assert!(cache.unwrap().entries.is_empty());
+        assert!(captured.contains("corrupt checksum cache"), "{}", captured);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("git".parse::<ProjectSource>().unwrap(), ProjectSource::Git);
+        assert_eq!(
+            "github-archive".parse::<ProjectSource>().unwrap(),
+            ProjectSource::GithubArchive
+        );
+        assert!("svn".parse::<ProjectSource>().is_err());
+    }
+}
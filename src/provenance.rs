@@ -0,0 +1,284 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use md5::Context;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// first line of the provenance block in the recipe header
+pub const BLOCK_START: &str = "# cargo-bitbake provenance:";
+/// start of the first line of generated recipes, followed by the version
+const HEADER: &str = "# Auto-Generated by cargo-bitbake";
+const VERSION_KEY: &str = "version";
+const COMPAT_KEY: &str = "compat";
+const LOCKFILE_KEY: &str = "lockfile-md5";
+const OPTION_KEY: &str = "option";
+
+/// The inputs that went into generating a recipe, recorded in the
+/// recipe header so that it can be regenerated and verified later.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Provenance {
+    pub tool_version: String,
+    pub compat: String,
+    pub lockfile_md5: String,
+    pub options: Vec<String>,
+}
+
+impl Provenance {
+    /// renders the provenance block as recipe comment lines
+    pub fn render(&self) -> String {
+        let mut block = format!(
+            "{}\n#   {}: {}\n#   {}: {}\n#   {}: {}\n",
+            BLOCK_START,
+            VERSION_KEY,
+            self.tool_version,
+            COMPAT_KEY,
+            self.compat,
+            LOCKFILE_KEY,
+            self.lockfile_md5
+        );
+        for opt in &self.options {
+            block.push_str(&format!("#   {}: {}\n", OPTION_KEY, opt));
+        }
+        block
+    }
+
+    /// renders the provenance as the single comment line used by compact
+    /// recipes, with spaces in options escaped
+    pub fn render_compact(&self) -> String {
+        let mut line = format!(
+            "{} {}={} {}={} {}={}",
+            BLOCK_START,
+            VERSION_KEY,
+            self.tool_version,
+            COMPAT_KEY,
+            self.compat,
+            LOCKFILE_KEY,
+            self.lockfile_md5
+        );
+        for opt in &self.options {
+            line.push_str(&format!(" {}={}", OPTION_KEY, escape(opt)));
+        }
+        line
+    }
+
+    /// attempts to find a provenance block in the contents of a recipe,
+    /// returning `None` for recipes generated before it was recorded
+    pub fn parse(recipe: &str) -> Option<Self> {
+        if let Some(line) = recipe
+            .lines()
+            .find_map(|line| line.strip_prefix(BLOCK_START)?.strip_prefix(' '))
+        {
+            return Self::parse_compact(line);
+        }
+
+        let mut lines = recipe
+            .lines()
+            .skip_while(|line| line.trim_end() != BLOCK_START);
+        lines.next()?;
+
+        let mut provenance = Self::default();
+        for line in lines {
+            let entry = match line.strip_prefix("#   ") {
+                Some(entry) => entry,
+                None => break,
+            };
+            let (key, value) = match entry.split_once(": ") {
+                Some(kv) => kv,
+                None => break,
+            };
+            match key {
+                VERSION_KEY => provenance.tool_version = value.to_string(),
+                COMPAT_KEY => provenance.compat = value.to_string(),
+                LOCKFILE_KEY => provenance.lockfile_md5 = value.to_string(),
+                OPTION_KEY => provenance.options.push(value.to_string()),
+                _ => break,
+            }
+        }
+
+        Some(provenance)
+    }
+
+    fn parse_compact(line: &str) -> Option<Self> {
+        let mut provenance = Self::default();
+        for entry in line.split_whitespace() {
+            let (key, value) = entry.split_once('=')?;
+            match key {
+                VERSION_KEY => provenance.tool_version = value.to_string(),
+                COMPAT_KEY => provenance.compat = value.to_string(),
+                LOCKFILE_KEY => provenance.lockfile_md5 = value.to_string(),
+                OPTION_KEY => provenance.options.push(unescape(value)),
+                _ => return None,
+            }
+        }
+        Some(provenance)
+    }
+
+    /// describes which recorded inputs differ between `self` (recorded)
+    /// and `current`
+    pub fn differences(&self, current: &Self) -> Vec<String> {
+        let mut diffs = vec![];
+        if self.tool_version != current.tool_version {
+            diffs.push(format!(
+                "tool version: recorded {}, current {}",
+                self.tool_version, current.tool_version
+            ));
+        }
+        if self.compat != current.compat {
+            diffs.push(format!(
+                "compat preset: recorded {}, current {}",
+                self.compat, current.compat
+            ));
+        }
+        if self.lockfile_md5 != current.lockfile_md5 {
+            diffs.push(format!(
+                "Cargo.lock md5: recorded {}, current {}",
+                self.lockfile_md5, current.lockfile_md5
+            ));
+        }
+        if self.options != current.options {
+            diffs.push(format!(
+                "options: recorded [{}], current [{}]",
+                self.options.join(" "),
+                current.options.join(" ")
+            ));
+        }
+        diffs
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('%', "%25").replace(' ', "%20")
+}
+
+fn unescape(value: &str) -> String {
+    value.replace("%20", " ").replace("%25", "%")
+}
+
+/// `recipe` without the cargo-bitbake version it was generated with, in
+/// the header and the provenance
+pub fn without_tool_version(recipe: &str) -> String {
+    let version_line = format!("#   {}: ", VERSION_KEY);
+    let compact_version = format!(" {}=", VERSION_KEY);
+    let mut out = String::with_capacity(recipe.len());
+    for line in recipe.split_inclusive('\n') {
+        let (text, eol) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        if text.starts_with(HEADER) {
+            out.push_str(HEADER);
+        } else if text.starts_with(&version_line) {
+            out.push_str(version_line.trim_end());
+        } else if text.starts_with(BLOCK_START) && text.contains(&compact_version) {
+            out.push_str(
+                &text
+                    .split(' ')
+                    .filter(|part| !part.starts_with(&compact_version[1..]))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+        } else {
+            out.push_str(text);
+        }
+        out.push_str(eol);
+    }
+    out
+}
+
+/// For a given lockfile at `path`, generate the MD5 sum
+pub fn lockfile_md5<P: AsRef<Path>>(path: P) -> Result<String, io::Error> {
+    let mut file = File::open(path)?;
+    let mut context = Context::new();
+
+    io::copy(&mut file, &mut context)?;
+    Ok(format!("{:x}", context.compute()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> Provenance {
+        Provenance {
+            tool_version: "0.3.16".into(),
+            compat: "modern".into(),
+            lockfile_md5: "0123456789abcdef0123456789abcdef".into(),
+            options: vec!["-R".into(), "--legacy-overrides".into()],
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let recipe = format!(
+            "# Auto-Generated by cargo-bitbake 0.3.16\n#\n{}#\ninherit cargo\n",
+            sample().render()
+        );
+        assert_eq!(Provenance::parse(&recipe), Some(sample()));
+    }
+
+    #[test]
+    fn roundtrip_no_options() {
+        let mut prov = sample();
+        prov.options.clear();
+        assert_eq!(Provenance::parse(&prov.render()), Some(prov));
+    }
+
+    #[test]
+    fn strip_tool_version() {
+        let recipe = format!(
+            "{} 0.3.16\n#\n{}\n#   version: 0.3.16\n#   option: --version\n",
+            HEADER, BLOCK_START
+        );
+        assert_eq!(
+            without_tool_version(&recipe),
+            format!(
+                "{}\n#\n{}\n#   version:\n#   option: --version\n",
+                HEADER, BLOCK_START
+            )
+        );
+        assert_eq!(
+            without_tool_version(&format!("{} version=0.3.16 compat=modern", BLOCK_START)),
+            format!("{} compat=modern", BLOCK_START)
+        );
+    }
+
+    #[test]
+    fn roundtrip_compact() {
+        let mut prov = sample();
+        prov.options.push("--config-file".into());
+        prov.options.push("my dir/100%.toml".into());
+        let line = prov.render_compact();
+        assert!(!line.contains('\n'));
+        let recipe = format!("{}\ninherit cargo\n", line);
+        assert_eq!(Provenance::parse(&recipe), Some(prov));
+    }
+
+    #[test]
+    fn missing_block() {
+        let recipe = "# Auto-Generated by cargo-bitbake 0.3.15\n#\ninherit cargo\n";
+        assert_eq!(Provenance::parse(recipe), None);
+    }
+
+    #[test]
+    fn differences() {
+        let recorded = sample();
+        let mut current = sample();
+        assert!(recorded.differences(&current).is_empty());
+
+        current.lockfile_md5 = "ffffffffffffffffffffffffffffffff".into();
+        current.tool_version = "0.3.17".into();
+        let diffs = recorded.differences(&current);
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs[0].starts_with("tool version"));
+        assert!(diffs[1].starts_with("Cargo.lock md5"));
+    }
+}
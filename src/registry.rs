@@ -0,0 +1,236 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Where the crates of registries other than crates.io are fetched from.
+
+use crate::src_uri;
+use cargo::core::{Dependency, SourceId};
+use cargo::sources::source::{QueryKind, Source};
+use cargo::sources::{RegistrySource, SourceConfigMap, CRATES_IO_DOMAIN};
+use cargo::util::cache_lock::CacheLockMode;
+use cargo::util::CargoResult;
+use cargo::GlobalContext;
+use std::collections::HashSet;
+use std::task::Poll;
+use url::Url;
+
+/// A registry other than crates.io the dependencies come from
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Registry {
+    /// the host given to the crate:// fetcher, with the path of the
+    /// downloads if there is one
+    pub host: String,
+    /// the download URL template of the registry's `config.json`, if known
+    pub dl: Option<String>,
+}
+
+impl Registry {
+    /// the registry with the index `index` and the download URL template
+    /// `dl`, with crates fetched from `host` if given, otherwise from where
+    /// the downloads are or else the host of the index
+    pub fn new(index: &str, dl: Option<&str>, host: Option<&str>) -> Self {
+        let host = host
+            .map(|host| location(host).unwrap_or_else(|| host.trim_matches('/').to_string()))
+            .or_else(|| {
+                dl.and_then(|dl| {
+                    if src_uri::has_markers(dl) {
+                        authority(dl)
+                    } else {
+                        location(dl)
+                    }
+                })
+            })
+            .or_else(|| authority(index))
+            .unwrap_or_else(|| index.to_string());
+        Self {
+            host,
+            dl: dl.map(str::to_string),
+        }
+    }
+
+    /// whether the crate:// fetcher, which asks `https://<host>`, finds the
+    /// crates where the registry serves them
+    pub fn crate_fetcher_works(&self) -> bool {
+        match self.dl {
+            Some(ref dl) => {
+                src_uri::download_url(dl, "name", "1.0.0", None)
+                    == fetcher_url(&self.host, "name", "1.0.0")
+            }
+            None => true,
+        }
+    }
+
+    /// whether the registry serves its crates over plain http, which the
+    /// crate:// fetcher never uses
+    pub fn serves_http(&self) -> bool {
+        self.dl
+            .as_deref()
+            .map_or(false, |dl| dl.starts_with("http://"))
+    }
+
+    /// the download URL of a crate, where the crate:// fetcher would look
+    /// for it without the registry's template
+    pub fn download_url(&self, name: &str, version: &str, checksum: Option<&str>) -> String {
+        match self.dl {
+            Some(ref dl) => src_uri::download_url(dl, name, version, checksum),
+            None => fetcher_url(&self.host, name, version),
+        }
+    }
+}
+
+/// the URL the crate:// fetcher downloads the crate `name` at `version`
+/// from `host` from, the API of crates.io or else right below the host
+pub fn fetcher_url(host: &str, name: &str, version: &str) -> String {
+    if host == CRATES_IO_DOMAIN {
+        format!(
+            "https://{}/api/v1/crates/{}/{}/download",
+            host, name, version
+        )
+    } else {
+        format!("https://{}/{}/{}/download", host, name, version)
+    }
+}
+
+/// the URL of `src_id`, for sparse registries without the `sparse+` cargo
+/// tells them from git indexes by
+pub fn index_url(src_id: SourceId) -> String {
+    let url = src_id.url().as_str();
+    url.strip_prefix("sparse+").unwrap_or(url).to_string()
+}
+
+/// `host[:port]/path` of `url`, without the trailing slash
+fn location(url: &str) -> Option<String> {
+    let authority = authority(url)?;
+    let path = Url::parse(url).ok()?.path().trim_matches('/').to_string();
+    Some(if path.is_empty() {
+        authority
+    } else {
+        format!("{}/{}", authority, path)
+    })
+}
+
+/// `host[:port]` of `url`
+fn authority(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// the download URL template of the remote registry `src_id` from the
+/// `config.json` of its index, `None` if it can't be read
+pub fn download_template(config: &GlobalContext, src_id: SourceId) -> Option<String> {
+    let _lock = config
+        .acquire_package_cache_lock(CacheLockMode::DownloadExclusive)
+        .ok()?;
+    let mut source = RegistrySource::remote(src_id, &HashSet::new(), config).ok()?;
+    loop {
+        match source.config() {
+            Poll::Ready(registry_config) => return registry_config.ok()?.map(|c| c.dl),
+            Poll::Pending => source.block_until_ready().ok()?,
+        }
+    }
+}
+
+/// whether the index of crates.io, or of the source replacing it, has a
+/// crate called `name`, `false` if it can't be asked
+pub fn known_on_crates_io(config: &GlobalContext, name: &str) -> bool {
+    let query = || -> CargoResult<bool> {
+        let _lock = config.acquire_package_cache_lock(CacheLockMode::DownloadExclusive)?;
+        let src_id = SourceId::crates_io(config)?;
+        let mut source = SourceConfigMap::new(config)?.load(src_id, &HashSet::new())?;
+        let dep = Dependency::parse(name, None, src_id)?;
+        let mut known = false;
+        loop {
+            match source.query(&dep, QueryKind::Exact, &mut |_| known = true) {
+                Poll::Ready(result) => return result.map(|()| known),
+                Poll::Pending => source.block_until_ready()?,
+            }
+        }
+    };
+    query().unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hosts() {
+        let index = "https://git.example.com/crates-index";
+        assert_eq!(Registry::new(index, None, None).host, "git.example.com");
+        // the path of the downloads is part of the fetcher's host
+        let registry = Registry::new(
+            index,
+            Some("https://crates.example.com:8443/api/v1/crates"),
+            None,
+        );
+        assert_eq!(registry.host, "crates.example.com:8443/api/v1/crates");
+        assert!(registry.crate_fetcher_works());
+        assert_eq!(
+            fetcher_url(&registry.host, "corp-util", "1.2.0"),
+            "https://crates.example.com:8443/api/v1/crates/corp-util/1.2.0/download"
+        );
+        let registry = Registry::new(index, Some("https://dl.example.com/"), None);
+        assert_eq!(registry.host, "dl.example.com");
+        assert!(registry.crate_fetcher_works());
+        // only crates.io gets its API path added
+        assert_eq!(
+            fetcher_url("crates.io", "itoa", "1.0.0"),
+            "https://crates.io/api/v1/crates/itoa/1.0.0/download"
+        );
+        // which is always fetched with https
+        let registry = Registry::new(index, Some("http://crates.example.com/api/v1/crates"), None);
+        assert!(registry.serves_http());
+        assert!(!registry.crate_fetcher_works());
+        assert_eq!(
+            Registry::new(index, None, Some("https://mirror.example.com/")).host,
+            "mirror.example.com"
+        );
+        assert_eq!(
+            Registry::new(index, None, Some("mirror.example.com")).host,
+            "mirror.example.com"
+        );
+    }
+
+    #[test]
+    fn sparse_indexes() {
+        let sparse = SourceId::from_url("sparse+https://crates.corp.example.com/index/").unwrap();
+        assert_eq!(index_url(sparse), "https://crates.corp.example.com/index/");
+        assert_eq!(
+            Registry::new(&index_url(sparse), None, None).host,
+            "crates.corp.example.com"
+        );
+        let git = SourceId::from_url("registry+https://git.corp.example.com/index").unwrap();
+        assert_eq!(index_url(git), "https://git.corp.example.com/index");
+    }
+
+    #[test]
+    fn download_layouts() {
+        let registry = Registry::new(
+            "https://git.example.com/crates-index",
+            Some("https://dl.example.com/{crate}/{crate}-{version}.crate"),
+            None,
+        );
+        assert_eq!(registry.host, "dl.example.com");
+        assert!(!registry.crate_fetcher_works());
+        assert_eq!(
+            registry.download_url("itoa", "1.0.11", None),
+            "https://dl.example.com/itoa/itoa-1.0.11.crate"
+        );
+        assert_eq!(
+            Registry::new("https://git.example.com/crates-index", None, None)
+                .download_url("itoa", "1.0.11", None),
+            "https://git.example.com/itoa/1.0.11/download"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! SECTION from the crates.io categories and keywords of a package.
+
+/// crates.io categories, without their subcategories, with the section of
+/// the packages in them. Categories saying little about what a package is,
+/// e.g. `asynchronous`, are left out.
+const CATEGORY_SECTIONS: &[(&str, &str)] = &[
+    ("command-line-utilities", "console/utils"),
+    ("development-tools", "devel"),
+    ("embedded", "base"),
+    ("emulators", "console/utils"),
+    ("filesystem", "base"),
+    ("games", "games"),
+    ("hardware-support", "base"),
+    ("multimedia", "multimedia"),
+    ("network-programming", "net"),
+    ("os", "base"),
+    ("science", "science"),
+    ("text-editors", "console/editors"),
+    ("web-programming", "net"),
+];
+
+/// keywords that are about as clear as a category
+const KEYWORD_SECTIONS: &[(&str, &str)] = &[
+    ("cli", "console/utils"),
+    ("command-line", "console/utils"),
+    ("embedded", "base"),
+    ("network", "net"),
+    ("networking", "net"),
+];
+
+/// the section of a package with `categories` and `keywords`, from the
+/// first category with one or else the first keyword, `None` if nothing
+/// says
+pub fn section(categories: &[&str], keywords: &[&str]) -> Option<&'static str> {
+    let lookup = |table: &[(&str, &'static str)], name: &str| {
+        // `network-programming::http-client` is network programming, too
+        let name = name.split("::").next().unwrap_or(name);
+        table
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, section)| *section)
+    };
+    categories
+        .iter()
+        .find_map(|category| lookup(CATEGORY_SECTIONS, category))
+        .or_else(|| {
+            keywords
+                .iter()
+                .find_map(|keyword| lookup(KEYWORD_SECTIONS, keyword))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        assert_eq!(
+            section(&["asynchronous", "command-line-utilities"], &[]),
+            Some("console/utils")
+        );
+        assert_eq!(
+            section(&["web-programming::http-server"], &["cli"]),
+            Some("net")
+        );
+        assert_eq!(
+            section(&["parser-implementations"], &["CLI"]),
+            Some("console/utils")
+        );
+        assert_eq!(section(&["encoding"], &["json", "serde"]), None);
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! The names oe-core's `meta/files/common-licenses` uses for SPDX license
+//! identifiers.
+
+/// SPDX identifiers with the name of their license in `common-licenses`,
+/// sorted by identifier. Most are the same, deprecated identifiers map to
+/// their current form.
+const COMMON_LICENSES: &[(&str, &str)] = &[
+    ("0BSD", "0BSD"),
+    ("AFL-2.1", "AFL-2.1"),
+    ("AFL-3.0", "AFL-3.0"),
+    ("AGPL-3.0", "AGPL-3.0-only"),
+    ("AGPL-3.0+", "AGPL-3.0-or-later"),
+    ("AGPL-3.0-only", "AGPL-3.0-only"),
+    ("AGPL-3.0-or-later", "AGPL-3.0-or-later"),
+    ("Apache-1.1", "Apache-1.1"),
+    ("Apache-2.0", "Apache-2.0"),
+    ("Artistic-1.0", "Artistic-1.0"),
+    ("Artistic-2.0", "Artistic-2.0"),
+    ("BSD-1-Clause", "BSD-1-Clause"),
+    ("BSD-2-Clause", "BSD-2-Clause"),
+    ("BSD-2-Clause-FreeBSD", "BSD-2-Clause"),
+    ("BSD-2-Clause-NetBSD", "BSD-2-Clause"),
+    ("BSD-2-Clause-Patent", "BSD-2-Clause-Patent"),
+    ("BSD-3-Clause", "BSD-3-Clause"),
+    ("BSD-3-Clause-Clear", "BSD-3-Clause-Clear"),
+    ("BSD-4-Clause", "BSD-4-Clause"),
+    ("BSL-1.0", "BSL-1.0"),
+    ("CC-BY-3.0", "CC-BY-3.0"),
+    ("CC-BY-4.0", "CC-BY-4.0"),
+    ("CC-BY-SA-4.0", "CC-BY-SA-4.0"),
+    ("CC0-1.0", "CC0-1.0"),
+    ("EPL-1.0", "EPL-1.0"),
+    ("EPL-2.0", "EPL-2.0"),
+    ("GPL-2.0", "GPL-2.0-only"),
+    ("GPL-2.0+", "GPL-2.0-or-later"),
+    ("GPL-2.0-only", "GPL-2.0-only"),
+    ("GPL-2.0-or-later", "GPL-2.0-or-later"),
+    ("GPL-3.0", "GPL-3.0-only"),
+    ("GPL-3.0+", "GPL-3.0-or-later"),
+    ("GPL-3.0-only", "GPL-3.0-only"),
+    ("GPL-3.0-or-later", "GPL-3.0-or-later"),
+    ("ISC", "ISC"),
+    ("LGPL-2.0", "LGPL-2.0-only"),
+    ("LGPL-2.0+", "LGPL-2.0-or-later"),
+    ("LGPL-2.0-only", "LGPL-2.0-only"),
+    ("LGPL-2.0-or-later", "LGPL-2.0-or-later"),
+    ("LGPL-2.1", "LGPL-2.1-only"),
+    ("LGPL-2.1+", "LGPL-2.1-or-later"),
+    ("LGPL-2.1-only", "LGPL-2.1-only"),
+    ("LGPL-2.1-or-later", "LGPL-2.1-or-later"),
+    ("LGPL-3.0", "LGPL-3.0-only"),
+    ("LGPL-3.0+", "LGPL-3.0-or-later"),
+    ("LGPL-3.0-only", "LGPL-3.0-only"),
+    ("LGPL-3.0-or-later", "LGPL-3.0-or-later"),
+    ("MIT", "MIT"),
+    ("MIT-0", "MIT-0"),
+    ("MPL-1.1", "MPL-1.1"),
+    ("MPL-2.0", "MPL-2.0"),
+    ("OpenSSL", "OpenSSL"),
+    ("PSF-2.0", "PSF-2.0"),
+    ("Unicode-3.0", "Unicode-3.0"),
+    ("Unicode-DFS-2016", "Unicode-DFS-2016"),
+    ("Unicode-TOU", "Unicode-TOU"),
+    ("Unlicense", "Unlicense"),
+    ("WTFPL", "WTFPL"),
+    ("Zlib", "Zlib"),
+    ("zlib-acknowledgement", "zlib-acknowledgement"),
+];
+
+/// the name of the license with the SPDX identifier `id` in
+/// `common-licenses`, `None` if it isn't there
+pub fn common_license(id: &str) -> Option<&'static str> {
+    COMMON_LICENSES
+        .binary_search_by(|(spdx, _)| spdx.cmp(&id))
+        .ok()
+        .map(|idx| COMMON_LICENSES[idx].1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sorted() {
+        assert!(COMMON_LICENSES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(common_license("MIT"), Some("MIT"));
+        assert_eq!(common_license("GPL-2.0+"), Some("GPL-2.0-or-later"));
+        assert_eq!(common_license("BSD-2-Clause-FreeBSD"), Some("BSD-2-Clause"));
+        assert_eq!(common_license("Unicode-DFS-2016"), Some("Unicode-DFS-2016"));
+        assert_eq!(common_license("mit"), None);
+        assert_eq!(common_license("LicenseRef-Proprietary"), None);
+    }
+}
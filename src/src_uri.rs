@@ -0,0 +1,353 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+
+/// where crates.io serves the crate archives from
+pub const CRATES_IO_DOWNLOAD: &str = "https://crates.io/api/v1/crates/";
+
+/// A single SRC_URI entry split into the URL and its `;key=value`
+/// parameters, which keep their order
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SrcUri {
+    pub url: String,
+    params: Vec<(String, String)>,
+}
+
+impl SrcUri {
+    pub fn parse(entry: &str) -> Self {
+        let mut parts = entry.split(';');
+        let url = parts.next().unwrap_or_default().to_string();
+        let params = parts
+            .filter(|part| !part.is_empty())
+            .map(|part| match part.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (part.to_string(), String::new()),
+            })
+            .collect();
+        Self { url, params }
+    }
+
+    /// the value of the parameter `key`
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// sets the parameter `key`, keeping its position if already present
+    pub fn set_param(&mut self, key: &str, value: &str) {
+        match self.params.iter_mut().find(|(k, _)| k == key) {
+            Some(param) => param.1 = value.to_string(),
+            None => self.params.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /// removes the parameter `key` if present
+    pub fn remove_param(&mut self, key: &str) {
+        self.params.retain(|(k, _)| k != key);
+    }
+}
+
+impl Display for SrcUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.url)?;
+        for (key, value) in &self.params {
+            write!(f, ";{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// where the entries of downloaded crates are put, to be unpacked next to
+/// the crates the crate:// fetcher would have fetched
+pub const DOWNLOAD_SUBDIR: &str = "cargo_home/bitbake";
+
+/// the https entry of a crates.io crate for releases without the crate://
+/// fetcher, unpacked next to the crates it would have fetched
+pub fn crates_io_download(name: &str, version: &str) -> SrcUri {
+    registry_download(
+        &format!("{}{}/{}/download", CRATES_IO_DOWNLOAD, name, version),
+        name,
+        version,
+    )
+}
+
+/// the entry of the crate `name` downloaded from `url`, unpacked like those
+/// of crates.io
+pub fn registry_download(url: &str, name: &str, version: &str) -> SrcUri {
+    SrcUri::parse(&format!(
+        "{url};downloadfilename={name}-{version}.crate;\
+         name={name}-{version}.crate;subdir={subdir}",
+        url = url,
+        name = name,
+        version = version,
+        subdir = DOWNLOAD_SUBDIR
+    ))
+}
+
+/// whether the `dl` template of a registry's `config.json` has markers for
+/// the crate, otherwise its path is appended
+pub fn has_markers(dl: &str) -> bool {
+    const MARKERS: &[&str] = &["{crate}", "{version}", "{prefix}", "{lowerprefix}"];
+    MARKERS.iter().any(|marker| dl.contains(marker))
+}
+
+/// the download URL of a crate from the `dl` template of its registry's
+/// `config.json`, which without any markers is the start of a crates.io
+/// like URL
+pub fn download_url(dl: &str, name: &str, version: &str, checksum: Option<&str>) -> String {
+    if !has_markers(dl) {
+        return format!("{}/{}/{}/download", dl.trim_end_matches('/'), name, version);
+    }
+    let prefix = match name.len() {
+        1 => "1".to_string(),
+        2 => "2".to_string(),
+        3 => format!("3/{}", &name[..1]),
+        _ => format!("{}/{}", &name[..2], &name[2..4]),
+    };
+    dl.replace("{crate}", name)
+        .replace("{version}", version)
+        .replace("{prefix}", &prefix)
+        .replace("{lowerprefix}", &prefix.to_lowercase())
+        .replace("{sha256-checksum}", checksum.unwrap_or_default())
+}
+
+/// `name` with the characters bitbake can't take in variable names,
+/// override suffixes or `;name=` lists replaced by `_`
+pub fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The names of the crates as used in `SRCREV_<name>`, `SRCREV_FORMAT`,
+/// `;name=` and the `SRC_URI[<name>-<version>.sha256sum]` flags. Names
+/// that sanitize to the same one are told apart by a `_2`, `_3`, ...
+/// suffix in the order of the original names, so the same crates always
+/// get the same names.
+#[derive(Debug, Default)]
+pub struct FetcherNames {
+    names: BTreeMap<String, String>,
+}
+
+impl FetcherNames {
+    pub fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let originals = names.into_iter().collect::<BTreeSet<_>>();
+        // names that need no change keep it even if sorted after another
+        let mut taken = originals
+            .iter()
+            .filter(|name| sanitize_name(name) == **name)
+            .map(|name| name.to_string())
+            .collect::<BTreeSet<_>>();
+        let mut names = BTreeMap::new();
+        for original in originals {
+            let sanitized = sanitize_name(original);
+            let name = if sanitized == original {
+                sanitized
+            } else {
+                let mut name = sanitized.clone();
+                let mut n = 2;
+                while !taken.insert(name.clone()) {
+                    name = format!("{}_{}", sanitized, n);
+                    n += 1;
+                }
+                name
+            };
+            names.insert(original.to_string(), name);
+        }
+        Self { names }
+    }
+
+    /// the name to use for the crate `name`
+    pub fn get(&self, name: &str) -> String {
+        self.names
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| sanitize_name(name))
+    }
+
+    /// the names that were changed, with what they were changed to
+    pub fn renamed(&self) -> Vec<(&str, &str)> {
+        self.names
+            .iter()
+            .filter(|(original, name)| original != name)
+            .map(|(original, name)| (original.as_str(), name.as_str()))
+            .collect()
+    }
+}
+
+/// Manual adjustments to a generated SRC_URI entry, keyed in the config
+/// file by crate name or repository URL
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SrcUriOverride {
+    /// replaces the URL entirely, keeping the parameters
+    pub url: Option<String>,
+    pub protocol: Option<String>,
+    pub branch: Option<String>,
+    pub nobranch: Option<bool>,
+    pub lfs: Option<bool>,
+    pub destsuffix: Option<String>,
+}
+
+impl SrcUriOverride {
+    /// applies this override on top of the automatically generated `uri`
+    pub fn apply(&self, uri: &mut SrcUri) {
+        if let Some(ref url) = self.url {
+            uri.url = url.clone();
+        }
+        if let Some(ref protocol) = self.protocol {
+            uri.set_param("protocol", protocol);
+        }
+        if let Some(nobranch) = self.nobranch {
+            if nobranch {
+                uri.remove_param("branch");
+                uri.set_param("nobranch", "1");
+            } else {
+                uri.remove_param("nobranch");
+            }
+        }
+        if let Some(ref branch) = self.branch {
+            uri.remove_param("nobranch");
+            uri.set_param("branch", branch);
+        }
+        if let Some(lfs) = self.lfs {
+            if lfs {
+                uri.set_param("lfs", "1");
+            } else {
+                uri.remove_param("lfs");
+            }
+        }
+        if let Some(ref destsuffix) = self.destsuffix {
+            uri.set_param("destsuffix", destsuffix);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GIT_URI: &str =
+        "git://github.com/rust-lang/cargo.git;protocol=https;nobranch=1;name=cargo;destsuffix=cargo";
+
+    #[test]
+    fn roundtrip() {
+        assert_eq!(SrcUri::parse(GIT_URI).to_string(), GIT_URI);
+        assert_eq!(
+            SrcUri::parse("crate://crates.io/foo/1.0.0").to_string(),
+            "crate://crates.io/foo/1.0.0"
+        );
+    }
+
+    #[test]
+    fn params() {
+        let uri = SrcUri::parse(GIT_URI);
+        assert_eq!(uri.url, "git://github.com/rust-lang/cargo.git");
+        assert_eq!(uri.param("protocol"), Some("https"));
+        assert_eq!(uri.param("branch"), None);
+    }
+
+    #[test]
+    fn crates_io_https() {
+        let uri = crates_io_download("itoa", "1.0.11");
+        assert_eq!(
+            uri.to_string(),
+            "https://crates.io/api/v1/crates/itoa/1.0.11/download;\
+             downloadfilename=itoa-1.0.11.crate;name=itoa-1.0.11.crate;subdir=cargo_home/bitbake"
+        );
+    }
+
+    #[test]
+    fn registry_downloads() {
+        assert_eq!(
+            download_url(
+                "https://crates.example.com/api/v1/crates",
+                "itoa",
+                "1.0.11",
+                None
+            ),
+            "https://crates.example.com/api/v1/crates/itoa/1.0.11/download"
+        );
+        let dl = "https://dl.example.com/{prefix}/{lowerprefix}/{crate}-{version}.crate?{sha256-checksum}";
+        assert_eq!(
+            download_url(dl, "Inflector", "0.11.4", Some("fe43")),
+            "https://dl.example.com/In/fl/in/fl/Inflector-0.11.4.crate?fe43"
+        );
+        assert_eq!(
+            download_url(dl, "syn", "2.0.0", None),
+            "https://dl.example.com/3/s/3/s/syn-2.0.0.crate?"
+        );
+        assert_eq!(
+            registry_download("https://dl.example.com/cc-1.0.crate", "cc", "1.0").to_string(),
+            "https://dl.example.com/cc-1.0.crate;downloadfilename=cc-1.0.crate;\
+             name=cc-1.0.crate;subdir=cargo_home/bitbake"
+        );
+    }
+
+    #[test]
+    fn fetcher_names() {
+        let names = FetcherNames::new(["foo_bar-sys", "foo.bar", "foo_bar", "foo:bar"]);
+        assert_eq!(names.get("foo_bar-sys"), "foo_bar-sys");
+        assert_eq!(names.get("foo_bar"), "foo_bar");
+        assert_eq!(names.get("foo.bar"), "foo_bar_2");
+        assert_eq!(names.get("foo:bar"), "foo_bar_3");
+        assert_eq!(
+            names.renamed(),
+            vec![("foo.bar", "foo_bar_2"), ("foo:bar", "foo_bar_3")]
+        );
+
+        let names = FetcherNames::new(["foo.bar", "foo_bar-sys"]);
+        assert_eq!(names.get("foo.bar"), "foo_bar");
+        assert_eq!(names.get("foo_bar-sys"), "foo_bar-sys");
+        assert_eq!(names.renamed(), vec![("foo.bar", "foo_bar")]);
+    }
+
+    #[test]
+    fn override_branch() {
+        let mut uri = SrcUri::parse(GIT_URI);
+        SrcUriOverride {
+            protocol: Some("ssh".into()),
+            branch: Some("release/1.x".into()),
+            lfs: Some(true),
+            ..Default::default()
+        }
+        .apply(&mut uri);
+        assert_eq!(
+            uri.to_string(),
+            "git://github.com/rust-lang/cargo.git;protocol=ssh;name=cargo;destsuffix=cargo;branch=release/1.x;lfs=1"
+        );
+    }
+
+    #[test]
+    fn override_url() {
+        let mut uri = SrcUri::parse(GIT_URI);
+        SrcUriOverride {
+            url: Some("git://mirror.example.com/cargo.git".into()),
+            destsuffix: Some("cargo-src".into()),
+            ..Default::default()
+        }
+        .apply(&mut uri);
+        assert_eq!(
+            uri.to_string(),
+            "git://mirror.example.com/cargo.git;protocol=https;nobranch=1;name=cargo;destsuffix=cargo-src"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! DEPENDS on the native libraries that `-sys` crates link against.
+
+use anyhow::Context as _;
+use cargo::util::CargoResult;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// the recipes providing the libraries of well-known `-sys` crates. An
+/// empty recipe marks crates that don't need one, e.g. because they only
+/// bind platform APIs or bundle their library.
+const BUILTIN: &[(&str, &str)] = &[
+    ("advapi32-sys", ""),
+    ("alsa-sys", "alsa-lib"),
+    ("bzip2-sys", "bzip2"),
+    ("cairo-sys-rs", "cairo"),
+    ("core-foundation-sys", ""),
+    ("curl-sys", "curl"),
+    ("dbus-sys", "dbus"),
+    ("expat-sys", "expat"),
+    ("freetype-sys", "freetype"),
+    ("gdk-pixbuf-sys", "gdk-pixbuf"),
+    ("gio-sys", "glib-2.0"),
+    ("glib-sys", "glib-2.0"),
+    ("gobject-sys", "glib-2.0"),
+    ("gstreamer-sys", "gstreamer1.0"),
+    ("gtk-sys", "gtk+3"),
+    ("js-sys", ""),
+    ("kernel32-sys", ""),
+    ("libdbus-sys", "dbus"),
+    ("libffi-sys", "libffi"),
+    ("libgit2-sys", "libgit2"),
+    ("libpulse-sys", "pulseaudio"),
+    ("libseccomp-sys", "libseccomp"),
+    ("libsqlite3-sys", "sqlite3"),
+    ("libssh2-sys", "libssh2"),
+    ("libsystemd-sys", "systemd"),
+    ("libudev-sys", "udev"),
+    ("libusb1-sys", "libusb1"),
+    ("libz-sys", "zlib"),
+    ("linux-raw-sys", ""),
+    ("lzma-sys", "xz"),
+    ("miniz-sys", ""),
+    ("openssl-sys", "openssl"),
+    ("pango-sys", "pango"),
+    ("pcre2-sys", "libpcre2"),
+    ("pq-sys", "postgresql"),
+    ("sdl2-sys", "libsdl2"),
+    ("security-framework-sys", ""),
+    ("wayland-sys", "wayland"),
+    ("web-sys", ""),
+    ("windows-sys", ""),
+    ("ws2_32-sys", ""),
+    ("zstd-sys", "zstd"),
+];
+
+/// The recipes the libraries of `-sys` crates are provided by
+#[derive(Debug)]
+pub struct SysDepsMap {
+    recipes: BTreeMap<String, String>,
+}
+
+impl Default for SysDepsMap {
+    fn default() -> Self {
+        Self {
+            recipes: BUILTIN
+                .iter()
+                .map(|(name, recipe)| (name.to_string(), recipe.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl SysDepsMap {
+    /// the built-in table extended with the `crate = "recipe"` pairs of the
+    /// TOML file at `path`
+    pub fn load(path: &Path) -> CargoResult<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read -sys crate map '{}'", path.display()))?;
+        let mut map = Self::default();
+        map.extend(&contents)
+            .with_context(|| format!("Unable to parse -sys crate map '{}'", path.display()))?;
+        Ok(map)
+    }
+
+    fn extend(&mut self, contents: &str) -> CargoResult<()> {
+        let recipes: BTreeMap<String, String> = toml::from_str(contents)?;
+        self.recipes.extend(recipes);
+        Ok(())
+    }
+
+    /// the recipes `krate` needs, `None` if it is unknown
+    pub fn get(&self, krate: &str) -> Option<Vec<&str>> {
+        self.recipes
+            .get(krate)
+            .map(|recipes| recipes.split_whitespace().collect())
+    }
+}
+
+/// whether `name` follows the naming convention of crates binding a
+/// native library
+fn is_sys_crate(name: &str) -> bool {
+    name.ends_with("-sys") || name.ends_with("_sys") || name.contains("-sys-")
+}
+
+/// The DEPENDS the `-sys` crates of a dependency graph call for
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct SysDepends {
+    /// each recipe with the crates needing it
+    pub recipes: BTreeMap<String, Vec<String>>,
+    /// `-sys` crates that aren't in the map
+    pub unknown: Vec<String>,
+}
+
+impl SysDepends {
+    /// looks up the `-sys` crates among `crates` in `map`
+    pub fn new<'a>(map: &SysDepsMap, crates: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut deps = Self::default();
+        for krate in crates.into_iter().filter(|krate| is_sys_crate(krate)) {
+            match map.get(krate) {
+                Some(recipes) => {
+                    for recipe in recipes {
+                        let crates = deps.recipes.entry(recipe.to_string()).or_default();
+                        if !crates.iter().any(|name| name == krate) {
+                            crates.push(krate.to_string());
+                        }
+                    }
+                }
+                None => deps.unknown.push(krate.to_string()),
+            }
+        }
+        deps.unknown.sort();
+        deps.unknown.dedup();
+        deps
+    }
+
+    /// the comment saying which crate brought in which recipe, empty if
+    /// there are none
+    pub fn comment(&self) -> String {
+        if self.recipes.is_empty() {
+            return String::new();
+        }
+        format!(
+            "# native libraries of -sys crates: {}\n",
+            self.recipes
+                .iter()
+                .map(|(recipe, crates)| format!("{} ({})", recipe, crates.join(", ")))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builtin() {
+        let deps = SysDepends::new(
+            &SysDepsMap::default(),
+            [
+                "openssl-sys",
+                "openssl",
+                "libz-sys",
+                "glib-sys",
+                "gio-sys",
+                "windows-sys",
+                "foo-sys",
+            ],
+        );
+        assert_eq!(
+            deps.recipes.keys().collect::<Vec<_>>(),
+            vec!["glib-2.0", "openssl", "zlib"]
+        );
+        assert_eq!(deps.unknown, vec!["foo-sys"]);
+        assert_eq!(
+            deps.comment(),
+            "# native libraries of -sys crates: glib-2.0 (glib-sys, gio-sys), \
+             openssl (openssl-sys), zlib (libz-sys)\n"
+        );
+    }
+
+    #[test]
+    fn extended() {
+        let mut map = SysDepsMap::default();
+        map.extend("foo-sys = \"libfoo libbar\"\nopenssl-sys = \"\"\n")
+            .unwrap();
+        let deps = SysDepends::new(&map, ["foo-sys", "openssl-sys"]);
+        assert_eq!(
+            deps.recipes.keys().collect::<Vec<_>>(),
+            vec!["libbar", "libfoo"]
+        );
+        assert!(deps.unknown.is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Runtime rendering of recipe templates.
+//!
+//! Templates use the same syntax as `format!`: `{field}` is replaced by
+//! the value of `field` and `{{`/`}}` produce literal braces. Fields of
+//! nested objects are addressed with a dot, e.g. `{workspace.members}`.
+//! A template can assert the context version it was written against with
+//! `{requires_context_version:N}`, which renders to nothing and fails if
+//! the context has changed incompatibly.
+
+use anyhow::bail;
+use cargo::util::CargoResult;
+use std::collections::BTreeMap;
+
+/// Version of the render context. This is bumped whenever a field is
+/// removed or changes meaning so user templates can detect it.
+pub const CONTEXT_VERSION: u32 = 1;
+
+/// The built-in recipe template
+pub const DEFAULT_TEMPLATE: &str = include_str!("bitbake.template");
+
+/// The versioned recipe written with `--split-inc`
+pub const SPLIT_BB_TEMPLATE: &str = include_str!("split.bb.template");
+
+/// The include file shared by all versions, written with `--split-inc`
+pub const SPLIT_INC_TEMPLATE: &str = include_str!("split.inc.template");
+
+/// The crate list and checksums, written with `--crates-inc`
+pub const CRATES_INC_TEMPLATE: &str = include_str!("crates.inc.template");
+
+/// The append updating an existing recipe, written with `--bbappend`
+pub const BBAPPEND_TEMPLATE: &str = include_str!("bbappend.template");
+
+/// `{crates_inc}` with `--crates-inc`
+pub const CRATES_INC_REQUIRE: &str = "require ${BPN}-crates.inc\n";
+
+/// directive a template uses to assert the context version
+const REQUIRES_VERSION: &str = "requires_context_version:";
+
+/// Information about the workspace layout, exposed to templates as the
+/// `workspace` object
+#[derive(Debug, Default)]
+pub struct WorkspaceLayout {
+    /// `workspace.root_name`: name of the directory at the workspace root
+    pub root_name: String,
+    /// `workspace.member_dir`: directory of the package relative to the
+    /// workspace root, empty for the root package
+    pub member_dir: String,
+    /// `workspace.members`: space separated names of the workspace members
+    pub members: Vec<String>,
+    /// `workspace.is_virtual`: `true` if the root manifest has no package
+    pub is_virtual: bool,
+    /// `workspace.bin_targets`: space separated names of the binaries built
+    pub bin_targets: Vec<String>,
+}
+
+/// The values available to a template
+#[derive(Debug)]
+pub struct Context {
+    values: BTreeMap<String, String>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        let mut ctx = Self {
+            values: BTreeMap::new(),
+        };
+        ctx.set("template_context_version", CONTEXT_VERSION);
+        ctx
+    }
+
+    /// sets the value of `field`
+    pub fn set<V: ToString>(&mut self, field: &str, value: V) {
+        self.values.insert(field.to_string(), value.to_string());
+    }
+
+    /// exposes `layout` as the `workspace` object
+    pub fn set_workspace(&mut self, layout: &WorkspaceLayout) {
+        self.set("workspace.root_name", &layout.root_name);
+        self.set("workspace.member_dir", &layout.member_dir);
+        self.set("workspace.members", layout.members.join(" "));
+        self.set("workspace.is_virtual", layout.is_virtual);
+        self.set("workspace.bin_targets", layout.bin_targets.join(" "));
+    }
+
+    /// renders `template` with the values of this context
+    pub fn render(&self, template: &str) -> CargoResult<String> {
+        let mut out = String::with_capacity(template.len());
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    out.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    out.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => bail!("unterminated placeholder '{{{}' in template", field),
+                        }
+                    }
+                    out.push_str(&self.lookup(field.trim())?);
+                }
+                '}' => bail!("unmatched '}}' in template"),
+                c => out.push(c),
+            }
+        }
+        Ok(out)
+    }
+
+    fn lookup(&self, field: &str) -> CargoResult<String> {
+        if let Some(version) = field.strip_prefix(REQUIRES_VERSION) {
+            let version = version.trim();
+            if version != CONTEXT_VERSION.to_string() {
+                bail!(
+                    "template requires context version {} but cargo-bitbake provides version {}",
+                    version,
+                    CONTEXT_VERSION
+                );
+            }
+            return Ok(String::new());
+        }
+
+        match self.values.get(field) {
+            Some(value) => Ok(value.clone()),
+            None => bail!("unknown placeholder '{{{}}}' in template", field),
+        }
+    }
+}
+
+/// makes `value` safe to put between the double quotes of an assignment:
+/// whitespace including newlines is collapsed, double quotes become single
+/// ones, `${` is kept from being expanded and a trailing backslash from
+/// continuing the line
+pub fn escape(value: &str) -> String {
+    value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('"', "'")
+        .replace("${", "$\\{")
+        .trim_end_matches('\\')
+        .trim_end()
+        .to_string()
+}
+
+/// strips the comments and blank lines from a rendered recipe, starting
+/// it with `marker` instead
+pub fn compact(rendered: &str, marker: &str) -> String {
+    let mut out = format!("{}\n", marker);
+    for line in rendered.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            escape("  A \"fast\" JSON parser\nwith\tzero copies \\"),
+            "A 'fast' JSON parser with zero copies"
+        );
+        assert_eq!(escape("C:\\path"), "C:\\path");
+        assert_eq!(escape("${@os.system('x')}"), "$\\{@os.system('x')}");
+        assert_eq!(escape("\n\n"), "");
+    }
+
+    #[test]
+    fn substitution() {
+        let mut ctx = Context::new();
+        ctx.set("name", "foo");
+        assert_eq!(
+            ctx.render("PN = \"{name}\" S = \"${{WORKDIR}}\"").unwrap(),
+            "PN = \"foo\" S = \"${WORKDIR}\""
+        );
+    }
+
+    #[test]
+    fn unknown_placeholder() {
+        let err = Context::new().render("{bogus}").unwrap_err();
+        assert!(err.to_string().contains("{bogus}"));
+    }
+
+    #[test]
+    fn unterminated_placeholder() {
+        assert!(Context::new().render("{name").is_err());
+    }
+
+    #[test]
+    fn context_version() {
+        let ctx = Context::new();
+        assert_eq!(ctx.render("{requires_context_version:1}").unwrap(), "");
+        assert!(ctx.render("{requires_context_version:2}").is_err());
+    }
+
+    #[test]
+    fn compact_recipe() {
+        let rendered = "# header\n#\ninherit cargo\n\n  # indented\nSRC_URI += \" \\\n    crate://crates.io/foo/1.0.0 \\\n\"\n\n\nLICENSE = \"MIT\"\n";
+        assert_eq!(
+            compact(rendered, "# marker"),
+            "# marker\ninherit cargo\nSRC_URI += \" \\\n    crate://crates.io/foo/1.0.0 \\\n\"\nLICENSE = \"MIT\"\n"
+        );
+    }
+
+    #[test]
+    fn example_workspace_template() {
+        let mut ctx = Context::new();
+        ctx.set("name", "agent");
+        ctx.set("version", "1.2.3");
+        ctx.set_workspace(&WorkspaceLayout {
+            root_name: "monorepo".into(),
+            member_dir: "rust/agent".into(),
+            members: vec!["agent".into(), "common".into()],
+            is_virtual: true,
+            bin_targets: vec!["agent".into(), "agentctl".into()],
+        });
+        let out = ctx
+            .render(include_str!("../templates/workspace.bb.template"))
+            .unwrap();
+        assert!(out.contains("# workspace: monorepo (virtual: true)"));
+        assert!(out.contains("# members: agent common"));
+        assert!(out.contains("CARGO_SRC_DIR = \"rust/agent\""));
+        assert!(out.contains("# binaries: agent agentctl"));
+        assert!(out.contains("PV = \"1.2.3\""));
+    }
+}
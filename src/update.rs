@@ -0,0 +1,651 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Refreshing the generated parts of an existing recipe.
+//!
+//! The dependency section of a recipe is wrapped in marker comments so
+//! that `--update` can replace it while keeping everything around it.
+//! SRC_URI entries inside the section which cargo-bitbake did not
+//! generate, e.g. `file://` patches, are carried over. `cargo bitbake
+//! update` also handles recipes written before the markers existed.
+
+use crate::provenance;
+use crate::src_uri::{SrcUri, CRATES_IO_DOWNLOAD};
+use anyhow::anyhow;
+use cargo::util::CargoResult;
+use std::ops::Range;
+
+/// first line of the section replaced by `--update`
+pub const BEGIN_MARKER: &str = "# BEGIN cargo-bitbake managed block";
+/// last line of the section replaced by `--update`
+pub const END_MARKER: &str = "# END cargo-bitbake managed block";
+
+/// first line of a multi-line SRC_URI assignment
+pub const SRC_URI_START: &str = "SRC_URI += \" \\";
+/// prefix of the comment recording a preserved entry
+const PRESERVED_COMMENT: &str = "# preserved by cargo-bitbake --update:";
+
+/// The result of updating a recipe
+#[derive(Debug, Default)]
+pub struct Update {
+    pub contents: String,
+    /// foreign SRC_URI entries carried over from the existing recipe
+    pub preserved: Vec<String>,
+    /// foreign SRC_URI entries dropped because of `--prune-foreign`
+    pub pruned: Vec<String>,
+}
+
+/// replaces the managed block of `existing` with the one of `fresh`,
+/// keeping foreign SRC_URI entries unless `prune_foreign` is set. A
+/// `compact` recipe has no markers and is managed as a whole.
+pub fn update(
+    existing: &str,
+    fresh: &str,
+    compact: bool,
+    prune_foreign: bool,
+) -> CargoResult<Update> {
+    let (old_range, new_range) = if compact {
+        (0..existing.len(), 0..fresh.len())
+    } else {
+        let old_range = managed_block(existing).ok_or_else(|| {
+            anyhow!(
+                "unable to find the '{}' and '{}' markers in the existing recipe, \
+                 regenerate it without --update once to add them",
+                BEGIN_MARKER,
+                END_MARKER
+            )
+        })?;
+        let new_range = managed_block(fresh)
+            .ok_or_else(|| anyhow!("the template does not contain the managed block markers"))?;
+        (old_range, new_range)
+    };
+
+    let old_block = &existing[old_range.clone()];
+    let new_block = &fresh[new_range.clone()];
+    let old_entries = src_uri_entries(old_block);
+    let new_entries = src_uri_entries(new_block);
+
+    // the URLs cargo-bitbake is responsible for
+    let mut known = new_entries
+        .iter()
+        .map(|entry| SrcUri::parse(entry).url)
+        .collect::<Vec<_>>();
+    known.extend(single_src_uris(fresh));
+
+    let mut update = Update::default();
+    let merged = merge_entries(
+        &old_entries,
+        &new_entries,
+        &known,
+        prune_foreign,
+        &mut update,
+    );
+
+    let src_uri = |block: &mut String| {
+        for entry in &update.preserved {
+            block.push_str(&format!("{} {}\n", PRESERVED_COMMENT, entry));
+        }
+        block.push_str(SRC_URI_START);
+        block.push('\n');
+        for entry in &merged {
+            block.push_str(&format!("    {} \\\n", entry));
+        }
+    };
+    let mut block = String::with_capacity(new_block.len());
+    let mut lines = new_block.lines();
+    // without generated entries the fresh block has no assignment, but the
+    // foreign ones still need one, right after the marker
+    if !compact && !merged.is_empty() && !new_block.lines().any(|line| line == SRC_URI_START) {
+        if let Some(marker) = lines.next() {
+            block.push_str(marker);
+            block.push('\n');
+        }
+        src_uri(&mut block);
+        block.push_str("\"\n");
+    }
+    while let Some(line) = lines.next() {
+        if line != SRC_URI_START {
+            block.push_str(line);
+            block.push('\n');
+            continue;
+        }
+
+        src_uri(&mut block);
+        // skip the entries of the fresh block up to the closing quote
+        for line in lines.by_ref() {
+            if line.trim_start().starts_with('"') {
+                block.push_str(line);
+                block.push('\n');
+                break;
+            }
+        }
+    }
+
+    let mut contents = String::with_capacity(existing.len());
+    contents.push_str(&existing[..old_range.start]);
+    contents.push_str(&block);
+    contents.push_str(&existing[old_range.end..]);
+    if !compact {
+        contents = replace_provenance(&contents, fresh);
+    }
+    update.contents = contents;
+    Ok(update)
+}
+
+/// Like [`update`], but also for recipes without the managed block
+/// markers: the generated SRC_URI assignment is then located by its
+/// entries and it, the checksum, `SRCREV_*` and `EXTRA_OECARGO_PATHS`
+/// lines of the dependencies are replaced while every other line is kept
+/// as is. The project's `SRCREV` and `PV` suffix are refreshed as well.
+pub fn refresh(existing: &str, fresh: &str, prune_foreign: bool) -> CargoResult<Update> {
+    let mut update = if managed_block(existing).is_some() && managed_block(fresh).is_some() {
+        update(existing, fresh, false, prune_foreign)?
+    } else {
+        refresh_unmarked(existing, fresh, prune_foreign)?
+    };
+    update.contents = refresh_project_rev(&update.contents, fresh);
+    Ok(update)
+}
+
+/// [`refresh`] for a recipe without markers
+fn refresh_unmarked(existing: &str, fresh: &str, prune_foreign: bool) -> CargoResult<Update> {
+    let new_entries = src_uri_entries(fresh);
+    let mut known = new_entries
+        .iter()
+        .map(|entry| SrcUri::parse(entry).url)
+        .collect::<Vec<_>>();
+    known.extend(single_src_uris(fresh));
+
+    let lines = existing.split_inclusive('\n').collect::<Vec<_>>();
+    let blocks = src_uri_blocks(&lines)
+        .into_iter()
+        .filter(|block| {
+            src_uri_entries(&lines[block.clone()].concat())
+                .iter()
+                .any(|entry| is_generated(entry, &known))
+        })
+        .collect::<Vec<_>>();
+    let block = match blocks.as_slice() {
+        [block] => block.clone(),
+        _ => {
+            return Err(anyhow!(
+                "unable to find {} SRC_URI assignment listing the dependencies, \
+                 regenerate the recipe once to add the '{}' and '{}' markers",
+                if blocks.is_empty() { "the" } else { "a single" },
+                BEGIN_MARKER,
+                END_MARKER
+            ))
+        }
+    };
+    let old_entries = src_uri_entries(&lines[block.clone()].concat());
+
+    let mut update = Update::default();
+    let merged = merge_entries(
+        &old_entries,
+        &new_entries,
+        &known,
+        prune_foreign,
+        &mut update,
+    );
+
+    // the lines tied to the dependencies, old or new
+    let generated = old_entries
+        .iter()
+        .chain(new_entries.iter())
+        .filter(|entry| is_generated(entry, &known))
+        .map(|entry| SrcUri::parse(entry))
+        .collect::<Vec<_>>();
+    let fresh_extras = fresh
+        .split_inclusive('\n')
+        .filter(|line| is_dependency_line(line, &generated))
+        .collect::<Vec<_>>();
+
+    let mut src_uri = vec![lines[block.start].to_string()];
+    src_uri.extend(merged.iter().map(|entry| format!("    {} \\\n", entry)));
+    src_uri.push(lines[block.end - 1].to_string());
+
+    let mut contents = String::with_capacity(existing.len());
+    let mut extras_at = None;
+    let mut after_block = 0;
+    for (idx, line) in lines.iter().enumerate() {
+        if idx == block.start {
+            contents.extend(src_uri.drain(..));
+            after_block = contents.len();
+        } else if block.contains(&idx) {
+            continue;
+        } else if is_dependency_line(line, &generated) {
+            // the fresh lines go where the first of the old ones was
+            extras_at.get_or_insert(contents.len());
+        } else {
+            contents.push_str(line);
+        }
+    }
+    contents.insert_str(extras_at.unwrap_or(after_block), &fresh_extras.concat());
+
+    update.contents = replace_provenance(&contents, fresh);
+    Ok(update)
+}
+
+/// merges the SRC_URI entries of an existing recipe into the generated
+/// ones, recording the foreign entries kept or dropped in `update`
+fn merge_entries(
+    old_entries: &[String],
+    new_entries: &[String],
+    known: &[String],
+    prune_foreign: bool,
+    update: &mut Update,
+) -> Vec<String> {
+    let mut merged = new_entries.to_vec();
+    // where the next foreign entry goes, right after the generated entry
+    // preceding it in the existing recipe
+    let mut insert_at = 0;
+    for entry in old_entries {
+        if is_generated(entry, known) {
+            if let Some(pos) = merged.iter().position(|e| e == entry) {
+                insert_at = pos + 1;
+            }
+        } else if prune_foreign {
+            update.pruned.push(entry.clone());
+        } else {
+            merged.insert(insert_at, entry.clone());
+            insert_at += 1;
+            update.preserved.push(entry.clone());
+        }
+    }
+    merged
+}
+
+/// the line ranges of the multi-line SRC_URI assignments in `lines`,
+/// including the opening and closing lines
+fn src_uri_blocks(lines: &[&str]) -> Vec<Range<usize>> {
+    let mut blocks = vec![];
+    let mut start = None;
+    for (idx, line) in lines.iter().enumerate() {
+        let line = line.trim_end_matches('\n');
+        if line == SRC_URI_START {
+            start = Some(idx);
+        } else if let Some(begin) = start {
+            if line.trim_start().starts_with('"') {
+                blocks.push(begin..idx + 1);
+                start = None;
+            }
+        }
+    }
+    blocks
+}
+
+/// whether `line` is a checksum, `SRCREV` or `EXTRA_OECARGO_PATHS` line
+/// belonging to one of the dependency entries in `entries`
+fn is_dependency_line(line: &str, entries: &[SrcUri]) -> bool {
+    let line = line.trim_end();
+    entries.iter().any(|uri| {
+        let name = match uri.param("name") {
+            Some(name) => name.to_string(),
+            // the crate fetcher names its entries <name>-<version>
+            None => uri
+                .url
+                .strip_prefix("crate://")
+                .and_then(|rest| rest.split_once('/'))
+                .map(|(_, crate_version)| crate_version.replace('/', "-"))
+                .unwrap_or_default(),
+        };
+        if !name.is_empty()
+            && (line.starts_with(&format!("SRC_URI[{}.", name))
+                || line.starts_with(&format!("SRCREV_{} ", name))
+                || line == format!("SRCREV_FORMAT .= \"_{}\"", name))
+        {
+            return true;
+        }
+        // the checkout itself or a crate within it
+        uri.param("destsuffix").map_or(false, |destsuffix| {
+            line.strip_prefix(&format!(
+                "EXTRA_OECARGO_PATHS += \"${{WORKDIR}}/{}",
+                destsuffix
+            ))
+            .map_or(false, |rest| {
+                rest == "\"" || (rest.starts_with('/') && rest.ends_with('"'))
+            })
+        })
+    })
+}
+
+/// whether `line` sets the project's `SRCREV` or `PV` suffix
+fn is_project_rev(line: &str) -> bool {
+    line.starts_with("SRCREV = \"")
+        || line.starts_with("PV:append = \".AUTOINC+")
+        || line.starts_with("PV_append = \".AUTOINC+")
+        || line.starts_with("PV:append = \"+git\"")
+        || line.starts_with("PV_append = \"+git\"")
+}
+
+/// replaces the project's `SRCREV` and `PV` suffix of `recipe` with the ones
+/// in `fresh`, adding the suffix after `SRCREV` if the project is no longer
+/// at a tag and dropping it once it is
+fn refresh_project_rev(recipe: &str, fresh: &str) -> String {
+    let fresh_srcrev = fresh.lines().find(|line| line.starts_with("SRCREV = \""));
+    let fresh_pv = fresh
+        .lines()
+        .find(|line| is_project_rev(line) && !line.starts_with("SRCREV"));
+    let has_pv = recipe
+        .lines()
+        .any(|line| is_project_rev(line) && !line.starts_with("SRCREV"));
+
+    let mut out = String::with_capacity(recipe.len());
+    for line in recipe.split_inclusive('\n') {
+        let newline = if line.ends_with('\n') { "\n" } else { "" };
+        if !is_project_rev(line) {
+            out.push_str(line);
+        } else if line.starts_with("SRCREV") {
+            out.push_str(fresh_srcrev.unwrap_or(line.trim_end_matches('\n')));
+            out.push_str(newline);
+            if let (false, Some(pv)) = (has_pv, fresh_pv) {
+                out.push_str(pv);
+                out.push('\n');
+            }
+        } else if let Some(pv) = fresh_pv {
+            out.push_str(pv);
+            out.push_str(newline);
+        }
+    }
+    out
+}
+
+/// the byte range of the managed block, including both markers
+fn managed_block(recipe: &str) -> Option<Range<usize>> {
+    let start = line_start(recipe, BEGIN_MARKER)?;
+    let end = start + line_start(&recipe[start..], END_MARKER)?;
+    let end = recipe[end..]
+        .find('\n')
+        .map_or(recipe.len(), |pos| end + pos + 1);
+    Some(start..end)
+}
+
+/// the offset of the first line starting with `prefix`
+fn line_start(text: &str, prefix: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.starts_with(prefix) {
+            return Some(offset);
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// the entries of the multi-line SRC_URI assignments in `block`
+fn src_uri_entries(block: &str) -> Vec<String> {
+    let mut entries = vec![];
+    let mut in_src_uri = false;
+    for line in block.lines() {
+        if line == SRC_URI_START {
+            in_src_uri = true;
+        } else if in_src_uri {
+            let entry = line.trim().trim_end_matches('\\').trim();
+            if entry == "\"" || line.trim_start().starts_with('"') {
+                in_src_uri = false;
+            } else if !entry.is_empty() {
+                entries.push(entry.to_string());
+            }
+        }
+    }
+    entries
+}
+
+/// the entries of the multi-line SRC_URI assignments in `recipe` that look
+/// like dependencies, leaving out the project itself, patches and the like
+pub fn dependency_entries(recipe: &str) -> Vec<String> {
+    src_uri_entries(recipe)
+        .into_iter()
+        .filter(|entry| is_generated(entry, &[]))
+        .collect()
+}
+
+/// the URLs of single line `SRC_URI += "..."` assignments in `recipe`
+fn single_src_uris(recipe: &str) -> Vec<String> {
+    recipe
+        .lines()
+        .filter_map(|line| line.strip_prefix("SRC_URI += \""))
+        .filter_map(|rest| rest.strip_suffix('"'))
+        .filter(|uri| !uri.trim().is_empty())
+        .map(|uri| SrcUri::parse(uri.trim()).url)
+        .collect()
+}
+
+/// whether `entry` was generated by cargo-bitbake, either because it is
+/// still generated or because it looks like a dependency that was removed
+fn is_generated(entry: &str, known: &[String]) -> bool {
+    let uri = SrcUri::parse(entry);
+    uri.url.starts_with("crate://")
+        || uri.url.starts_with(CRATES_IO_DOWNLOAD)
+        || known.contains(&uri.url)
+        || ((uri.url.starts_with("git://") || uri.url.starts_with("gitsm://"))
+            && uri.param("name").is_some()
+            && uri.param("destsuffix").is_some())
+}
+
+/// replaces the provenance block of `recipe` with the one in `fresh`
+fn replace_provenance(recipe: &str, fresh: &str) -> String {
+    let (old, new) = match (provenance_lines(recipe), provenance_lines(fresh)) {
+        (Some(old), Some(new)) => (old, new),
+        _ => return recipe.to_string(),
+    };
+    let mut lines = recipe.lines().collect::<Vec<_>>();
+    lines.splice(old, fresh.lines().skip(new.start).take(new.len()));
+
+    let mut out = lines.join("\n");
+    if recipe.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// the range of lines making up the provenance block
+fn provenance_lines(recipe: &str) -> Option<Range<usize>> {
+    let lines = recipe.lines().collect::<Vec<_>>();
+    let start = lines
+        .iter()
+        .position(|line| line.trim_end() == provenance::BLOCK_START)?;
+    let len = lines[start + 1..]
+        .iter()
+        .take_while(|line| line.starts_with("#   "))
+        .count();
+    Some(start..start + 1 + len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn recipe(provenance: &str, entries: &[&str], extras: &str, tail: &str) -> String {
+        let mut recipe = format!(
+            "# Auto-Generated by cargo-bitbake\n#\n{}\n#   version: 1\n#\ninherit cargo\n\
+             SRC_URI += \"git://example.com/app.git;protocol=https\"\n{}\nSRC_URI += \" \\\n",
+            provenance::BLOCK_START,
+            BEGIN_MARKER
+        );
+        for entry in entries {
+            recipe.push_str(&format!("    {} \\\n", entry));
+        }
+        recipe.push_str(&format!("\"\n\n{}\n{}\n{}", extras, END_MARKER, tail));
+        recipe.replace("version: 1", provenance)
+    }
+
+    #[test]
+    fn dependency_lines() {
+        let entries = [SrcUri::parse(
+            "git://github.com/tokio-rs/tokio.git;nobranch=1;name=tokio-util;destsuffix=tokio-util",
+        )];
+        for line in [
+            "SRCREV_tokio-util = \"abc\"",
+            "EXTRA_OECARGO_PATHS += \"${WORKDIR}/tokio-util\"",
+            "EXTRA_OECARGO_PATHS += \"${WORKDIR}/tokio-util/tokio-util\"",
+        ] {
+            assert!(is_dependency_line(line, &entries), "{}", line);
+        }
+        assert!(!is_dependency_line(
+            "EXTRA_OECARGO_PATHS += \"${WORKDIR}/tokio-util-fork\"",
+            &entries
+        ));
+    }
+
+    #[test]
+    fn keeps_foreign_entries_in_place() {
+        let existing = recipe(
+            "version: 1",
+            &[
+                "crate://crates.io/a/1.0.0",
+                "file://0001-fix.patch",
+                "crate://crates.io/b/1.0.0",
+                "git://old.example.com/gone.git;name=gone;destsuffix=gone",
+            ],
+            "SRC_URI[a-1.0.0.sha256sum] = \"aaa\"",
+            "do_install:append() {\n}\n",
+        );
+        let fresh = recipe(
+            "version: 2",
+            &["crate://crates.io/a/1.0.0", "crate://crates.io/b/2.0.0"],
+            "SRC_URI[a-1.0.0.sha256sum] = \"aaa\"\nSRC_URI[b-2.0.0.sha256sum] = \"bbb\"",
+            "",
+        );
+
+        let update = update(&existing, &fresh, false, false).unwrap();
+        assert_eq!(update.preserved, vec!["file://0001-fix.patch"]);
+        assert!(update.pruned.is_empty());
+
+        let mut expected = recipe(
+            "version: 2",
+            &[
+                "crate://crates.io/a/1.0.0",
+                "file://0001-fix.patch",
+                "crate://crates.io/b/2.0.0",
+            ],
+            "SRC_URI[a-1.0.0.sha256sum] = \"aaa\"\nSRC_URI[b-2.0.0.sha256sum] = \"bbb\"",
+            "do_install:append() {\n}\n",
+        );
+        expected = expected.replace(
+            "SRC_URI += \" \\\n",
+            "# preserved by cargo-bitbake --update: file://0001-fix.patch\nSRC_URI += \" \\\n",
+        );
+        assert_eq!(update.contents, expected);
+    }
+
+    #[test]
+    fn prune_foreign() {
+        let existing = recipe(
+            "version: 1",
+            &["file://0001-fix.patch", "crate://crates.io/a/1.0.0"],
+            "",
+            "",
+        );
+        let fresh = recipe("version: 1", &["crate://crates.io/a/1.0.0"], "", "");
+        let update = update(&existing, &fresh, false, true).unwrap();
+        assert_eq!(update.pruned, vec!["file://0001-fix.patch"]);
+        assert_eq!(update.contents, fresh);
+    }
+
+    #[test]
+    fn foreign_entries_without_generated_ones() {
+        let existing = recipe(
+            "version: 1",
+            &["crate://crates.io/a/1.0.0", "file://0001-fix.patch"],
+            "",
+            "",
+        );
+        // every crate went to the crates file
+        let fresh = recipe("version: 1", &[], "", "").replace("SRC_URI += \" \\\n\"\n", "");
+        let update = update(&existing, &fresh, false, false).unwrap();
+        assert_eq!(update.preserved, vec!["file://0001-fix.patch"]);
+        assert_eq!(
+            update.contents,
+            recipe("version: 1", &["file://0001-fix.patch"], "", "").replace(
+                "SRC_URI += \" \\\n",
+                "# preserved by cargo-bitbake --update: file://0001-fix.patch\nSRC_URI += \" \\\n",
+            )
+        );
+        assert_eq!(
+            super::update(&recipe("version: 1", &[], "", ""), &fresh, false, false)
+                .unwrap()
+                .contents,
+            fresh
+        );
+    }
+
+    #[test]
+    fn missing_markers() {
+        let fresh = recipe("version: 1", &[], "", "");
+        let err = update("inherit cargo\n", &fresh, false, false).unwrap_err();
+        assert!(err.to_string().contains(BEGIN_MARKER));
+    }
+
+    #[test]
+    fn refresh_without_markers() {
+        let existing = "inherit cargo\n\
+                        SRCREV = \"1111111111111111111111111111111111111111\"\n\
+                        PV:append = \".AUTOINC+1111111111\"\n\
+                        PACKAGECONFIG ??= \"foo\"\n\
+                        SRC_URI += \" \\\n    crate://crates.io/a/1.0.0 \\\n    file://0001-fix.patch \\\n\"\n\
+                        \n\
+                        SRC_URI[a-1.0.0.sha256sum] = \"aaa\"\n\
+                        SRC_URI[data.sha256sum] = \"ddd\"\n\
+                        do_install:append() {\n}\n";
+        let fresh = recipe(
+            "version: 1",
+            &["crate://crates.io/b/2.0.0"],
+            "SRC_URI[b-2.0.0.sha256sum] = \"bbb\"",
+            "",
+        )
+        .replace(
+            "inherit cargo\n",
+            "inherit cargo\nSRCREV = \"2222222222222222222222222222222222222222\"\n",
+        );
+
+        let update = refresh(existing, &fresh, false).unwrap();
+        assert_eq!(update.preserved, vec!["file://0001-fix.patch"]);
+        assert_eq!(
+            update.contents,
+            "inherit cargo\n\
+             SRCREV = \"2222222222222222222222222222222222222222\"\n\
+             PACKAGECONFIG ??= \"foo\"\n\
+             SRC_URI += \" \\\n    file://0001-fix.patch \\\n    crate://crates.io/b/2.0.0 \\\n\"\n\
+             \n\
+             SRC_URI[b-2.0.0.sha256sum] = \"bbb\"\n\
+             SRC_URI[data.sha256sum] = \"ddd\"\n\
+             do_install:append() {\n}\n"
+        );
+    }
+
+    #[test]
+    fn refresh_ambiguous() {
+        let block = "SRC_URI += \" \\\n    crate://crates.io/a/1.0.0 \\\n\"\n";
+        let fresh = recipe("version: 1", &["crate://crates.io/a/1.0.0"], "", "");
+        let err = refresh(&block.repeat(2), &fresh, false).unwrap_err();
+        assert!(err.to_string().contains("a single SRC_URI assignment"));
+    }
+
+    #[test]
+    fn generated_entries() {
+        let known = vec!["git://example.com/app.git".to_string()];
+        assert!(is_generated("crate://crates.io/a/1.0.0", &known));
+        assert!(is_generated(
+            "git://example.com/app.git;protocol=https",
+            &known
+        ));
+        assert!(is_generated(
+            "git://github.com/a/b.git;protocol=https;name=b;destsuffix=b",
+            &known
+        ));
+        assert!(is_generated(
+            "https://crates.io/api/v1/crates/a/1.0.0/download;name=a-1.0.0.crate",
+            &known
+        ));
+        assert!(!is_generated("file://0001-fix.patch", &known));
+        assert!(!is_generated("https://example.com/data.tar.gz", &known));
+    }
+}
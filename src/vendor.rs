@@ -0,0 +1,219 @@
+/*
+ * Copyright 2016-2017 Doug Goldstein <cardoe@cardoe.com>
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Bundling the dependencies into a single tarball for `--vendor`.
+//!
+//! The tarball has the layout of `cargo vendor --versioned-dirs`: a
+//! `vendor/<name>-<version>` directory with a `.cargo-checksum.json` per
+//! crate and a `.cargo/config.toml` replacing the original sources with
+//! it. Unpacked next to the project's sources, cargo picks the config up
+//! from the parent directory. Entries are sorted and their metadata is
+//! fixed so the same dependencies always produce the same tarball. It is
+//! compressed with gzip rather than xz: flate2 is already built for the
+//! crate archives, xz would add liblzma to the build, and the gzip header
+//! is written without a name or timestamp, which keeps it reproducible.
+
+use crate::archive::ArchiveCache;
+use anyhow::Context as _;
+use cargo::core::{GitReference, Package, PackageId, PackageSet, SourceId};
+use cargo::sources::PathSource;
+use cargo::util::{CargoResult, GlobalContext};
+use flate2::{Compression, GzBuilder};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// directory the crates are unpacked to
+const VENDOR_DIR: &str = "vendor";
+/// name of the source replacing the original ones
+const VENDORED_SOURCES: &str = "vendored-sources";
+
+/// A tarball of the vendored dependencies
+#[derive(Debug)]
+pub struct Tarball {
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+    pub sha256: String,
+}
+
+/// builds the tarball of `packages` for the recipe `name`
+pub fn tarball(
+    config: &GlobalContext,
+    archives: &mut ArchiveCache<'_>,
+    package_set: &PackageSet<'_>,
+    packages: &[PackageId],
+    checksums: &HashMap<PackageId, Option<String>>,
+    name: &str,
+) -> CargoResult<Tarball> {
+    let mut files = BTreeMap::new();
+    let mut sources = BTreeSet::new();
+    for pkg in archives
+        .get_many(package_set, packages, checksums)
+        .context("Unable to download the crates to vendor")?
+    {
+        let id = pkg.package_id();
+        let dir = format!("{}/{}-{}", VENDOR_DIR, id.name(), id.version());
+        let mut file_checksums = BTreeMap::new();
+        for (rel, contents, executable) in package_files(config, pkg)? {
+            file_checksums.insert(rel.clone(), format!("{:x}", Sha256::digest(&contents)));
+            files.insert(format!("{}/{}", dir, rel), (contents, executable));
+        }
+        let checksum = serde_json::json!({
+            "files": file_checksums,
+            "package": checksums.get(&id).cloned().flatten(),
+        });
+        files.insert(
+            format!("{}/.cargo-checksum.json", dir),
+            (checksum.to_string().into_bytes(), false),
+        );
+        sources.insert(id.source_id().without_precise());
+    }
+    files.insert(
+        ".cargo/config.toml".to_string(),
+        (source_replacement(&sources).into_bytes(), false),
+    );
+
+    let mut archive = tar::Builder::new(GzBuilder::new().write(Vec::new(), Compression::best()));
+    for (path, (contents, executable)) in &files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(if *executable { 0o755 } else { 0o644 });
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_entry_type(tar::EntryType::Regular);
+        archive
+            .append_data(&mut header, path, contents.as_slice())
+            .with_context(|| format!("Unable to add '{}' to the vendor tarball", path))?;
+    }
+    let contents = archive
+        .into_inner()
+        .and_then(|gz| gz.finish())
+        .context("Unable to write the vendor tarball")?;
+
+    let sha256 = format!("{:x}", Sha256::digest(&contents));
+    Ok(Tarball {
+        path: PathBuf::from(format!("{}-crates-{}.tar.gz", name, &sha256[..16])),
+        contents,
+        sha256,
+    })
+}
+
+/// the files of `pkg` as cargo vendor copies them: the path relative to
+/// the package, the contents and whether it is executable
+fn package_files(
+    config: &GlobalContext,
+    pkg: &Package,
+) -> CargoResult<Vec<(String, Vec<u8>, bool)>> {
+    let root = pkg.root();
+    let source = PathSource::new(root, pkg.package_id().source_id(), config);
+    let mut files = vec![];
+    for path in source.list_files(pkg)? {
+        let rel = match path.strip_prefix(root) {
+            Ok(rel) => rel,
+            Err(_) => continue,
+        };
+        let rel = rel
+            .iter()
+            .map(|part| part.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if matches!(
+            rel.as_str(),
+            ".gitattributes" | ".gitignore" | ".git" | ".cargo-ok"
+        ) || rel.ends_with(".orig")
+            || rel.ends_with(".rej")
+        {
+            continue;
+        }
+
+        // git dependencies may inherit from their workspace, which isn't
+        // vendored along with them
+        let contents = if rel == "Cargo.toml" && pkg.package_id().source_id().is_git() {
+            pkg.manifest().to_resolved_contents()?.into_bytes()
+        } else {
+            fs::read(&path).with_context(|| format!("Unable to read '{}'", path.display()))?
+        };
+        files.push((rel, contents, is_executable(&path)));
+    }
+    Ok(files)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|meta| meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    false
+}
+
+/// the cargo configuration replacing `sources` with the vendor directory
+fn source_replacement(sources: &BTreeSet<SourceId>) -> String {
+    let mut config = String::new();
+    for source in sources {
+        if source.is_crates_io() {
+            config.push_str("[source.crates-io]\n");
+        } else if source.is_git() {
+            config.push_str(&format!(
+                "[source.\"{}\"]\ngit = \"{}\"\n",
+                source.without_precise().as_url(),
+                source.url()
+            ));
+            match source.git_reference() {
+                Some(GitReference::Branch(branch)) => {
+                    config.push_str(&format!("branch = \"{}\"\n", branch))
+                }
+                Some(GitReference::Tag(tag)) => config.push_str(&format!("tag = \"{}\"\n", tag)),
+                Some(GitReference::Rev(rev)) => config.push_str(&format!("rev = \"{}\"\n", rev)),
+                _ => {}
+            }
+        } else {
+            config.push_str(&format!(
+                "[source.\"{}\"]\nregistry = \"{}\"\n",
+                source.as_url(),
+                source.url()
+            ));
+        }
+        config.push_str(&format!("replace-with = \"{}\"\n\n", VENDORED_SOURCES));
+    }
+    config.push_str(&format!(
+        "[source.{}]\ndirectory = \"{}\"\n",
+        VENDORED_SOURCES, VENDOR_DIR
+    ));
+    config
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cargo::util::IntoUrl;
+
+    #[test]
+    fn git_source_replacement() {
+        let url = "https://github.com/a/b".into_url().unwrap();
+        let source = SourceId::for_git(&url, GitReference::Branch("main".into())).unwrap();
+        let config = source_replacement(&std::iter::once(source).collect());
+        assert_eq!(
+            config,
+            "[source.\"git+https://github.com/a/b?branch=main\"]\n\
+             git = \"https://github.com/a/b\"\n\
+             branch = \"main\"\n\
+             replace-with = \"vendored-sources\"\n\n\
+             [source.vendored-sources]\n\
+             directory = \"vendor\"\n"
+        );
+    }
+}